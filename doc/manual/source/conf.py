@@ -189,8 +189,30 @@ latex_documents = [
 # (source start file, name, description, authors, manual section).
 man_pages = [
     ('man/dnst', 'dnst', 'DNS Management Tools', author, 1),
+    ('man/dnst-compare-zones', 'dnst-compare-zones', 'DNS Management Tools',
+     author, 1),
+    ('man/dnst-completions', 'dnst-completions', 'DNS Management Tools',
+     author, 1),
+    ('man/dnst-install-links', 'dnst-install-links', 'DNS Management Tools',
+     author, 1),
+    ('man/dnst-key2ds', 'dnst-key2ds', 'DNS Management Tools', author, 1),
+    ('man/dnst-keygen', 'dnst-keygen', 'DNS Management Tools', author, 1),
+    ('man/dnst-manpages', 'dnst-manpages', 'DNS Management Tools', author,
+     1),
+    ('man/dnst-notify', 'dnst-notify', 'DNS Management Tools', author, 1),
+    ('man/dnst-nsec3-guess', 'dnst-nsec3-guess', 'DNS Management Tools',
+     author, 1),
     ('man/dnst-nsec3-hash', 'dnst-nsec3-hash', 'DNS Management Tools', author,
      1),
+    ('man/dnst-nsec3-proof', 'dnst-nsec3-proof', 'DNS Management Tools',
+     author, 1),
+    ('man/dnst-query', 'dnst-query', 'DNS Management Tools', author, 1),
+    ('man/dnst-read-zone', 'dnst-read-zone', 'DNS Management Tools', author,
+     1),
+    ('man/dnst-signzone', 'dnst-signzone', 'DNS Management Tools', author, 1),
+    ('man/dnst-update', 'dnst-update', 'DNS Management Tools', author, 1),
+    ('man/dnst-verify-zone', 'dnst-verify-zone', 'DNS Management Tools',
+     author, 1),
 ]
 
 
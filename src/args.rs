@@ -6,18 +6,41 @@ use super::error::Error;
 #[derive(Clone, Debug, clap::Parser)]
 #[command(version, disable_help_subcommand = true)]
 pub struct Args {
+    /// The format of the output
+    #[arg(
+        long = "format",
+        value_name = "FORMAT",
+        global = true,
+        default_value = "text"
+    )]
+    pub format: OutputFormat,
+
     #[command(subcommand)]
     command: Command,
 }
 
+/// The format that commands print their output in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text, like the ldns tools print
+    #[default]
+    Text,
+
+    /// JSON, with one JSON object per line
+    Json,
+}
+
 impl Args {
     pub fn execute(self, env: impl Env) -> Result<(), Error> {
-        self.command.execute(env)
+        self.command.execute(env, self.format)
     }
 }
 
 impl From<Command> for Args {
     fn from(value: Command) -> Self {
-        Args { command: value }
+        Args {
+            format: OutputFormat::Text,
+            command: value,
+        }
     }
 }
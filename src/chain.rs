@@ -0,0 +1,391 @@
+//! Validating responses along the DNSSEC chain of trust.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use domain::base::cmp::CanonicalOrd;
+use domain::base::iana::Rtype;
+use domain::base::name::{ParsedName, ToName};
+use domain::base::rdata::{ComposeRecordData, RecordData};
+use domain::base::{Message, Record};
+use domain::net::client::request::{self, GetResponse, RequestMessage, SendRequest};
+use domain::rdata::dnssec::Timestamp;
+use domain::rdata::{Dnskey, Ds, Rrsig, ZoneRecordData};
+use domain::validate::{DnskeyExt, RrsigExt};
+use domain::validator::anchor::TrustAnchors;
+use domain::validator::context::{ValidationContext, ValidationState};
+
+use crate::error::Error;
+use crate::zone::{ZoneName, ZoneRecord};
+
+/// The trust anchors of the root zone, for KSK-2017 and KSK-2024
+pub const ROOT_ANCHORS: &str = "\
+. IN DS 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D
+. IN DS 38696 8 2 683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16
+";
+
+/// The outcome of validating a response
+pub struct Validation {
+    /// The validation state of the response
+    pub state: ValidationState,
+
+    /// Why the response is not secure, if known
+    pub reason: Option<String>,
+
+    /// The links of the chain of trust, from the trust anchor down
+    pub links: Vec<Link>,
+}
+
+/// A link in the chain of trust, from a DS record to the keys of a zone
+pub struct Link {
+    /// The zone that the link leads to
+    pub zone: ZoneName,
+
+    /// Whether the DS or DNSKEY record is a trust anchor
+    pub anchor: bool,
+
+    /// The key tag of the DS or DNSKEY record, or `None` if the parent
+    /// zone has no DS records for the zone
+    pub key_tag: Option<u16>,
+
+    /// Why the link does not hold, if it does not
+    pub problem: Option<String>,
+}
+
+/// The name of a validation state, as used in the output
+pub fn state_name(state: ValidationState) -> &'static str {
+    match state {
+        ValidationState::Secure => "secure",
+        ValidationState::Insecure => "insecure",
+        ValidationState::Bogus => "bogus",
+        ValidationState::Indeterminate => "indeterminate",
+    }
+}
+
+/// Validate a response, chasing the chain of trust from `anchors`
+///
+/// The anchors must be DS or DNSKEY records. The validator sends its
+/// queries for DS and DNSKEY records to `upstream`. If the validator fails,
+/// e.g. because it cannot parse a response from `upstream`, the state is
+/// indeterminate with the error as the reason.
+pub async fn validate<U>(
+    anchors: &[ZoneRecord],
+    upstream: U,
+    response: &Message<Bytes>,
+) -> Result<Validation, Error>
+where
+    U: SendRequest<RequestMessage<Vec<u8>>>,
+{
+    let mut trust_anchors = TrustAnchors::empty();
+    for record in anchors {
+        if !matches!(record.rtype(), Rtype::DS | Rtype::DNSKEY) {
+            return Err(format!(
+                "trust anchors must be DS or DNSKEY records, but got {}",
+                record.rtype()
+            )
+            .into());
+        }
+        // Not the display of the record itself, which gets the root wrong.
+        let anchor = format!(
+            "{} {} {} {}",
+            record.owner().fmt_with_dot(),
+            record.class(),
+            record.rtype(),
+            record.data()
+        );
+        trust_anchors
+            .add_u8(anchor.as_bytes())
+            .map_err(|_| format!("invalid trust anchor: {anchor}"))?;
+    }
+
+    let responses = Arc::default();
+    let recorder = Recorder {
+        upstream,
+        responses: Arc::clone(&responses),
+    };
+    let context = ValidationContext::new(trust_anchors, recorder);
+
+    let mut msg = response.clone();
+    let (state, reason) = match context.validate_msg::<_, Vec<u8>>(&mut msg).await {
+        Ok((state, ede)) => {
+            let reason = ede.map(|ede| match ede.text_slice() {
+                Some(text) => format!("{}: {}", ede.code(), String::from_utf8_lossy(text)),
+                None => ede.code().to_string(),
+            });
+            (state, reason)
+        }
+        Err(e) => (
+            ValidationState::Indeterminate,
+            Some(format!("could not validate the response: {e}")),
+        ),
+    };
+
+    let rrsets = Rrsets::new(&responses.lock().unwrap());
+    Ok(Validation {
+        state,
+        reason,
+        links: rrsets.links(anchors),
+    })
+}
+
+//------------ Recorder ------------------------------------------------------
+
+/// An upstream for the validator that keeps the responses it receives
+///
+/// The validator does not tell which DS and DNSKEY records it used, so they
+/// are taken from these responses to show the chain of trust.
+struct Recorder<U> {
+    upstream: U,
+    responses: Arc<Mutex<Vec<Message<Bytes>>>>,
+}
+
+impl<U, Octs> SendRequest<RequestMessage<Octs>> for Recorder<U>
+where
+    U: SendRequest<RequestMessage<Octs>>,
+    Octs: AsRef<[u8]>,
+{
+    fn send_request(&self, request: RequestMessage<Octs>) -> Box<dyn GetResponse + Send + Sync> {
+        Box::new(RecordedResponse {
+            inner: self.upstream.send_request(request),
+            responses: Arc::clone(&self.responses),
+        })
+    }
+}
+
+/// A response that is kept by the [`Recorder`] once it arrives
+#[derive(Debug)]
+struct RecordedResponse {
+    inner: Box<dyn GetResponse + Send + Sync>,
+    responses: Arc<Mutex<Vec<Message<Bytes>>>>,
+}
+
+impl GetResponse for RecordedResponse {
+    fn get_response(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Message<Bytes>, request::Error>> + Send + Sync + '_>>
+    {
+        Box::pin(async move {
+            let response = self.inner.get_response().await?;
+            self.responses.lock().unwrap().push(response.clone());
+            Ok(response)
+        })
+    }
+}
+
+//------------ Rrsets --------------------------------------------------------
+
+/// An RRset with the signatures covering it
+struct Signed<D> {
+    records: Vec<Record<ParsedName<Bytes>, D>>,
+    rrsigs: Vec<Rrsig<Bytes, ParsedName<Bytes>>>,
+}
+
+/// The DNSKEY and DS RRsets that the validator received, by owner
+#[derive(Default)]
+struct Rrsets {
+    dnskeys: BTreeMap<ZoneName, Signed<Dnskey<Bytes>>>,
+    ds: BTreeMap<ZoneName, Signed<Ds<Bytes>>>,
+}
+
+impl Rrsets {
+    fn new(responses: &[Message<Bytes>]) -> Self {
+        let mut rrsets = Self::default();
+        for msg in responses {
+            let Some(question) = msg.first_question() else {
+                continue;
+            };
+            let qname: ZoneName = question.qname().to_name();
+            let Ok(answer) = msg.answer() else {
+                continue;
+            };
+
+            let rrsigs: Vec<_> = answer
+                .limit_to::<Rrsig<_, _>>()
+                .flatten()
+                .filter(|r| {
+                    r.owner().name_eq(&qname) && r.data().type_covered() == question.qtype()
+                })
+                .map(|r| r.into_data())
+                .collect();
+            match question.qtype() {
+                Rtype::DNSKEY => {
+                    let records = answer
+                        .limit_to::<Dnskey<_>>()
+                        .flatten()
+                        .filter(|r| r.owner().name_eq(&qname))
+                        .collect();
+                    rrsets
+                        .dnskeys
+                        .entry(qname)
+                        .or_insert(Signed { records, rrsigs });
+                }
+                Rtype::DS => {
+                    let records = answer
+                        .limit_to::<Ds<_>>()
+                        .flatten()
+                        .filter(|r| r.owner().name_eq(&qname))
+                        .collect();
+                    rrsets.ds.entry(qname).or_insert(Signed { records, rrsigs });
+                }
+                _ => {}
+            }
+        }
+        rrsets
+    }
+
+    /// The links of the chain of trust, from the top down
+    fn links(&self, anchors: &[ZoneRecord]) -> Vec<Link> {
+        let mut zones: Vec<&ZoneName> = self
+            .dnskeys
+            .keys()
+            .chain(self.ds.keys())
+            .chain(anchors.iter().map(|r| r.owner()))
+            .collect();
+        zones.sort_by_key(|zone| (zone.label_count(), *zone));
+        zones.dedup();
+
+        let mut links = Vec::new();
+        for zone in zones {
+            let keys = self.dnskeys.get(zone);
+            let zone_anchors: Vec<_> = anchors.iter().filter(|r| r.owner() == zone).collect();
+
+            if !zone_anchors.is_empty() {
+                for anchor in zone_anchors {
+                    let (key_tag, problem) = match anchor.data() {
+                        ZoneRecordData::Ds(ds) => (ds.key_tag(), check_ds(zone, ds, keys).err()),
+                        ZoneRecordData::Dnskey(dnskey) => (
+                            dnskey.key_tag(),
+                            check_key(keys, "the trust anchor", |key| {
+                                key.algorithm() == dnskey.algorithm()
+                                    && key.public_key() == dnskey.public_key()
+                            })
+                            .err(),
+                        ),
+                        _ => continue,
+                    };
+                    links.push(Link {
+                        zone: zone.clone(),
+                        anchor: true,
+                        key_tag: Some(key_tag),
+                        problem,
+                    });
+                }
+                continue;
+            }
+
+            let Some(ds) = self.ds.get(zone) else {
+                continue;
+            };
+            if ds.records.is_empty() {
+                links.push(Link {
+                    zone: zone.clone(),
+                    anchor: false,
+                    key_tag: None,
+                    problem: None,
+                });
+                continue;
+            }
+
+            // The DS records are only as good as the signature of the parent.
+            let parent_keys = self
+                .dnskeys
+                .iter()
+                .filter(|(name, _)| *name != zone && zone.ends_with(*name))
+                .max_by_key(|(name, _)| name.label_count());
+            let ds_problem = match parent_keys {
+                Some((_, parent_keys)) => {
+                    let keys: Vec<_> = parent_keys.records.iter().map(Record::data).collect();
+                    verify_rrset(ds, &keys)
+                        .err()
+                        .map(|e| format!("the DS RRset {e}"))
+                }
+                None => Some("the keys of the parent zone are missing".into()),
+            };
+
+            for record in &ds.records {
+                links.push(Link {
+                    zone: zone.clone(),
+                    anchor: false,
+                    key_tag: Some(record.data().key_tag()),
+                    problem: ds_problem
+                        .clone()
+                        .or_else(|| check_ds(zone, record.data(), keys).err()),
+                });
+            }
+        }
+        links
+    }
+}
+
+/// Check that a DS record matches a key that signed the DNSKEY RRset
+fn check_ds(
+    zone: &ZoneName,
+    ds: &Ds<Bytes>,
+    keys: Option<&Signed<Dnskey<Bytes>>>,
+) -> Result<(), String> {
+    check_key(keys, "the DS record", |key| {
+        key.key_tag() == ds.key_tag()
+            && key.algorithm() == ds.algorithm()
+            && key
+                .digest(zone, ds.digest_type())
+                .is_ok_and(|digest| digest.as_ref() == ds.digest().as_ref())
+    })
+}
+
+/// Check that a key in the DNSKEY RRset is selected and signed the RRset
+fn check_key(
+    keys: Option<&Signed<Dnskey<Bytes>>>,
+    what: &str,
+    selected: impl Fn(&Dnskey<Bytes>) -> bool,
+) -> Result<(), String> {
+    let Some(keys) = keys.filter(|keys| !keys.records.is_empty()) else {
+        return Err("there are no DNSKEY records".into());
+    };
+    let Some(key) = keys
+        .records
+        .iter()
+        .map(Record::data)
+        .find(|key| selected(key))
+    else {
+        return Err(format!("no DNSKEY record matches {what}"));
+    };
+    verify_rrset(keys, &[key]).map_err(|e| format!("the DNSKEY RRset {e}"))
+}
+
+/// Check that an RRset has a valid signature by one of `keys`
+fn verify_rrset<D>(rrset: &Signed<D>, keys: &[&Dnskey<Bytes>]) -> Result<(), String>
+where
+    D: ComposeRecordData + CanonicalOrd + RecordData,
+{
+    if rrset.rrsigs.is_empty() {
+        return Err("is not signed".into());
+    }
+
+    let now = Timestamp::now();
+    let mut problem = "is not signed by the key";
+    for rrsig in &rrset.rrsigs {
+        let matching = keys
+            .iter()
+            .filter(|key| key.key_tag() == rrsig.key_tag() && key.algorithm() == rrsig.algorithm());
+        for key in matching {
+            if now < rrsig.inception() || rrsig.expiration() < now {
+                problem = "has no signature that is valid now";
+                continue;
+            }
+
+            let mut records: Vec<_> = rrset.records.iter().collect();
+            let mut signed_data = Vec::new();
+            rrsig
+                .signed_data(&mut signed_data, &mut records)
+                .expect("infallible");
+            match rrsig.verify_signed_data(*key, &signed_data) {
+                Ok(()) => return Ok(()),
+                Err(_) => problem = "has an invalid signature",
+            }
+        }
+    }
+    Err(problem.into())
+}
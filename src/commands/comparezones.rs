@@ -0,0 +1,384 @@
+use std::cmp::Ordering;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use domain::base::cmp::CanonicalOrd;
+use domain::base::iana::Rtype;
+use domain::base::{Record, Serial, ToName};
+use domain::rdata::{Soa, ZoneRecordData};
+use lexopt::Arg;
+
+use crate::args::OutputFormat;
+use crate::env::{Env, Stream};
+use crate::error::Error;
+use crate::zone::ZoneRecord;
+use crate::zone::{display_record, is_dnssec_type, read_zone_file, record_json, sort_records};
+
+use super::{parse_os, LdnsCommand};
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct CompareZones {
+    /// Print the records that were added
+    #[arg(short = 'i', long = "show-added")]
+    show_added: bool,
+
+    /// Print the records that were removed
+    #[arg(short = 'd', long = "show-removed")]
+    show_removed: bool,
+
+    /// Print the new records of RRsets that were changed
+    #[arg(short = 'c', long = "show-changed")]
+    show_changed: bool,
+
+    /// Print all differences (same as -i -d -c)
+    #[arg(short = 'a', long = "show-all")]
+    show_all: bool,
+
+    /// Also compare the SOA records, which are ignored by default
+    #[arg(short = 's', long = "include-soa")]
+    include_soa: bool,
+
+    /// Ignore the serial when comparing the SOA records (implies --include-soa)
+    #[arg(long = "ignore-serial")]
+    ignore_serial: bool,
+
+    /// Ignore RRSIG, NSEC, NSEC3 and NSEC3PARAM records
+    #[arg(long = "ignore-dnssec")]
+    ignore_dnssec: bool,
+
+    /// The old zone file
+    #[arg(value_name = "OLD_ZONEFILE")]
+    old: PathBuf,
+
+    /// The new zone file
+    #[arg(value_name = "NEW_ZONEFILE")]
+    new: PathBuf,
+}
+
+const LDNS_HELP: &str = "\
+Usage: ldns-compare-zones [-v] [-i] [-d] [-c] [-s] <zonefile1> <zonefile2>
+       Zones are read and compared and the differences printed.
+       -a : print all differences (-i -d -c)
+       -c : show changed records
+       -d : show deleted records
+       -h : show this help
+       -i : show inserted records
+       -s : do not exclude SOA record from comparison
+       -z : sort zone files before comparing (always done)\
+";
+
+impl LdnsCommand for CompareZones {
+    const HELP: &'static str = LDNS_HELP;
+
+    fn parse_ldns<I: IntoIterator<Item = OsString>>(args: I) -> Result<Self, Error> {
+        let mut show_added = false;
+        let mut show_removed = false;
+        let mut show_changed = false;
+        let mut show_all = false;
+        let mut include_soa = false;
+        let mut zonefiles = Vec::new();
+
+        let mut parser = lexopt::Parser::from_args(args);
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Arg::Short('a') => show_all = true,
+                Arg::Short('c') => show_changed = true,
+                Arg::Short('d') => show_removed = true,
+                Arg::Short('i') => show_added = true,
+                Arg::Short('s') => include_soa = true,
+                // The zones are always sorted before comparing them.
+                Arg::Short('z') => {}
+                Arg::Value(val) => {
+                    if zonefiles.len() == 2 {
+                        return Err("Only two zone files can be given".into());
+                    }
+                    zonefiles.push(parse_os("zone file", &val)?);
+                }
+                Arg::Short(x) => return Err(format!("Invalid short option: -{x}").into()),
+                Arg::Long(x) => {
+                    return Err(format!("Long options are not supported, but `--{x}` given").into())
+                }
+            }
+        }
+
+        let [old, new]: [PathBuf; 2] = zonefiles
+            .try_into()
+            .map_err(|_| "Two zone files must be given")?;
+
+        Ok(Self {
+            show_added,
+            show_removed,
+            show_changed,
+            show_all,
+            include_soa,
+            ignore_serial: false,
+            ignore_dnssec: false,
+            old,
+            new,
+        })
+    }
+}
+
+impl CompareZones {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let old = self.load(&env, &self.old)?;
+        let new = self.load(&env, &self.new)?;
+
+        let mut out = env.stdout();
+        let (mut added, mut removed, mut changed) = (0, 0, 0);
+
+        let mut old_rrsets = old.iter().peekable();
+        let mut new_rrsets = new.iter().peekable();
+        loop {
+            let order = match (old_rrsets.peek(), new_rrsets.peek()) {
+                (None, None) => break,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(old), Some(new)) => rrset_cmp(old, new),
+            };
+
+            match order {
+                Ordering::Less => {
+                    let rrset = old_rrsets.next().expect("peeked");
+                    removed += 1;
+                    if self.show_removed || self.show_all {
+                        for record in rrset {
+                            print_change(&mut out, format, Change::Removed, record);
+                        }
+                    }
+                }
+                Ordering::Greater => {
+                    let rrset = new_rrsets.next().expect("peeked");
+                    added += 1;
+                    if self.show_added || self.show_all {
+                        for record in rrset {
+                            print_change(&mut out, format, Change::Added, record);
+                        }
+                    }
+                }
+                Ordering::Equal => {
+                    let old = old_rrsets.next().expect("peeked");
+                    let new = new_rrsets.next().expect("peeked");
+                    if rrset_eq(old, new) {
+                        continue;
+                    }
+                    changed += 1;
+                    if self.show_changed || self.show_all {
+                        for record in new {
+                            print_change(&mut out, format, Change::Changed, record);
+                        }
+                    }
+                }
+            }
+        }
+
+        match format {
+            OutputFormat::Text => writeln!(out, "\t+{added}\t-{removed}\t~{changed}"),
+            OutputFormat::Json => writeln!(
+                out,
+                "{}",
+                serde_json::json!({
+                    "added": added,
+                    "removed": removed,
+                    "changed": changed,
+                })
+            ),
+        }
+        Ok(())
+    }
+
+    /// Read a zone file and group its records into sorted RRsets
+    fn load(&self, env: &impl Env, path: &Path) -> Result<Vec<Vec<ZoneRecord>>, Error> {
+        let mut records = read_zone_file(env, path, None)?;
+
+        let include_soa = self.include_soa || self.ignore_serial;
+        records.retain(|r| {
+            (include_soa || r.rtype() != Rtype::SOA)
+                && !(self.ignore_dnssec && is_dnssec_type(r.rtype()))
+        });
+
+        if self.ignore_serial {
+            for record in &mut records {
+                if let ZoneRecordData::Soa(soa) = record.data() {
+                    let soa = Soa::new(
+                        soa.mname().clone(),
+                        soa.rname().clone(),
+                        Serial(0),
+                        soa.refresh(),
+                        soa.retry(),
+                        soa.expire(),
+                        soa.minimum(),
+                    );
+                    *record = Record::new(
+                        record.owner().clone(),
+                        record.class(),
+                        record.ttl(),
+                        soa.into(),
+                    );
+                }
+            }
+        }
+
+        sort_records(&mut records);
+
+        let mut rrsets: Vec<Vec<ZoneRecord>> = Vec::new();
+        for record in records {
+            match rrsets.last_mut() {
+                Some(rrset) if rrset_cmp(rrset, std::slice::from_ref(&record)).is_eq() => {
+                    rrset.push(record)
+                }
+                _ => rrsets.push(vec![record]),
+            }
+        }
+        Ok(rrsets)
+    }
+}
+
+/// How a record differs between the zones
+#[derive(Clone, Copy)]
+enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Print a record that differs between the zones
+fn print_change(
+    out: &mut Stream<impl fmt::Write>,
+    format: OutputFormat,
+    change: Change,
+    record: &ZoneRecord,
+) {
+    match format {
+        OutputFormat::Text => {
+            let prefix = match change {
+                Change::Added => '+',
+                Change::Removed => '-',
+                Change::Changed => '~',
+            };
+            writeln!(out, "{prefix}{}", display_record(record));
+        }
+        OutputFormat::Json => {
+            let mut json = record_json(record);
+            json["change"] = match change {
+                Change::Added => "added",
+                Change::Removed => "removed",
+                Change::Changed => "changed",
+            }
+            .into();
+            writeln!(out, "{json}");
+        }
+    }
+}
+
+/// Compare RRsets by class, owner and type in canonical order
+fn rrset_cmp(a: &[ZoneRecord], b: &[ZoneRecord]) -> Ordering {
+    let (a, b) = (&a[0], &b[0]);
+    a.class()
+        .cmp(&b.class())
+        .then_with(|| a.owner().name_cmp(b.owner()))
+        .then_with(|| a.rtype().cmp(&b.rtype()))
+}
+
+/// Whether two sorted RRsets have the same TTLs and data
+fn rrset_eq(a: &[ZoneRecord], b: &[ZoneRecord]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| a.ttl() == b.ttl() && a.data().canonical_cmp(b.data()).is_eq())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::env::fake::FakeCmd;
+
+    const OLD: &str = "\
+$ORIGIN example.test.
+$TTL 3600
+@       SOA ns1 hostmaster 2024010101 7200 3600 1209600 300
+@       NS  ns1
+ns1     A   192.0.2.1
+www     A   192.0.2.2
+www     A   192.0.2.3
+old     TXT \"removed\"
+www     RRSIG A 15 3 3600 20300101000000 20240101000000 1234 example.test. AAAA
+";
+
+    const NEW: &str = "\
+$ORIGIN example.test.
+@       3600 SOA ns1 hostmaster 2024010102 7200 3600 1209600 300
+@       3600 NS  ns1
+NS1.example.test. 3600 A 192.0.2.1
+www     3600 A   192.0.2.3
+www     3600 A   192.0.2.4
+new     600  TXT \"added\"
+www     RRSIG A 15 3 3600 20300101000000 20240101000000 1234 example.test. BBBB
+";
+
+    #[test]
+    fn dnst_parse() {
+        let cmd = FakeCmd::new(["dnst", "compare-zones"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["old"]).parse().is_err());
+        assert!(cmd.args(["old", "new"]).parse().is_ok());
+        assert!(cmd
+            .args(["-a", "--ignore-dnssec", "old", "new"])
+            .parse()
+            .is_ok());
+        assert!(cmd.args(["old", "new", "newer"]).parse().is_err());
+    }
+
+    #[test]
+    fn ldns_parse() {
+        let cmd = FakeCmd::new(["ldns-compare-zones"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["old"]).parse().is_err());
+        assert!(cmd
+            .args(["-i", "-d", "-c", "-s", "-z", "old", "new"])
+            .parse()
+            .is_ok());
+        assert!(cmd.args(["old", "new", "newer"]).parse().is_err());
+        assert!(cmd.args(["-x", "old", "new"]).parse().is_err());
+    }
+
+    #[test]
+    fn compare() {
+        let cmd = FakeCmd::new(["dnst", "compare-zones"])
+            .file("old", OLD)
+            .file("new", NEW);
+        let (old, new) = ("old", "new");
+
+        let res = cmd.args([&old, &new]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, "\t+1\t-1\t~2\n");
+
+        let res = cmd.args(["-a", "--ignore-dnssec"]).args([&old, &new]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(
+            res.stdout,
+            "\
++new.example.test.\t600\tIN\tTXT\t\"added\"
+-old.example.test.\t3600\tIN\tTXT\t\"removed\"
+~www.example.test.\t3600\tIN\tA\t192.0.2.3
+~www.example.test.\t3600\tIN\tA\t192.0.2.4
+\t+1\t-1\t~1
+"
+        );
+
+        let res = cmd.args(["-s"]).args([&old, &new]).run();
+        assert_eq!(res.stdout, "\t+1\t-1\t~3\n");
+
+        let res = cmd
+            .args(["--ignore-serial", "--ignore-dnssec"])
+            .args([&old, &new])
+            .run();
+        assert_eq!(res.stdout, "\t+1\t-1\t~1\n");
+
+        let res = cmd.args([&old, &old]).run();
+        assert_eq!(res.stdout, "\t+0\t-0\t~0\n");
+    }
+}
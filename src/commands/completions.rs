@@ -0,0 +1,53 @@
+use clap::CommandFactory;
+use clap_complete::Shell;
+
+use crate::env::Env;
+use crate::error::Error;
+use crate::Args;
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct Completions {
+    /// The shell to generate the completion script for
+    #[arg(value_name = "SHELL")]
+    shell: Shell,
+}
+
+impl Completions {
+    pub fn execute(self, env: impl Env) -> Result<(), Error> {
+        let mut script = Vec::new();
+        clap_complete::generate(self.shell, &mut Args::command(), "dnst", &mut script);
+        let script = String::from_utf8(script).expect("completion scripts are UTF-8");
+        write!(env.stdout(), "{script}");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::env::fake::FakeCmd;
+
+    #[test]
+    fn dnst_parse() {
+        let cmd = FakeCmd::new(["dnst", "completions"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["tcsh"]).parse().is_err());
+        assert!(cmd.args(["bash"]).parse().is_ok());
+    }
+
+    #[test]
+    fn shells() {
+        for (shell, expected) in [
+            ("bash", "_dnst() {"),
+            ("zsh", "#compdef dnst"),
+            ("fish", "complete -c dnst"),
+        ] {
+            let res = FakeCmd::new(["dnst", "completions", shell]).run();
+            assert_eq!(res.exit_code, 0, "{}", res.stderr);
+            assert!(res.stdout.contains(expected), "{shell}");
+            // All commands are completed.
+            assert!(res.stdout.contains("nsec3-hash"), "{shell}");
+            assert!(res.stdout.contains("verify-zone"), "{shell}");
+        }
+    }
+}
@@ -1,13 +1,305 @@
+use std::io::Write as _;
+use std::process::{Command, Stdio};
+
+use crate::args::OutputFormat;
+use crate::env::Env;
 use crate::error::Error;
 
 #[derive(Clone, Debug, clap::Args)]
 pub struct Help {
+    /// The command to show the manual page of [default: dnst itself]
     #[arg(value_name = "COMMAND")]
     command: Option<String>,
 }
 
+/// The manual pages of dnst and its commands, in reStructuredText
+///
+/// These are the same sources that the manual is built from.
+const PAGES: &[(&str, &str)] = &[
+    ("dnst", include_str!("../../doc/manual/source/man/dnst.rst")),
+    (
+        "compare-zones",
+        include_str!("../../doc/manual/source/man/dnst-compare-zones.rst"),
+    ),
+    (
+        "completions",
+        include_str!("../../doc/manual/source/man/dnst-completions.rst"),
+    ),
+    (
+        "install-links",
+        include_str!("../../doc/manual/source/man/dnst-install-links.rst"),
+    ),
+    (
+        "key2ds",
+        include_str!("../../doc/manual/source/man/dnst-key2ds.rst"),
+    ),
+    (
+        "keygen",
+        include_str!("../../doc/manual/source/man/dnst-keygen.rst"),
+    ),
+    (
+        "manpages",
+        include_str!("../../doc/manual/source/man/dnst-manpages.rst"),
+    ),
+    (
+        "notify",
+        include_str!("../../doc/manual/source/man/dnst-notify.rst"),
+    ),
+    (
+        "nsec3-guess",
+        include_str!("../../doc/manual/source/man/dnst-nsec3-guess.rst"),
+    ),
+    (
+        "nsec3-hash",
+        include_str!("../../doc/manual/source/man/dnst-nsec3-hash.rst"),
+    ),
+    (
+        "nsec3-proof",
+        include_str!("../../doc/manual/source/man/dnst-nsec3-proof.rst"),
+    ),
+    (
+        "query",
+        include_str!("../../doc/manual/source/man/dnst-query.rst"),
+    ),
+    (
+        "read-zone",
+        include_str!("../../doc/manual/source/man/dnst-read-zone.rst"),
+    ),
+    (
+        "signzone",
+        include_str!("../../doc/manual/source/man/dnst-signzone.rst"),
+    ),
+    (
+        "update",
+        include_str!("../../doc/manual/source/man/dnst-update.rst"),
+    ),
+    (
+        "verify-zone",
+        include_str!("../../doc/manual/source/man/dnst-verify-zone.rst"),
+    ),
+];
+
+/// The indentation of paragraphs
+const INDENT: &str = "       ";
+
+/// The indentation of the descriptions of options and glossary terms
+const NESTED_INDENT: &str = "              ";
+
 impl Help {
-    pub fn execute(self) -> Result<(), Error> {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let name = self.command.as_deref().unwrap_or("dnst");
+        // Also accept the names of the manual pages themselves.
+        let name = name.strip_prefix("dnst-").unwrap_or(name);
+
+        let Some((_, page)) = PAGES.iter().find(|(n, _)| *n == name) else {
+            let commands: Vec<_> = PAGES[1..].iter().map(|(n, _)| *n).collect();
+            return Err(format!(
+                "no manual page for `{name}`, the available commands are: {}",
+                commands.join(", ")
+            )
+            .into());
+        };
+
+        let text = render(page);
+        if format == OutputFormat::Json {
+            let json = serde_json::json!({ "command": name, "text": text });
+            writeln!(env.stdout(), "{json}");
+            return Ok(());
+        }
+        if env.stdout_is_terminal() && page_text(&text).is_ok() {
+            return Ok(());
+        }
+        write!(env.stdout(), "{text}");
         Ok(())
     }
 }
+
+/// Show text in the pager from `$PAGER`, or `less` if it is not set
+fn page_text(text: &str) -> std::io::Result<()> {
+    let pager = std::env::var("PAGER").unwrap_or_default();
+    let mut pager = pager.split_whitespace();
+    let mut cmd = Command::new(pager.next().unwrap_or("less"));
+    cmd.args(pager).stdin(Stdio::piped());
+    if std::env::var_os("LESS").is_none() {
+        // Quit if the page fits on the screen and don't clear it on exit.
+        cmd.env("LESS", "FRX");
+    }
+
+    let mut child = cmd.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user may quit the pager before reading everything, which is
+        // not an error.
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Render a manual page in reStructuredText as plain text
+///
+/// This only supports the subset of reStructuredText and Sphinx used by our
+/// manual pages: section titles, paragraphs, option and glossary directives
+/// and inline markup.
+fn render(source: &str) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut out = String::new();
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim_end();
+
+        // The title and section titles are underlined. Like in man pages,
+        // they are printed in capitals instead.
+        if let Some(next) = lines.get(i + 1) {
+            if !line.is_empty() && is_underline(next) {
+                out.push_str(&inline(line).to_uppercase());
+                out.push('\n');
+                i += 2;
+                continue;
+            }
+        }
+
+        if line.is_empty() {
+            // Collapse blank lines, so that skipped directives leave no gaps.
+            if !out.is_empty() && !out.ends_with("\n\n") {
+                out.push('\n');
+            }
+        } else if line.trim_start() == ".. glossary::" {
+            // The glossary terms themselves are rendered as paragraphs.
+        } else if let Some(option) = line.trim_start().strip_prefix(".. option::") {
+            out.push_str(INDENT);
+            out.push_str(&inline(option.trim()));
+            out.push('\n');
+        } else {
+            // Glossary terms are indented by 3 and the descriptions of
+            // options and glossary terms by 6 or more.
+            let indent = line.len() - line.trim_start().len();
+            out.push_str(if indent > 3 { NESTED_INDENT } else { INDENT });
+            out.push_str(&inline(line.trim_start()));
+            out.push('\n');
+        }
+        i += 1;
+    }
+
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+/// Whether a line underlines a title
+fn is_underline(line: &str) -> bool {
+    let line = line.trim_end();
+    !line.is_empty() && (line.chars().all(|c| c == '=') || line.chars().all(|c| c == '-'))
+}
+
+/// Strip the inline markup from a line of text
+///
+/// Roles like ``:samp:`text` `` are replaced by their text, except for
+/// ``:doc:`title <page>` ``, which is replaced by its title,
+/// ``:rfc:`number` ``, which becomes "RFC number", and ``:samp:`text` ``,
+/// which loses the braces around its variables.
+fn inline(line: &str) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+
+    while let Some(c) = rest.chars().next() {
+        if let Some((text, after)) = delimited(rest, "``", "``")
+            .or_else(|| delimited(rest, "**", "**"))
+            .or_else(|| delimited(rest, "*", "*"))
+        {
+            out.push_str(text);
+            rest = after;
+        } else if let Some((role, text, after)) = role(rest) {
+            match role {
+                "doc" => out.push_str(text.split(" <").next().unwrap_or(text)),
+                "rfc" => {
+                    out.push_str("RFC ");
+                    out.push_str(text);
+                }
+                // Braces mark the variable parts of a sample.
+                "samp" => out.extend(text.chars().filter(|c| !matches!(c, '{' | '}'))),
+                _ => out.push_str(text),
+            }
+            rest = after;
+        } else {
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    out
+}
+
+/// Split off text enclosed in `open` and `close` at the start of `s`
+///
+/// Returns the enclosed text and the rest of `s`.
+fn delimited<'a>(s: &'a str, open: &str, close: &str) -> Option<(&'a str, &'a str)> {
+    let s = s.strip_prefix(open)?;
+    if s.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let end = s.find(close)?;
+    if end == 0 {
+        return None;
+    }
+    Some((&s[..end], &s[end + close.len()..]))
+}
+
+/// Split off a role like ``:samp:`text` `` at the start of `s`
+///
+/// Returns the name of the role, its text and the rest of `s`.
+fn role(s: &str) -> Option<(&str, &str, &str)> {
+    let s = s.strip_prefix(':')?;
+    let end = s.find(":`")?;
+    let name = &s[..end];
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase()) {
+        return None;
+    }
+    let (text, rest) = delimited(&s[end + 1..], "`", "`")?;
+    Some((name, text, rest))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::env::fake::FakeCmd;
+
+    use super::{render, PAGES};
+
+    #[test]
+    fn help() {
+        let cmd = FakeCmd::new(["dnst", "help"]);
+
+        let res = cmd.run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res.stdout.starts_with("DNST\n\nSYNOPSIS\n"));
+        assert!(res.stdout.contains("\n       dnst-notify (1)\n"));
+
+        let res = cmd.args(["notify"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res.stdout.starts_with("DNST-NOTIFY\n"));
+        assert!(res
+            .stdout
+            .contains("\n       dnst notify [options] -z zone server [server ...]\n"));
+        assert!(res.stdout.contains(
+            "\n       -z domain, --zone=domain\n\n              The zone to send the NOTIFY for."
+        ));
+
+        let res = cmd.args(["dnst-notify"]).run();
+        assert!(res.stdout.starts_with("DNST-NOTIFY\n"));
+
+        let res = cmd.args(["unknown"]).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("no manual page for `unknown`"));
+        assert!(res.stderr.contains("notify"));
+    }
+
+    #[test]
+    fn no_markup_left() {
+        for (name, page) in PAGES {
+            let text = render(page);
+            for markup in ["``", "**", ":samp:", ":doc:", ".. ", "===", "---"] {
+                assert!(!text.contains(markup), "{markup} in page {name}");
+            }
+        }
+    }
+}
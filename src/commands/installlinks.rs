@@ -0,0 +1,241 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::args::OutputFormat;
+use crate::env::Env;
+use crate::error::Error;
+
+use super::LDNS_TOOLS;
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct InstallLinks {
+    /// Create hard links instead of symbolic links
+    #[arg(long = "hard")]
+    hard: bool,
+
+    /// Only print what would be done
+    #[arg(short = 'n', long = "dry-run")]
+    dry_run: bool,
+
+    /// Remove the links instead of creating them
+    #[arg(short = 'u', long = "uninstall")]
+    uninstall: bool,
+
+    /// Also replace or remove files that are not dnst, such as ldns binaries
+    #[arg(short = 'f', long = "force")]
+    force: bool,
+
+    /// The directory to create the links in
+    #[arg(value_name = "DIRECTORY")]
+    directory: PathBuf,
+}
+
+/// What is at the path of a link
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Existing {
+    /// Nothing
+    Nothing,
+
+    /// A link to dnst, or a copy of it
+    Dnst,
+
+    /// Some other file, e.g. a binary of ldns itself
+    Other,
+}
+
+/// A change to make to the directory
+#[derive(Clone, Copy)]
+enum Action {
+    Create,
+    Replace,
+    Remove,
+}
+
+impl InstallLinks {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let exe = env
+            .current_exe()
+            .map_err(|e| format!("could not find the dnst executable: {e}"))?;
+        let dnst = read_file(&env, &exe)?;
+
+        // Decide on all changes first, so that nothing is changed if a file
+        // is in the way.
+        let mut actions = Vec::new();
+        let mut refused = Vec::new();
+        for tool in LDNS_TOOLS {
+            let link = self.directory.join(tool.name);
+            let existing = if !env.exists(&link) {
+                Existing::Nothing
+            } else if read_file(&env, &link).is_ok_and(|contents| contents == dnst) {
+                Existing::Dnst
+            } else {
+                Existing::Other
+            };
+
+            let action = match (existing, self.uninstall) {
+                (Existing::Nothing, false) => Action::Create,
+                (Existing::Dnst, false) | (Existing::Nothing, true) => continue,
+                (Existing::Other, false) if self.force => Action::Replace,
+                (Existing::Dnst, true) => Action::Remove,
+                (Existing::Other, true) if self.force => Action::Remove,
+                (Existing::Other, _) => {
+                    refused.push(link.display().to_string());
+                    continue;
+                }
+            };
+            actions.push((action, link));
+        }
+
+        if !refused.is_empty() {
+            return Err(format!(
+                "refusing to {} {}, which {} not dnst, use --force to do so anyway",
+                if self.uninstall { "remove" } else { "replace" },
+                refused.join(", "),
+                if refused.len() == 1 { "is" } else { "are" },
+            )
+            .into());
+        }
+
+        let mut out = env.stdout();
+        for (action, link) in actions {
+            if !self.dry_run {
+                self.apply(&env, action, &exe, &link)?;
+            }
+
+            let verb = match action {
+                Action::Create => "create",
+                Action::Replace => "replace",
+                Action::Remove => "remove",
+            };
+            match format {
+                OutputFormat::Text if self.dry_run => {
+                    writeln!(out, "would {verb} {}", link.display())
+                }
+                OutputFormat::Text => writeln!(out, "{verb}d {}", link.display()),
+                OutputFormat::Json => writeln!(
+                    out,
+                    "{}",
+                    serde_json::json!({
+                        "action": verb,
+                        "link": link.display().to_string(),
+                        "dry_run": self.dry_run,
+                    })
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&self, env: &impl Env, action: Action, exe: &Path, link: &Path) -> Result<(), Error> {
+        if let Action::Replace | Action::Remove = action {
+            env.remove_file(link)
+                .map_err(|e| format!("could not remove {}: {e}", link.display()))?;
+        }
+        if let Action::Create | Action::Replace = action {
+            let res = if self.hard {
+                env.hard_link(exe, link)
+            } else {
+                env.symlink(exe, link)
+            };
+            res.map_err(|e| format!("could not create link {}: {e}", link.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn read_file(env: &impl Env, path: &Path) -> Result<Vec<u8>, Error> {
+    let mut contents = Vec::new();
+    env.open(path)
+        .and_then(|mut file| file.read_to_end(&mut contents))
+        .map_err(|e| format!("could not read {}: {e}", path.display()))?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::env::fake::FakeCmd;
+
+    const DNST: &str = "/usr/bin/dnst";
+
+    fn cmd() -> FakeCmd {
+        FakeCmd::new([DNST, "install-links"]).file(DNST, "dnst binary")
+    }
+
+    #[test]
+    fn dnst_parse() {
+        let cmd = FakeCmd::new(["dnst", "install-links"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["bin"]).parse().is_ok());
+        assert!(cmd
+            .args(["-n", "-u", "-f", "--hard", "bin"])
+            .parse()
+            .is_ok());
+    }
+
+    #[test]
+    fn install() {
+        let cmd = cmd();
+
+        let res = cmd.args(["-n", "bin"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res
+            .stdout
+            .starts_with("would create bin/drill\nwould create bin/ldns-compare-zones\n"));
+        assert_eq!(res.stdout.lines().count(), 10);
+        assert!(cmd.read_file("bin/ldns-key2ds").is_none());
+
+        let res = cmd.args(["bin"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res.stdout.contains("created bin/ldns-signzone\n"));
+        assert_eq!(cmd.read_file("bin/ldns-signzone").unwrap(), "dnst binary");
+
+        // Installing again changes nothing.
+        let res = cmd.args(["bin"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, "");
+
+        let res = cmd.args(["-u", "bin"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res.stdout.contains("removed bin/ldns-signzone\n"));
+        assert_eq!(res.stdout.lines().count(), 10);
+        assert!(cmd.read_file("bin/ldns-signzone").is_none());
+    }
+
+    #[test]
+    fn hard_links() {
+        let cmd = cmd();
+        let res = cmd.args(["--hard", "bin"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(cmd.read_file("bin/ldns-keygen").unwrap(), "dnst binary");
+
+        // Hard links are recognized as dnst too.
+        let res = cmd.args(["--uninstall", "bin"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout.lines().count(), 10);
+    }
+
+    #[test]
+    fn ldns_binary() {
+        let cmd = cmd().file("bin/ldns-notify", "ldns binary");
+
+        let res = cmd.args(["bin"]).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains(
+            "refusing to replace bin/ldns-notify, which is not dnst, use --force to do so anyway"
+        ));
+        // Nothing is changed.
+        assert!(cmd.read_file("bin/ldns-keygen").is_none());
+
+        let res = cmd.args(["-u", "bin"]).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("refusing to remove bin/ldns-notify"));
+
+        let res = cmd.args(["--format", "json", "-f", "bin"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res
+            .stdout
+            .contains(r#"{"action":"replace","dry_run":false,"link":"bin/ldns-notify"}"#));
+        assert_eq!(cmd.read_file("bin/ldns-notify").unwrap(), "dnst binary");
+    }
+}
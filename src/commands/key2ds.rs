@@ -0,0 +1,391 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use clap::builder::ValueParser;
+use domain::base::iana::{DigestAlg, SecAlg};
+use domain::base::{Name, Record, ToName};
+use domain::rdata::{Dnskey, Ds, ZoneRecordData};
+use domain::validate::DnskeyExt;
+use lexopt::Arg;
+
+use crate::args::OutputFormat;
+use crate::env::Env;
+use crate::error::{Context, Error};
+use crate::zone::{read_zone_file, ZoneName};
+
+use super::{parse_os, LdnsCommand};
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct Key2ds {
+    /// Ignore the SEP flag and make DS records for any key
+    #[arg(long = "ignore-sep")]
+    ignore_sep: bool,
+
+    /// Do not write DS records to file(s) but to stdout
+    #[arg(short = 'n')]
+    write_to_stdout: bool,
+
+    /// Overwrite existing DS files
+    #[arg(short = 'f', long = "force")]
+    force_overwrite: bool,
+
+    /// Write the DS files to this directory instead of next to the key file
+    #[arg(short = 'd', long = "directory", value_name = "DIR")]
+    directory: Option<PathBuf>,
+
+    /// The digest algorithm to use, by default derived from the key algorithm
+    #[arg(
+        short = 'a',
+        long = "algorithm",
+        value_name = "NUMBER_OR_MNEMONIC",
+        value_parser = ValueParser::new(Key2ds::parse_digest_alg)
+    )]
+    algorithm: Option<DigestAlg>,
+
+    /// The file containing the DNSKEY record(s)
+    #[arg(value_name = "KEYFILE")]
+    keyfile: PathBuf,
+}
+
+const LDNS_HELP: &str = "\
+ldns-key2ds [-fn] [-1|-2|-4] keyfile
+  Generate a DS RR from the DNSKEYS in keyfile
+  The following file will be created for each key:
+  `K<name>+<alg>+<id>.ds`. The base name (K<name>+<alg>+<id>
+  will be printed to stdout.
+
+Options:
+  -f: ignore SEP flag (i.e. make DS records for any key)
+  -n: do not write DS records to file(s) but to stdout
+  (default) use similar hash to the key algorithm.
+  -1: use SHA1 for the DS hash
+  -2: use SHA256 for the DS hash
+  -4: use SHA384 for the DS hash\
+";
+
+impl LdnsCommand for Key2ds {
+    const HELP: &'static str = LDNS_HELP;
+
+    fn parse_ldns<I: IntoIterator<Item = OsString>>(args: I) -> Result<Self, Error> {
+        let mut ignore_sep = false;
+        let mut write_to_stdout = false;
+        let mut algorithm = None;
+        let mut keyfile = None;
+
+        let mut parser = lexopt::Parser::from_args(args);
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Arg::Short('1') => algorithm = Some(DigestAlg::SHA1),
+                Arg::Short('2') => algorithm = Some(DigestAlg::SHA256),
+                Arg::Short('4') => algorithm = Some(DigestAlg::SHA384),
+                Arg::Short('f') => ignore_sep = true,
+                Arg::Short('n') => write_to_stdout = true,
+                Arg::Value(val) => {
+                    if keyfile.is_some() {
+                        return Err("Only one key file can be given".into());
+                    }
+                    keyfile = Some(parse_os("keyfile", &val)?);
+                }
+                Arg::Short(x) => return Err(format!("Invalid short option: -{x}").into()),
+                Arg::Long(x) => {
+                    return Err(format!("Long options are not supported, but `--{x}` given").into())
+                }
+            }
+        }
+
+        let Some(keyfile) = keyfile else {
+            return Err("No keyfile given".into());
+        };
+
+        Ok(Self {
+            ignore_sep,
+            write_to_stdout,
+            // ldns-key2ds silently overwrites existing files
+            force_overwrite: true,
+            // ldns-key2ds writes to the working directory
+            directory: Some(PathBuf::from(".")),
+            algorithm,
+            keyfile,
+        })
+    }
+}
+
+impl Key2ds {
+    pub fn parse_digest_alg(arg: &str) -> Result<DigestAlg, &'static str> {
+        if let Ok(num) = arg.parse() {
+            let alg = DigestAlg::from_int(num);
+            if domain::validate::supported_digest(&alg) {
+                Ok(alg)
+            } else {
+                Err("unknown or unsupported algorithm number")
+            }
+        } else {
+            let alg =
+                DigestAlg::from_mnemonic(arg.as_bytes()).ok_or("unknown algorithm mnemonic")?;
+            if domain::validate::supported_digest(&alg) {
+                Ok(alg)
+            } else {
+                Err("unsupported algorithm")
+            }
+        }
+    }
+
+    /// The digest algorithm ldns picks for a given key algorithm
+    pub(super) fn suitable_digest(alg: SecAlg) -> DigestAlg {
+        match alg {
+            SecAlg::RSASHA256
+            | SecAlg::RSASHA512
+            | SecAlg::ECDSAP256SHA256
+            | SecAlg::ED25519
+            | SecAlg::ED448 => DigestAlg::SHA256,
+            SecAlg::ECDSAP384SHA384 => DigestAlg::SHA384,
+            _ => DigestAlg::SHA1,
+        }
+    }
+}
+
+impl Key2ds {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        self.execute_inner(env, format)
+            .with_context(|| format!("generating DS records from {}", self.keyfile.display()))
+    }
+
+    fn execute_inner(&self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let keys = read_dnskeys(&env, &self.keyfile)?;
+        if keys.is_empty() {
+            return Err("no DNSKEY records found".into());
+        }
+
+        let mut out = env.stdout();
+        for key in keys {
+            let owner = key.owner();
+            let dnskey = key.data();
+
+            if !self.ignore_sep && !dnskey.is_secure_entry_point() {
+                continue;
+            }
+
+            let algorithm = self
+                .algorithm
+                .unwrap_or_else(|| Self::suitable_digest(dnskey.algorithm()));
+
+            let ds = Record::new(
+                owner,
+                key.class(),
+                key.ttl(),
+                make_ds(owner, dnskey, algorithm)?,
+            );
+            let rr = format_ds(&ds);
+
+            if self.write_to_stdout {
+                match format {
+                    OutputFormat::Text => writeln!(out, "{rr}"),
+                    OutputFormat::Json => writeln!(out, "{}", ds_json(&ds)),
+                }
+                continue;
+            }
+
+            let basename = format!(
+                "K{}+{:03}+{:05}",
+                owner.fmt_with_dot(),
+                dnskey.algorithm().to_int(),
+                dnskey.key_tag()
+            );
+            let filename = match &self.directory {
+                Some(dir) => dir.join(format!("{basename}.ds")),
+                None => self.keyfile.with_file_name(format!("{basename}.ds")),
+            };
+
+            write_file(&env, &filename, &format!("{rr}\n"), self.force_overwrite)?;
+
+            match format {
+                OutputFormat::Text => writeln!(out, "{basename}"),
+                OutputFormat::Json => writeln!(
+                    out,
+                    "{}",
+                    serde_json::json!({
+                        "key": basename,
+                        "file": filename.display().to_string(),
+                    })
+                ),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+type DnskeyRecord = Record<ZoneName, Dnskey<Bytes>>;
+
+/// Read all DNSKEY records from a file in zone file format
+fn read_dnskeys(env: &impl Env, path: &Path) -> Result<Vec<DnskeyRecord>, Error> {
+    let keys = read_zone_file(env, path, None)?
+        .into_iter()
+        .filter_map(|record| {
+            let (owner, class, ttl) = (record.owner().clone(), record.class(), record.ttl());
+            match record.into_data() {
+                ZoneRecordData::Dnskey(dnskey) => Some(Record::new(owner, class, ttl, dnskey)),
+                _ => None,
+            }
+        })
+        .collect();
+    Ok(keys)
+}
+
+fn write_file(env: &impl Env, path: &Path, contents: &str, force: bool) -> Result<(), Error> {
+    let res = if force {
+        env.write(path, contents.as_bytes())
+    } else {
+        env.create_new(path, contents.as_bytes())
+    };
+
+    res.map_err(|e| match e.kind() {
+        std::io::ErrorKind::AlreadyExists => format!(
+            "{} already exists, use --force to overwrite it",
+            path.display()
+        )
+        .into(),
+        _ => format!("could not create {}: {e}", path.display()).into(),
+    })
+}
+
+/// Compute the DS record data for a DNSKEY
+pub(super) fn make_ds<N: ToName>(
+    owner: &N,
+    dnskey: &Dnskey<impl AsRef<[u8]>>,
+    algorithm: DigestAlg,
+) -> Result<Ds<Vec<u8>>, Error> {
+    let digest = dnskey
+        .digest(owner, algorithm)
+        .map_err(|e| format!("could not compute digest: {e}"))?;
+
+    Ds::new(
+        dnskey.key_tag(),
+        dnskey.algorithm(),
+        algorithm,
+        digest.as_ref().to_vec(),
+    )
+    .map_err(|_| "digest too long".into())
+}
+
+/// Format a DS record the way ldns prints it
+pub(super) fn format_ds<Octs: AsRef<[u8]>>(record: &Record<&Name<Octs>, Ds<Vec<u8>>>) -> String {
+    format!(
+        "{}\t{}\t{}\tDS\t{}",
+        record.owner().fmt_with_dot(),
+        record.ttl().as_secs(),
+        record.class(),
+        format_ds_data(record.data()),
+    )
+}
+
+/// A DS record as a JSON object, with the same fields as [`format_ds`]
+fn ds_json<Octs: AsRef<[u8]>>(record: &Record<&Name<Octs>, Ds<Vec<u8>>>) -> serde_json::Value {
+    serde_json::json!({
+        "owner": record.owner().fmt_with_dot().to_string(),
+        "ttl": record.ttl().as_secs(),
+        "class": record.class().to_string(),
+        "type": "DS",
+        "data": format_ds_data(record.data()),
+    })
+}
+
+fn format_ds_data(ds: &Ds<Vec<u8>>) -> String {
+    let mut digest = String::with_capacity(ds.digest().len() * 2);
+    for b in ds.digest() {
+        digest.push_str(&format!("{b:02x}"));
+    }
+    format!(
+        "{} {} {} {}",
+        ds.key_tag(),
+        ds.algorithm().to_int(),
+        ds.digest_type().to_int(),
+        digest,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::env::fake::FakeCmd;
+
+    const KSK: &str = "example.test. 3600 IN DNSKEY 257 3 15 \
+        Cw82YmVEZLJPLEp+LuIGLpqlshxnfDuPB2MGdO6FlWs=\n";
+    const ZSK: &str = "example.test. 3600 IN DNSKEY 256 3 15 \
+        qXkCmRqbCtHeCAIBqwHqDPjLWjpDGFZUBmGgHrJCcOo=\n";
+
+    #[test]
+    fn dnst_parse() {
+        let cmd = FakeCmd::new(["dnst", "key2ds"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["-a", "foo", "keyfile"]).parse().is_err());
+        assert!(cmd
+            .args(["-a", "GOST R 34.11-94", "keyfile"])
+            .parse()
+            .is_err());
+        assert!(cmd.args(["-a", "SHA-384", "keyfile"]).parse().is_ok());
+        assert!(cmd.args(["keyfile"]).parse().is_ok());
+    }
+
+    #[test]
+    fn ldns_parse() {
+        let cmd = FakeCmd::new(["ldns-key2ds"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["-x", "keyfile"]).parse().is_err());
+        assert!(cmd.args(["-fn", "-2", "keyfile"]).parse().is_ok());
+    }
+
+    #[test]
+    fn dnst_run_stdout() {
+        let cmd = FakeCmd::new(["dnst", "key2ds", "-n"]).file("key.key", format!("{KSK}{ZSK}"));
+
+        let res = cmd.args(["key.key"]).run();
+        assert_eq!(res.exit_code, 0);
+        assert_eq!(res.stdout.lines().count(), 1);
+        assert!(res.stdout.starts_with("example.test.\t3600\tIN\tDS\t"));
+        assert!(res.stdout.contains(" 15 2 "));
+
+        let res = cmd.args(["--ignore-sep", "-a", "1", "key.key"]).run();
+        assert_eq!(res.exit_code, 0);
+        assert_eq!(res.stdout.lines().count(), 2);
+        assert!(res.stdout.lines().all(|l| l.contains(" 15 1 ")));
+    }
+
+    #[test]
+    fn ldns_run_file() {
+        let cmd = FakeCmd::new(["ldns-key2ds", "-4"]).file("keys/key.key", KSK);
+
+        let res = cmd.args(["keys/key.key"]).run();
+        assert_eq!(res.exit_code, 0);
+
+        let basename = res.stdout.trim();
+        assert!(basename.starts_with("Kexample.test.+015+"));
+
+        // ldns writes the file to the working directory
+        let ds = cmd.read_file(format!("{basename}.ds")).unwrap();
+        assert!(ds.contains(" 15 4 "));
+        assert!(cmd.read_file(format!("keys/{basename}.ds")).is_none());
+
+        // dnst refuses to overwrite the file without --force
+        let cmd = FakeCmd::new(["dnst", "key2ds", "keys/key.key"])
+            .file("keys/key.key", KSK)
+            .file(format!("keys/{basename}.ds"), ds);
+        assert_eq!(cmd.run().exit_code, 1);
+        assert_eq!(cmd.args(["--force"]).run().exit_code, 0);
+
+        // Unless dnst is given another directory
+        let res = cmd.args(["-d", "ds"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(cmd.read_file(format!("ds/{basename}.ds")).is_some());
+    }
+
+    #[test]
+    fn missing_file() {
+        let res = FakeCmd::new(["dnst", "key2ds", "/nonexistent/file.key"]).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("/nonexistent/file.key"));
+    }
+}
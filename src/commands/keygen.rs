@@ -0,0 +1,299 @@
+use std::ffi::OsString;
+use std::path::Path;
+
+use clap::builder::ValueParser;
+use domain::base::iana::Class;
+use domain::base::name::Name;
+use domain::base::{Record, Ttl};
+use lexopt::Arg;
+
+use crate::args::OutputFormat;
+use crate::env::Env;
+use crate::error::Error;
+use crate::keys::{KeyAlgorithm, KeyPair, KSK_FLAGS, ZSK_FLAGS};
+
+use super::key2ds::{format_ds, make_ds, Key2ds};
+use super::nsec3hash::Nsec3Hash;
+use super::{parse_os, parse_os_with, LdnsCommand};
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct Keygen {
+    /// The signing algorithm to use, or `list` to show the supported ones
+    #[arg(
+        short = 'a',
+        long = "algorithm",
+        value_name = "NUMBER_OR_MNEMONIC",
+        value_parser = ValueParser::new(Keygen::parse_algorithm)
+    )]
+    algorithm: AlgorithmArg,
+
+    /// Generate a key signing key (KSK) instead of a zone signing key (ZSK)
+    #[arg(short = 'k', long = "ksk")]
+    ksk: bool,
+
+    /// The length of the key in bits (only used for RSA keys)
+    #[arg(
+        short = 'b',
+        long = "bits",
+        value_name = "BITS",
+        default_value_t = 2048
+    )]
+    bits: u32,
+
+    /// Create symlinks `.key` and `.private` to the generated keys
+    #[arg(short = 's', long = "symlink")]
+    symlink: bool,
+
+    /// Overwrite existing symlinks
+    #[arg(short = 'f', long = "force")]
+    force_symlink: bool,
+
+    /// The domain name to generate a key for
+    #[arg(
+        value_name = "DOMAIN_NAME",
+        value_parser = ValueParser::new(Nsec3Hash::parse_name)
+    )]
+    name: Option<Name<Vec<u8>>>,
+}
+
+/// The value of the algorithm argument
+#[derive(Clone, Copy, Debug)]
+pub enum AlgorithmArg {
+    /// List the supported algorithms instead of generating a key
+    List,
+    Algorithm(KeyAlgorithm),
+}
+
+const LDNS_HELP: &str = "\
+ldns-keygen -a <algorithm> [-b bits] [-r /dev/random] [-s] [-f] domain
+  generate a new key pair for domain
+  -a <alg>	use the specified algorithm (-a list to show a list)
+  -k		set the flags to 257; key signing key
+  -b <bits>	specify the keylength (only used for RSA keys)
+  -r <random>	specify a random device (defaults to /dev/random)
+		to seed the random generator with
+  -s		create additional symlinks with constant names
+  -f		force override of existing symlinks
+  The following files will be created:
+    K<name>+<alg>+<id>.key	Public key in RR format
+    K<name>+<alg>+<id>.private	Private key in key format
+    K<name>+<alg>+<id>.ds	DS in RR format (only for DNSSEC KSK keys)
+  The base name (K<name>+<alg>+<id> will be printed to stdout\
+";
+
+impl LdnsCommand for Keygen {
+    const HELP: &'static str = LDNS_HELP;
+
+    fn parse_ldns<I: IntoIterator<Item = OsString>>(args: I) -> Result<Self, Error> {
+        let mut algorithm = None;
+        let mut ksk = false;
+        let mut bits = 2048;
+        let mut symlink = false;
+        let mut force_symlink = false;
+        let mut name = None;
+
+        let mut parser = lexopt::Parser::from_args(args);
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Arg::Short('a') => {
+                    let val = parser.value()?;
+                    algorithm = Some(parse_os_with(
+                        "algorithm (-a)",
+                        &val,
+                        Keygen::parse_algorithm,
+                    )?);
+                }
+                Arg::Short('b') => {
+                    let val = parser.value()?;
+                    bits = parse_os("bits (-b)", &val)?;
+                }
+                Arg::Short('k') => ksk = true,
+                Arg::Short('r') => {
+                    // We always use the random number generator of the
+                    // operating system, so the device is ignored.
+                    parser.value()?;
+                }
+                Arg::Short('s') => symlink = true,
+                Arg::Short('f') => force_symlink = true,
+                Arg::Value(val) => {
+                    if name.is_some() {
+                        return Err("Only one domain name can be given".into());
+                    }
+                    name = Some(parse_os_with("domain name", &val, Nsec3Hash::parse_name)?);
+                }
+                Arg::Short(x) => return Err(format!("Invalid short option: -{x}").into()),
+                Arg::Long(x) => {
+                    return Err(format!("Long options are not supported, but `--{x}` given").into())
+                }
+            }
+        }
+
+        let Some(algorithm) = algorithm else {
+            return Err("Missing algorithm (-a) argument".into());
+        };
+
+        if name.is_none() && !matches!(algorithm, AlgorithmArg::List) {
+            return Err("Missing domain name argument".into());
+        }
+
+        Ok(Self {
+            algorithm,
+            ksk,
+            bits,
+            symlink,
+            force_symlink,
+            name,
+        })
+    }
+}
+
+impl Keygen {
+    pub fn parse_algorithm(arg: &str) -> Result<AlgorithmArg, &'static str> {
+        if arg == "list" {
+            return Ok(AlgorithmArg::List);
+        }
+        KeyAlgorithm::parse(arg).map(AlgorithmArg::Algorithm)
+    }
+}
+
+impl Keygen {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let mut out = env.stdout();
+
+        let algorithm = match self.algorithm {
+            AlgorithmArg::List if format == OutputFormat::Json => {
+                for alg in KeyAlgorithm::ALL {
+                    let json = serde_json::json!({
+                        "number": alg.to_sec_alg().to_int(),
+                        "mnemonic": alg.to_string(),
+                    });
+                    writeln!(out, "{json}");
+                }
+                return Ok(());
+            }
+            AlgorithmArg::List => {
+                writeln!(out, "Possible algorithms:");
+                for alg in KeyAlgorithm::ALL {
+                    writeln!(out, "{alg}");
+                }
+                return Ok(());
+            }
+            AlgorithmArg::Algorithm(alg) => alg,
+        };
+
+        let Some(name) = self.name else {
+            return Err("missing domain name".into());
+        };
+
+        let flags = if self.ksk { KSK_FLAGS } else { ZSK_FLAGS };
+        let key = KeyPair::generate(name, algorithm, flags, self.bits)?;
+        let base_name = key.base_name();
+
+        let public_file = format!("{base_name}.key");
+        write_new_file(&env, &public_file, &key.display_public().to_string())?;
+
+        let private_file = format!("{base_name}.private");
+        write_new_file(&env, &private_file, &key.display_private().to_string())?;
+
+        if self.ksk {
+            let digest_alg = Key2ds::suitable_digest(key.dnskey().algorithm());
+            let ds = make_ds(key.owner(), key.dnskey(), digest_alg)?;
+            let ds = format_ds(&Record::new(
+                key.owner(),
+                Class::IN,
+                Ttl::from_secs(3600),
+                ds,
+            ));
+            write_new_file(&env, &format!("{base_name}.ds"), &format!("{ds}\n"))?;
+        }
+
+        if self.symlink {
+            create_symlink(&env, &public_file, ".key", self.force_symlink)?;
+            create_symlink(&env, &private_file, ".private", self.force_symlink)?;
+        }
+
+        match format {
+            OutputFormat::Text => writeln!(out, "{base_name}"),
+            OutputFormat::Json => writeln!(out, "{}", serde_json::json!({ "key": base_name })),
+        }
+        Ok(())
+    }
+}
+
+fn write_new_file(env: &impl Env, path: &str, contents: &str) -> Result<(), Error> {
+    env.create_new(Path::new(path), contents.as_bytes())
+        .map_err(|e| format!("could not create {path}: {e}").into())
+}
+
+fn create_symlink(env: &impl Env, target: &str, link: &str, force: bool) -> Result<(), Error> {
+    let link = Path::new(link);
+    if env.exists(link) {
+        if !force {
+            return Err(format!(
+                "symlink {} already exists, use --force to overwrite it",
+                link.display()
+            )
+            .into());
+        }
+        env.remove_file(link)
+            .map_err(|e| format!("could not remove {}: {e}", link.display()))?;
+    }
+
+    env.symlink(Path::new(target), link)
+        .map_err(|e| format!("could not create symlink {}: {e}", link.display()).into())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::env::fake::FakeCmd;
+
+    #[test]
+    fn dnst_parse() {
+        let cmd = FakeCmd::new(["dnst", "keygen"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["example.test"]).parse().is_err());
+        assert!(cmd.args(["-a", "RSASHA1", "example.test"]).parse().is_err());
+        assert!(cmd.args(["-a", "13", "example.test"]).parse().is_ok());
+        assert!(cmd
+            .args(["-a", "ED25519", "-k", "example.test"])
+            .parse()
+            .is_ok());
+        assert!(cmd.args(["-a", "list"]).parse().is_ok());
+    }
+
+    #[test]
+    fn ldns_parse() {
+        let cmd = FakeCmd::new(["ldns-keygen"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["example.test"]).parse().is_err());
+        assert!(cmd.args(["-a", "13"]).parse().is_err());
+        assert!(cmd.args(["-a", "list"]).parse().is_ok());
+        assert!(cmd.args(["-v", "-a", "list"]).parse().is_err());
+        assert!(cmd
+            .args([
+                "-a",
+                "8",
+                "-b",
+                "4096",
+                "-r",
+                "/dev/urandom",
+                "-ks",
+                "example.test"
+            ])
+            .parse()
+            .is_ok());
+    }
+
+    #[test]
+    fn list() {
+        let res = FakeCmd::new(["dnst", "keygen", "-a", "list"]).run();
+        assert_eq!(res.exit_code, 0);
+        assert!(res.stdout.starts_with("Possible algorithms:\n"));
+        assert!(res.stdout.contains("\nED25519\n"));
+        // Only the names, like ldns-keygen
+        assert!(res.stdout.lines().skip(1).all(|l| l.parse::<u8>().is_err()));
+    }
+}
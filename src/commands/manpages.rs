@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+use clap::CommandFactory;
+use clap_mangen::Man;
+
+use crate::args::OutputFormat;
+use crate::env::Env;
+use crate::error::Error;
+use crate::Args;
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct Manpages {
+    /// The directory to write the manual pages to
+    #[arg(value_name = "DIRECTORY", default_value = ".")]
+    out_dir: PathBuf,
+}
+
+impl Manpages {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let mut dnst = Args::command().name("dnst");
+        // Building propagates the global options to the commands.
+        dnst.build();
+
+        self.write_page(&env, format, &dnst, "dnst")?;
+        for command in dnst.get_subcommands().filter(|c| !c.is_hide_set()) {
+            let name = format!("dnst-{}", command.get_name());
+            let command = command.clone().display_name(&name);
+            self.write_page(&env, format, &command, &name)?;
+        }
+        Ok(())
+    }
+
+    /// Render the manual page of a command and write it to `<name>.1`
+    fn write_page(
+        &self,
+        env: &impl Env,
+        format: OutputFormat,
+        command: &clap::Command,
+        name: &str,
+    ) -> Result<(), Error> {
+        let mut page = Vec::new();
+        Man::new(command.clone())
+            .title(name.to_uppercase())
+            .section("1")
+            .manual("DNS Management Tools")
+            .source(format!("dnst {}", env!("CARGO_PKG_VERSION")))
+            .render(&mut page)
+            .map_err(|e| format!("could not render the manual page of {name}: {e}"))?;
+
+        let path = self.out_dir.join(format!("{name}.1"));
+        env.write(&path, &page)
+            .map_err(|e| format!("could not write {}: {e}", path.display()))?;
+
+        match format {
+            OutputFormat::Text => writeln!(env.stdout(), "{}", path.display()),
+            OutputFormat::Json => writeln!(
+                env.stdout(),
+                "{}",
+                serde_json::json!({ "file": path.display().to_string() })
+            ),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::env::fake::FakeCmd;
+
+    #[test]
+    fn pages() {
+        let cmd = FakeCmd::new(["dnst", "manpages", "man"]);
+        let res = cmd.run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res
+            .stdout
+            .starts_with("man/dnst.1\nman/dnst-nsec3-hash.1\n"));
+
+        let page = cmd.read_file("man/dnst.1").unwrap();
+        assert!(page.starts_with(".ie \\n(.g .ds Aq \\(aq"));
+        assert!(page.contains(".TH DNST 1"));
+        assert!(page.contains("dnst\\-signzone(1)"));
+
+        // The global options are documented for each command.
+        let page = cmd.read_file("man/dnst-signzone.1").unwrap();
+        assert!(page.contains(".TH DNST-SIGNZONE 1"));
+        assert!(page.contains("\\-\\-format"));
+    }
+}
@@ -1,15 +1,44 @@
 //! The command of _dnst_.
 
+pub mod comparezones;
+pub mod completions;
 pub mod help;
+pub mod installlinks;
+pub mod key2ds;
+pub mod keygen;
+pub mod manpages;
+pub mod notify;
+pub mod nsec3guess;
 pub mod nsec3hash;
+pub mod nsec3proof;
+pub mod query;
+pub mod readzone;
+pub mod signzone;
+pub mod update;
+pub mod verifyzone;
 
 use std::ffi::{OsStr, OsString};
+use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
 
+use comparezones::CompareZones;
+use completions::Completions;
+use installlinks::InstallLinks;
+use key2ds::Key2ds;
+use keygen::Keygen;
+use manpages::Manpages;
+use notify::Notify;
+use nsec3guess::Nsec3Guess;
 use nsec3hash::Nsec3Hash;
+use nsec3proof::Nsec3Proof;
+use query::Query;
+use readzone::ReadZone;
+use signzone::SignZone;
+use update::Update;
+use verifyzone::VerifyZone;
 
 use crate::env::Env;
-use crate::Args;
+use crate::{Args, OutputFormat};
 
 use super::error::Error;
 
@@ -19,15 +48,85 @@ pub enum Command {
     #[command(name = "nsec3-hash")]
     Nsec3Hash(self::nsec3hash::Nsec3Hash),
 
+    /// Show which NSEC3 records prove the response for a query name
+    #[command(name = "nsec3-proof")]
+    Nsec3Proof(self::nsec3proof::Nsec3Proof),
+
+    /// Find the names of NSEC3 hashes with a wordlist
+    #[command(name = "nsec3-guess")]
+    Nsec3Guess(self::nsec3guess::Nsec3Guess),
+
+    /// Generate DS records from the DNSKEY records in a file
+    #[command(name = "key2ds")]
+    Key2ds(self::key2ds::Key2ds),
+
+    /// Generate a new key pair for a domain name
+    #[command(name = "keygen")]
+    Keygen(self::keygen::Keygen),
+
+    /// Sign a zone with one or more keys
+    #[command(name = "signzone")]
+    SignZone(self::signzone::SignZone),
+
+    /// Send a NOTIFY message for a zone to one or more servers
+    #[command(name = "notify")]
+    Notify(self::notify::Notify),
+
+    /// Send a dynamic update (RFC 2136) for a zone
+    #[command(name = "update")]
+    Update(self::update::Update),
+
+    /// Look up records by sending a query to a DNS server
+    #[command(name = "query")]
+    Query(self::query::Query),
+
+    /// Read a zone file and print it in canonical form
+    #[command(name = "read-zone")]
+    ReadZone(self::readzone::ReadZone),
+
+    /// Verify the DNSSEC signatures and NSEC(3) chain of a signed zone
+    #[command(name = "verify-zone")]
+    VerifyZone(self::verifyzone::VerifyZone),
+
+    /// Compare two zone files and print the differences
+    #[command(name = "compare-zones")]
+    CompareZones(self::comparezones::CompareZones),
+
+    /// Create links for running dnst as the ldns tools it implements
+    #[command(name = "install-links")]
+    InstallLinks(self::installlinks::InstallLinks),
+
+    /// Print a shell completion script for dnst
+    #[command(name = "completions")]
+    Completions(self::completions::Completions),
+
+    /// Generate manual pages in roff format from the command line options
+    #[command(name = "manpages")]
+    Manpages(self::manpages::Manpages),
+
     /// Show the manual pages
     Help(self::help::Help),
 }
 
 impl Command {
-    pub fn execute(self, env: impl Env) -> Result<(), Error> {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
         match self {
-            Self::Nsec3Hash(nsec3hash) => nsec3hash.execute(env),
-            Self::Help(help) => help.execute(),
+            Self::Nsec3Hash(nsec3hash) => nsec3hash.execute(env, format),
+            Self::Nsec3Proof(nsec3proof) => nsec3proof.execute(env, format),
+            Self::Nsec3Guess(nsec3guess) => nsec3guess.execute(env, format),
+            Self::Key2ds(key2ds) => key2ds.execute(env, format),
+            Self::Keygen(keygen) => keygen.execute(env, format),
+            Self::SignZone(signzone) => signzone.execute(env, format),
+            Self::Notify(notify) => notify.execute(env, format),
+            Self::Update(update) => update.execute(env, format),
+            Self::Query(query) => query.execute(env, format),
+            Self::ReadZone(readzone) => readzone.execute(env, format),
+            Self::VerifyZone(verifyzone) => verifyzone.execute(env, format),
+            Self::CompareZones(comparezones) => comparezones.execute(env, format),
+            Self::InstallLinks(installlinks) => installlinks.execute(env, format),
+            Self::Completions(completions) => completions.execute(env),
+            Self::Manpages(manpages) => manpages.execute(env, format),
+            Self::Help(help) => help.execute(env, format),
         }
     }
 }
@@ -53,12 +152,167 @@ pub trait LdnsCommand: Into<Command> {
     }
 }
 
+/// An ldns tool that dnst can be invoked as
+///
+/// When dnst is invoked under the name of the tool, e.g. through a symbolic
+/// link, it parses its arguments like that tool does.
+pub struct LdnsTool {
+    /// The name of the tool, e.g. `ldns-nsec3-hash`
+    pub name: &'static str,
+
+    /// Parse the arguments, without the program name, into [`Args`]
+    pub parse_args: fn(Vec<OsString>) -> Result<Args, Error>,
+
+    /// The usage of the tool, as [`LdnsCommand::HELP`]
+    pub help: &'static str,
+}
+
+impl LdnsTool {
+    const fn new<T: LdnsCommand>(name: &'static str) -> Self {
+        Self {
+            name,
+            parse_args: T::parse_ldns_args::<Vec<OsString>>,
+            help: T::HELP,
+        }
+    }
+
+    /// Find a tool by its name
+    pub fn find(name: &str) -> Option<&'static Self> {
+        LDNS_TOOLS.iter().find(|tool| tool.name == name)
+    }
+
+    /// Find a tool by its name, with an error listing the tools otherwise
+    pub fn find_or_err(name: &str) -> Result<&'static Self, Error> {
+        Self::find(name).ok_or_else(|| {
+            let names: Vec<_> = LDNS_TOOLS.iter().map(|tool| tool.name).collect();
+            format!(
+                "unknown ldns tool `{name}`, the supported tools are: {}",
+                names.join(", ")
+            )
+            .into()
+        })
+    }
+}
+
+/// The ldns tools that dnst implements, sorted by name
+pub const LDNS_TOOLS: &[LdnsTool] = &[
+    LdnsTool::new::<Query>("drill"),
+    LdnsTool::new::<CompareZones>("ldns-compare-zones"),
+    LdnsTool::new::<Key2ds>("ldns-key2ds"),
+    LdnsTool::new::<Keygen>("ldns-keygen"),
+    LdnsTool::new::<Notify>("ldns-notify"),
+    LdnsTool::new::<Nsec3Hash>("ldns-nsec3-hash"),
+    LdnsTool::new::<ReadZone>("ldns-read-zone"),
+    LdnsTool::new::<SignZone>("ldns-signzone"),
+    LdnsTool::new::<Update>("ldns-update"),
+    LdnsTool::new::<VerifyZone>("ldns-verify-zone"),
+];
+
 impl From<Nsec3Hash> for Command {
     fn from(val: Nsec3Hash) -> Self {
         Command::Nsec3Hash(val)
     }
 }
 
+impl From<Nsec3Proof> for Command {
+    fn from(val: Nsec3Proof) -> Self {
+        Command::Nsec3Proof(val)
+    }
+}
+
+impl From<Nsec3Guess> for Command {
+    fn from(val: Nsec3Guess) -> Self {
+        Command::Nsec3Guess(val)
+    }
+}
+
+impl From<Key2ds> for Command {
+    fn from(val: Key2ds) -> Self {
+        Command::Key2ds(val)
+    }
+}
+
+impl From<Keygen> for Command {
+    fn from(val: Keygen) -> Self {
+        Command::Keygen(val)
+    }
+}
+
+impl From<SignZone> for Command {
+    fn from(val: SignZone) -> Self {
+        Command::SignZone(val)
+    }
+}
+
+impl From<Notify> for Command {
+    fn from(val: Notify) -> Self {
+        Command::Notify(val)
+    }
+}
+
+impl From<Update> for Command {
+    fn from(val: Update) -> Self {
+        Command::Update(val)
+    }
+}
+
+impl From<Query> for Command {
+    fn from(val: Query) -> Self {
+        Command::Query(val)
+    }
+}
+
+impl From<ReadZone> for Command {
+    fn from(val: ReadZone) -> Self {
+        Command::ReadZone(val)
+    }
+}
+
+impl From<VerifyZone> for Command {
+    fn from(val: VerifyZone) -> Self {
+        Command::VerifyZone(val)
+    }
+}
+
+impl From<CompareZones> for Command {
+    fn from(val: CompareZones) -> Self {
+        Command::CompareZones(val)
+    }
+}
+
+impl From<InstallLinks> for Command {
+    fn from(val: InstallLinks) -> Self {
+        Command::InstallLinks(val)
+    }
+}
+
+impl From<Completions> for Command {
+    fn from(val: Completions) -> Self {
+        Command::Completions(val)
+    }
+}
+
+impl From<Manpages> for Command {
+    fn from(val: Manpages) -> Self {
+        Command::Manpages(val)
+    }
+}
+
+/// Get the socket addresses for a server given as address or host name
+fn resolve_server(env: &impl Env, server: &str, port: u16) -> Result<Vec<SocketAddr>, Error> {
+    if let Ok(addr) = server.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(addr, port)]);
+    }
+
+    let addrs = env
+        .lookup_host(server, port)
+        .map_err(|e| format!("could not resolve {server}: {e}"))?;
+    if addrs.is_empty() {
+        return Err(format!("could not resolve {server}: no addresses found").into());
+    }
+    Ok(addrs)
+}
+
 /// Utility function to parse an [`OsStr`] with a custom function
 fn parse_os_with<T, E>(opt: &str, val: &OsStr, f: impl Fn(&str) -> Result<T, E>) -> Result<T, Error>
 where
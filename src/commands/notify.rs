@@ -0,0 +1,470 @@
+use std::ffi::OsString;
+use std::time::Duration;
+
+use clap::builder::ValueParser;
+use domain::base::iana::{Class, Opcode, Rtype};
+use domain::base::name::Name;
+use domain::base::{Message, MessageBuilder, Record, Serial, Ttl};
+use domain::net::client::dgram;
+use domain::net::client::request::{RequestMessage, SendRequest};
+use domain::net::client::tsig;
+use domain::rdata::Soa;
+use lexopt::Arg;
+
+use crate::args::OutputFormat;
+use crate::env::Env;
+use crate::error::{Error, Warning};
+use crate::message::{message_json, print_message};
+use crate::tsig::TsigKey;
+
+use super::nsec3hash::Nsec3Hash;
+use super::{parse_os, parse_os_with, resolve_server, LdnsCommand};
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct Notify {
+    /// The zone to send the NOTIFY for
+    #[arg(
+        short = 'z',
+        long = "zone",
+        value_name = "DOMAIN_NAME",
+        value_parser = ValueParser::new(Nsec3Hash::parse_name)
+    )]
+    zone: Name<Vec<u8>>,
+
+    /// The SOA serial to include in the NOTIFY
+    #[arg(short = 's', long = "soa-serial", value_name = "SERIAL")]
+    soa_serial: Option<u32>,
+
+    /// The port to send the NOTIFY to
+    #[arg(short = 'p', long = "port", value_name = "PORT", default_value_t = 53)]
+    port: u16,
+
+    /// A TSIG key to sign the NOTIFY with
+    #[arg(short = 'y', long = "tsig", value_name = "NAME:SECRET[:ALGORITHM]")]
+    tsig: Option<TsigKey>,
+
+    /// The maximum number of retries
+    #[arg(
+        short = 'r',
+        long = "retries",
+        value_name = "NUMBER",
+        default_value_t = 15
+    )]
+    retries: u8,
+
+    /// The number of seconds to wait for a response before retrying
+    #[arg(
+        short = 't',
+        long = "timeout",
+        value_name = "SECONDS",
+        default_value_t = 1
+    )]
+    timeout: u64,
+
+    /// The servers to send the NOTIFY to
+    #[arg(value_name = "SERVER", required = true)]
+    servers: Vec<String>,
+}
+
+const LDNS_HELP: &str = "\
+usage: ldns-notify [other options] -z zone <servers>
+Ldns notify utility
+
+ Supported options:
+\t-z zone\t\tThe zone
+\t-s version\tSOA version number to include
+\t-y key:data[:algo]\tTSIG sign the query
+\t-p port\t\tport to use to send to
+\t-r num\t\tmax number of retries (default 15)\
+";
+
+impl LdnsCommand for Notify {
+    const HELP: &'static str = LDNS_HELP;
+
+    fn parse_ldns<I: IntoIterator<Item = OsString>>(args: I) -> Result<Self, Error> {
+        let mut zone = None;
+        let mut soa_serial = None;
+        let mut port = 53;
+        let mut tsig = None;
+        let mut retries = 15;
+        let mut servers = Vec::new();
+
+        let mut parser = lexopt::Parser::from_args(args);
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Arg::Short('z') => {
+                    let val = parser.value()?;
+                    zone = Some(parse_os_with("zone (-z)", &val, Nsec3Hash::parse_name)?);
+                }
+                Arg::Short('s') => {
+                    let val = parser.value()?;
+                    soa_serial = Some(parse_os("SOA version (-s)", &val)?);
+                }
+                Arg::Short('p') => {
+                    let val = parser.value()?;
+                    port = parse_os("port (-p)", &val)?;
+                }
+                Arg::Short('y') => {
+                    let val = parser.value()?;
+                    tsig = Some(parse_os_with(
+                        "TSIG key (-y)",
+                        &val,
+                        TsigKey::from_ldns_str,
+                    )?);
+                }
+                Arg::Short('r') => {
+                    let val = parser.value()?;
+                    retries = parse_os("retries (-r)", &val)?;
+                }
+                Arg::Value(val) => {
+                    servers.push(parse_os("server", &val)?);
+                }
+                Arg::Short(x) => return Err(format!("Invalid short option: -{x}").into()),
+                Arg::Long(x) => {
+                    return Err(format!("Long options are not supported, but `--{x}` given").into())
+                }
+            }
+        }
+
+        let Some(zone) = zone else {
+            return Err("Missing zone (-z) argument".into());
+        };
+
+        if servers.is_empty() {
+            return Err("No servers given".into());
+        }
+
+        Ok(Self {
+            zone,
+            soa_serial,
+            port,
+            tsig,
+            retries,
+            timeout: 1,
+            servers,
+        })
+    }
+}
+
+impl Notify {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| format!("could not start the runtime: {e}"))?;
+        runtime.block_on(self.run(env, format))
+    }
+
+    async fn run(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let msg = self.create_message()?;
+        let tsig_key = self.tsig.as_ref().map(TsigKey::to_key).transpose()?;
+
+        let mut config = dgram::Config::new();
+        config.set_max_retries(self.retries);
+        config.set_read_timeout(Duration::from_secs(self.timeout));
+        // ldns-notify does not use EDNS and neither do we.
+        config.set_udp_payload_size(None);
+
+        let mut out = env.stdout();
+        let mut failures = 0;
+
+        // Problems with a single server do not stop the others.
+        let report = |problem: String| match format {
+            OutputFormat::Text => writeln!(env.stderr(), "{problem}"),
+            OutputFormat::Json => Warning::from(problem).pretty_print(&env, format),
+        };
+
+        for server in &self.servers {
+            let addrs = match resolve_server(&env, server, self.port) {
+                Ok(addrs) => addrs,
+                Err(e) => {
+                    report(format!("skipping bad address: {server}: {e}"));
+                    failures += 1;
+                    continue;
+                }
+            };
+
+            for addr in addrs {
+                let conn =
+                    dgram::Connection::with_config(env.make_connection(addr), config.clone());
+                let request = RequestMessage::new(msg.clone())
+                    .map_err(|e| format!("could not create NOTIFY message: {e}"))?;
+
+                let response = match &tsig_key {
+                    Some(key) => {
+                        let conn = tsig::Connection::new(key.clone(), conn);
+                        conn.send_request(request).get_response().await
+                    }
+                    None => conn.send_request(request).get_response().await,
+                };
+
+                match response {
+                    Ok(response) => match format {
+                        OutputFormat::Text => {
+                            writeln!(out, "# reply from {addr}:");
+                            print_message(&mut out, &response);
+                        }
+                        OutputFormat::Json => {
+                            let json = serde_json::json!({
+                                "server": addr.to_string(),
+                                "reply": message_json(&response),
+                            });
+                            writeln!(out, "{json}");
+                        }
+                    },
+                    Err(e) => {
+                        report(format!("error sending NOTIFY to {addr}: {e}"));
+                        failures += 1;
+                    }
+                }
+            }
+        }
+
+        if failures > 0 {
+            return Err(format!("could not notify {failures} server(s)").into());
+        }
+        Ok(())
+    }
+
+    /// Create the NOTIFY message for the zone
+    fn create_message(&self) -> Result<Message<Vec<u8>>, Error> {
+        let mut msg = MessageBuilder::new_vec();
+        let header = msg.header_mut();
+        header.set_opcode(Opcode::NOTIFY);
+        header.set_aa(true);
+
+        let mut msg = msg.question();
+        msg.push((&self.zone, Rtype::SOA, Class::IN))
+            .map_err(|e| format!("could not create NOTIFY message: {e}"))?;
+
+        let mut msg = msg.answer();
+        if let Some(serial) = self.soa_serial {
+            let soa = Soa::new(
+                Name::root_vec(),
+                Name::root_vec(),
+                Serial(serial),
+                Ttl::ZERO,
+                Ttl::ZERO,
+                Ttl::ZERO,
+                Ttl::ZERO,
+            );
+            msg.push(Record::new(&self.zone, Class::IN, Ttl::ZERO, soa))
+                .map_err(|e| format!("could not create NOTIFY message: {e}"))?;
+        }
+
+        Ok(msg.into_message())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use domain::base::iana::{Opcode, Rcode};
+    use domain::base::{Message, MessageBuilder, Serial};
+    use domain::rdata::tsig::Time48;
+    use domain::rdata::Soa;
+    use domain::tsig::ServerTransaction;
+
+    use crate::env::fake::FakeCmd;
+    use crate::tsig::TsigKey;
+
+    /// Answer NOTIFY messages like a secondary server would
+    fn respond(msg: &Message<[u8]>) -> Option<Message<Vec<u8>>> {
+        let rcode = if msg.header().opcode() == Opcode::NOTIFY {
+            Rcode::NOERROR
+        } else {
+            Rcode::NOTIMP
+        };
+        let mut builder = MessageBuilder::new_vec().start_answer(msg, rcode).unwrap();
+        builder.header_mut().set_aa(true);
+        Some(builder.into_message())
+    }
+
+    #[test]
+    fn dnst_parse() {
+        let cmd = FakeCmd::new(["dnst", "notify"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["192.0.2.1"]).parse().is_err());
+        assert!(cmd.args(["-z", "example.test"]).parse().is_err());
+        assert!(cmd
+            .args(["-z", "example.test", "192.0.2.1"])
+            .parse()
+            .is_ok());
+        assert!(cmd
+            .args(["-z", "example.test", "-y", "key:bm90IGEga2V5", "ns.test"])
+            .parse()
+            .is_ok());
+        assert!(cmd
+            .args([
+                "-z",
+                "example.test",
+                "-y",
+                "key:bm90IGEga2V5:hmac-md5",
+                "ns.test"
+            ])
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn ldns_parse() {
+        let cmd = FakeCmd::new(["ldns-notify"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["192.0.2.1"]).parse().is_err());
+        assert!(cmd.args(["-z", "example.test"]).parse().is_err());
+        assert!(cmd
+            .args(["-z", "example.test", "-s", "2024010101", "-p", "5353"])
+            .args(["-r", "3", "-y", "key:bm90IGEga2V5:hmac-sha1."])
+            .args(["192.0.2.1", "2001:db8::1"])
+            .parse()
+            .is_ok());
+        assert!(cmd
+            .args(["-z", "example.test", "-t", "5", "192.0.2.1"])
+            .parse()
+            .is_err());
+
+        // ldns defaults to hmac-md5, which is not supported.
+        let res = cmd
+            .args(["-z", "example.test", "-y", "key:bm90IGEga2V5", "192.0.2.1"])
+            .run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res
+            .stderr
+            .contains("the default algorithm hmac-md5 is not supported"));
+        assert!(res.stderr.contains("<name:secret:algorithm>"));
+    }
+
+    #[test]
+    fn notify() {
+        let res = FakeCmd::new(["dnst", "notify", "-z", "example.test", "-p", "5353"])
+            .args(["-s", "2024010101", "192.0.2.1", "2001:db8::1"])
+            .server(|addr, msg| {
+                assert_eq!(addr.port(), 5353);
+                assert!(msg.header().aa());
+                let answer = msg.answer().unwrap();
+                let soa = answer.limit_to::<Soa<_>>().next().unwrap().unwrap();
+                assert_eq!(soa.data().serial(), Serial(2024010101));
+                respond(msg)
+            })
+            .run();
+
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res.stdout.contains("# reply from 192.0.2.1:5353:\n"));
+        assert!(res.stdout.contains("# reply from [2001:db8::1]:5353:\n"));
+        assert!(res
+            .stdout
+            .contains(";; ->>HEADER<<- opcode: NOTIFY, rcode: NOERROR, id: "));
+        assert!(res.stdout.contains(";; flags: qr aa ; QUERY: 1, ANSWER: 0"));
+        assert!(res.stdout.contains(";; example.test.\tSOA\tIN\n"));
+    }
+
+    #[test]
+    fn server_names() {
+        let res = FakeCmd::new(["dnst", "notify", "-z", "example.test"])
+            .args(["ns1.example.test", "ns2.example.test"])
+            .file("/etc/hosts", "192.0.2.1 ns1.example.test\n")
+            .server(|_, msg| respond(msg))
+            .run();
+
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stdout.contains("# reply from 192.0.2.1:53:\n"));
+        assert!(res
+            .stderr
+            .contains("skipping bad address: ns2.example.test: could not resolve"));
+    }
+
+    #[test]
+    fn stelline() {
+        let script = "\
+CONFIG_END
+
+SCENARIO_BEGIN A secondary accepting NOTIFY messages for example.test
+
+RANGE_BEGIN 0 100
+ENTRY_BEGIN
+MATCH opcode qtype qname
+ADJUST copy_id
+REPLY QR AA NOTIFY NOERROR
+SECTION QUESTION
+example.test. IN SOA
+ENTRY_END
+RANGE_END
+
+SCENARIO_END
+";
+        let res = FakeCmd::new(["ldns-notify", "-z", "example.test", "192.0.2.1"])
+            .stelline("notify", script)
+            .run();
+
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res.stdout.contains("# reply from 192.0.2.1:53:\n"));
+        assert!(res
+            .stdout
+            .contains(";; ->>HEADER<<- opcode: NOTIFY, rcode: NOERROR, id: "));
+    }
+
+    #[test]
+    fn retry() {
+        // Drop the first request, answer the second.
+        let count = Arc::new(AtomicUsize::new(0));
+        let server_count = count.clone();
+        let cmd = FakeCmd::new(["ldns-notify", "-z", "example.test", "-r", "1", "192.0.2.1"])
+            .server(move |_, msg| {
+                if server_count.fetch_add(1, Ordering::SeqCst) == 0 {
+                    None
+                } else {
+                    respond(msg)
+                }
+            });
+
+        let res = cmd.run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        // Without retries, it fails.
+        count.store(0, Ordering::SeqCst);
+        let res = FakeCmd::new(["ldns-notify", "-z", "example.test", "-r", "0", "192.0.2.1"])
+            .server(move |_, _| None)
+            .run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("error sending NOTIFY to 192.0.2.1:53"));
+    }
+
+    #[test]
+    fn tsig() {
+        let key = "key.example:bm90IGEga2V5".parse::<TsigKey>().unwrap();
+        let key = key.to_key().unwrap();
+
+        let cmd = FakeCmd::new(["dnst", "notify", "-z", "example.test"]).args([
+            "-y",
+            "key.example:bm90IGEga2V5",
+            "192.0.2.1",
+        ]);
+
+        let res = cmd
+            .server(move |_, msg| {
+                let mut msg = Message::from_octets(msg.as_slice().to_vec()).unwrap();
+                let tran = ServerTransaction::request(&key, &mut msg, Time48::now())
+                    .unwrap()
+                    .unwrap();
+                let mut builder = MessageBuilder::new_vec()
+                    .start_answer(&msg, Rcode::NOERROR)
+                    .unwrap()
+                    .additional();
+                tran.answer(&mut builder, Time48::now()).unwrap();
+                Some(builder.into_message())
+            })
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+
+        // An unsigned response is rejected.
+        let res = cmd.server(|_, msg| respond(msg)).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("error sending NOTIFY to 192.0.2.1:53"));
+    }
+}
@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+
+use clap::builder::ValueParser;
+use domain::base::iana::nsec3::Nsec3HashAlg;
+use domain::base::iana::Rtype;
+use domain::base::name::Name;
+use domain::base::ToName;
+use domain::rdata::nsec3::{Nsec3Salt, OwnerHash};
+use domain::rdata::ZoneRecordData;
+
+use crate::args::OutputFormat;
+use crate::env::Env;
+use crate::error::{Context, Error};
+use crate::zone::{is_in_zone, parse_name, read_zone_file, ZoneName};
+
+use super::nsec3hash::{nsec3param_of, Nsec3Digest, Nsec3Hash};
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct Nsec3Guess {
+    /// The hashing algorithm [default: from the zone or SHA-1]
+    #[arg(
+        short = 'a',
+        long = "algorithm",
+        value_name = "NUMBER_OR_MNEMONIC",
+        value_parser = ValueParser::new(Nsec3Hash::parse_nsec_alg)
+    )]
+    algorithm: Option<Nsec3HashAlg>,
+
+    /// The number of hash iterations [default: from the zone or 0]
+    #[arg(
+        short = 'i',
+        visible_short_alias = 't',
+        long = "iterations",
+        value_name = "NUMBER"
+    )]
+    iterations: Option<u16>,
+
+    /// The salt in hex representation [default: from the zone or none]
+    #[arg(short = 's', long = "salt", value_name = "HEX_STRING")]
+    salt: Option<Nsec3Salt<Vec<u8>>>,
+
+    /// The file with the labels to try, one per line, or - for stdin
+    #[arg(short = 'w', long = "wordlist", value_name = "FILE")]
+    wordlist: PathBuf,
+
+    /// Take the hashes and parameters from the NSEC3 records of a signed zone
+    #[arg(short = 'z', long = "zone", value_name = "ZONEFILE")]
+    zone: Option<PathBuf>,
+
+    /// The zone that the hashes belong to
+    #[arg(
+        short = 'o',
+        long = "origin",
+        value_name = "DOMAIN_NAME",
+        value_parser = ValueParser::new(Nsec3Hash::parse_name),
+        required_unless_present = "zone",
+        conflicts_with = "zone"
+    )]
+    origin: Option<Name<Vec<u8>>>,
+
+    /// The number of threads to use [default: the number of CPU cores]
+    #[arg(short = 'j', long = "jobs", value_name = "NUMBER")]
+    jobs: Option<NonZeroUsize>,
+
+    /// The hashes to find names for, optionally followed by the zone name
+    #[arg(
+        value_name = "HASH",
+        required_unless_present = "zone",
+        conflicts_with = "zone"
+    )]
+    hashes: Vec<String>,
+}
+
+impl Nsec3Guess {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let mut apex: ZoneName = match &self.origin {
+            Some(origin) => origin.to_bytes(),
+            None => Name::root_bytes(),
+        };
+        let mut hashes = Vec::new();
+        let mut params = None;
+
+        if let Some(path) = &self.zone {
+            let records = read_zone_file(&env, path, None)
+                .with_context(|| format!("reading {}", path.display()))?;
+            apex = records
+                .iter()
+                .find(|r| r.rtype() == Rtype::SOA)
+                .map(|r| r.owner().clone())
+                .ok_or_else(|| format!("no SOA record in {}", path.display()))?;
+            for record in &records {
+                if let ZoneRecordData::Nsec3(nsec3) = record.data() {
+                    params.get_or_insert_with(|| {
+                        (
+                            nsec3.hash_algorithm(),
+                            nsec3.iterations(),
+                            Nsec3Salt::from_octets(nsec3.salt().as_slice().to_vec())
+                                .expect("the salt is no longer than before"),
+                        )
+                    });
+                    let label = record.owner().first();
+                    let hash = OwnerHash::<Vec<u8>>::from_str(&label.to_string())
+                        .map_err(|e| format!("invalid NSEC3 owner name {}: {e}", record.owner()))?;
+                    hashes.push(hash);
+                }
+            }
+            if hashes.is_empty() {
+                return Err(format!("no NSEC3 records in {}", path.display()).into());
+            }
+            // The NSEC3PARAM record tells which chain is in use.
+            if let Some(param) = records
+                .iter()
+                .filter(|r| r.owner().name_eq(&apex))
+                .find_map(nsec3param_of)
+            {
+                params = Some((
+                    param.hash_algorithm(),
+                    param.iterations(),
+                    param.salt().clone(),
+                ));
+            }
+        } else {
+            for hash in &self.hashes {
+                // Allow for the owner names of NSEC3 records.
+                let (label, _) = hash.split_once('.').unwrap_or((hash, ""));
+                let hash = OwnerHash::<Vec<u8>>::from_str(label)
+                    .map_err(|e| format!("invalid NSEC3 hash {hash}: {e}"))?;
+                hashes.push(hash);
+            }
+        }
+
+        let algorithm = self
+            .algorithm
+            .or(params.as_ref().map(|p| p.0))
+            .unwrap_or(Nsec3HashAlg::SHA1);
+        let iterations = self
+            .iterations
+            .or(params.as_ref().map(|p| p.1))
+            .unwrap_or(0);
+        let salt = self
+            .salt
+            .clone()
+            .or(params.map(|p| p.2))
+            .unwrap_or_else(Nsec3Salt::empty);
+        let digest = Nsec3Digest::new(algorithm)?;
+
+        let candidates = self.read_wordlist(&env, &apex)?;
+
+        // The index of each distinct hash, to report the names in order.
+        let mut targets = HashMap::new();
+        hashes.retain(|hash| {
+            let index = targets.len();
+            targets.insert(hash.as_slice().to_vec(), index).is_none()
+        });
+
+        let jobs = self
+            .jobs
+            .or_else(|| thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get);
+        let chunk_size = candidates.len().div_ceil(jobs).max(1);
+
+        let mut found: Vec<Option<&ZoneName>> = vec![None; hashes.len()];
+        thread::scope(|scope| {
+            let threads: Vec<_> = candidates
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let (targets, salt) = (&targets, &salt);
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .filter_map(|name| {
+                                let hash = digest.hash(name, iterations, salt);
+                                targets.get(hash.as_slice()).map(|&i| (i, name))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            for thread in threads {
+                for (i, name) in thread.join().expect("hashing does not panic") {
+                    found[i].get_or_insert(name);
+                }
+            }
+        });
+
+        let mut out = env.stdout();
+        for (hash, name) in hashes.iter().zip(&found) {
+            let Some(name) = name else {
+                continue;
+            };
+            let hash = hash.to_string().to_lowercase();
+            match format {
+                OutputFormat::Text => writeln!(out, "{hash} {}", name.fmt_with_dot()),
+                OutputFormat::Json => writeln!(
+                    out,
+                    "{}",
+                    serde_json::json!({
+                        "hash": hash,
+                        "name": name.fmt_with_dot().to_string(),
+                    })
+                ),
+            }
+        }
+
+        let count = found.iter().flatten().count();
+        match format {
+            OutputFormat::Text => writeln!(
+                out,
+                "found {count} of {} hashes with {} words",
+                hashes.len(),
+                candidates.len()
+            ),
+            OutputFormat::Json => writeln!(
+                out,
+                "{}",
+                serde_json::json!({
+                    "found": count,
+                    "hashes": hashes.len(),
+                    "words": candidates.len(),
+                })
+            ),
+        }
+        Ok(())
+    }
+
+    /// Read the names to try, which are the words of the wordlist below `apex`
+    ///
+    /// Invalid words are reported on stderr and skipped.
+    fn read_wordlist(&self, env: &impl Env, apex: &ZoneName) -> Result<Vec<ZoneName>, Error> {
+        let path: &Path = &self.wordlist;
+        let text = if path.as_os_str() == "-" {
+            let mut text = String::new();
+            env.stdin().read_to_string(&mut text).map(|_| text)
+        } else {
+            env.read_to_string(path)
+        }
+        .map_err(|e| format!("could not read {}: {e}", path.display()))?;
+
+        let mut names = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let word = line.trim();
+            if word.is_empty() {
+                continue;
+            }
+            match parse_name(word, apex) {
+                Ok(name) if is_in_zone(&name, apex) => names.push(name),
+                Ok(name) => writeln!(
+                    env.stderr(),
+                    "{}:{}: {} is not in the zone {}",
+                    path.display(),
+                    i + 1,
+                    name.fmt_with_dot(),
+                    apex.fmt_with_dot()
+                ),
+                Err(e) => writeln!(env.stderr(), "{}:{}: {e}", path.display(), i + 1),
+            }
+        }
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::env::fake::{signed_zone, FakeCmd};
+    use crate::keys::{KeyAlgorithm, ZSK_FLAGS};
+
+    const WORDS: &str = "www\nmail\nns1\nwww\nnot..valid\nexample.org.\n";
+
+    #[test]
+    fn dnst_parse() {
+        let cmd = FakeCmd::new(["dnst", "nsec3-guess"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["-w", "words"]).parse().is_err());
+        assert!(cmd
+            .args(["-w", "words", "v3n2r9chqlp7tdetolmmakfms81udcje"])
+            .parse()
+            .is_err());
+        assert!(cmd
+            .args(["-w", "words", "-o", "example.test"])
+            .args(["v3n2r9chqlp7tdetolmmakfms81udcje"])
+            .parse()
+            .is_ok());
+        assert!(cmd.args(["-w", "words", "-z", "zone"]).parse().is_ok());
+        assert!(cmd
+            .args(["-w", "words", "-z", "zone", "-o", "example.test"])
+            .parse()
+            .is_err());
+        assert!(cmd
+            .args(["-w", "words", "-z", "zone", "-j", "0"])
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn listed() {
+        let cmd = FakeCmd::new(["dnst", "nsec3-guess", "-t", "0", "-o", "example.test"])
+            .args(["-w", "words", "-j", "2"])
+            .file("words", WORDS);
+
+        let res = cmd
+            .args(["AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"])
+            .args(["V3N2R9CHQLP7TDETOLMMAKFMS81UDCJE"])
+            .args(["r01tvlkdfkaoshi4nmio4qj4i8vo78v4.example.test."])
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(
+            res.stdout,
+            "\
+v3n2r9chqlp7tdetolmmakfms81udcje www.example.test.
+r01tvlkdfkaoshi4nmio4qj4i8vo78v4 mail.example.test.
+found 2 of 3 hashes with 3 words
+"
+        );
+        assert_eq!(
+            res.stderr,
+            "\
+words:5: invalid domain name 'not..valid': empty label
+words:6: example.org. is not in the zone example.test.
+"
+        );
+
+        // Other parameters give other hashes.
+        let res = cmd
+            .args(["-s", "cafe", "v3n2r9chqlp7tdetolmmakfms81udcje"])
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, "found 0 of 1 hashes with 3 words\n");
+
+        let res = cmd.args(["not-a-hash"]).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("invalid NSEC3 hash not-a-hash"));
+    }
+
+    #[test]
+    fn zone() {
+        let zone = "\
+$ORIGIN example.test.
+$TTL 3600
+@       IN SOA ns1 hostmaster 2024010101 7200 3600 1209600 300
+@       IN NS  ns1
+ns1     IN A   192.0.2.1
+www     IN A   192.0.2.2
+secret  IN A   192.0.2.3
+";
+        let signed = signed_zone(
+            zone,
+            "example.test",
+            KeyAlgorithm::Ed25519,
+            &[ZSK_FLAGS],
+            &["-n", "-s", "cafe", "-t", "2"],
+        );
+
+        // The parameters are taken from the zone.
+        let res = FakeCmd::new(["dnst", "--format", "json", "nsec3-guess", "-z", "signed"])
+            .args(["-w", "-"])
+            .file("signed", signed)
+            .stdin("www\nns1\n@\n")
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        let lines: Vec<_> = res.stdout.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines
+            .contains(&r#"{"hash":"9pvv643sdep3p3euj32irslpd0gh2c00","name":"example.test."}"#));
+        assert_eq!(lines[3], r#"{"found":3,"hashes":4,"words":3}"#);
+    }
+}
@@ -1,15 +1,28 @@
-use crate::env::Env;
-use crate::error::Error;
+use crate::args::OutputFormat;
+use crate::env::{Env, StubResolve};
+use crate::error::{Context, Error, Warning};
+use crate::zone::{
+    group_nodes, is_dnssec_type, is_in_zone, parse_record, read_zone_file, sort_records, Node,
+    NodeKind, ZoneName, ZoneRecord,
+};
+use bytes::{Bytes, BytesMut};
 use clap::builder::ValueParser;
 use domain::base::iana::nsec3::Nsec3HashAlg;
-use domain::base::name::{self, Name};
+use domain::base::iana::{Rcode, Rtype};
+use domain::base::name::{self, Name, NameBuilder};
 use domain::base::ToName;
+use domain::rdata::dnssec::RtypeBitmap;
 use domain::rdata::nsec3::{Nsec3Salt, OwnerHash};
+use domain::rdata::{Nsec3param, ZoneRecordData};
+use domain::utils::base32;
 use lexopt::Arg;
 // use domain::validator::nsec::nsec3_hash;
 use octseq::OctetsBuilder;
 use ring::digest;
+use std::collections::BTreeSet;
 use std::ffi::OsString;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use super::{parse_os, parse_os_with, LdnsCommand};
@@ -40,9 +53,68 @@ pub struct Nsec3Hash {
     #[arg(short = 's', long, value_name = "HEX_STRING", default_value_t = Nsec3Salt::empty())]
     salt: Nsec3Salt<Vec<u8>>,
 
-    /// The domain name to hash
-    #[arg(value_name = "DOMAIN_NAME", value_parser = ValueParser::new(Nsec3Hash::parse_name))]
-    name: Name<Vec<u8>>,
+    /// Take the algorithm, iterations and salt from an NSEC3PARAM record
+    ///
+    /// The record can be given in full or as just its data, e.g. "1 0 0 -".
+    #[arg(
+        long = "nsec3param",
+        value_name = "RECORD",
+        conflicts_with_all = ["algorithm", "iterations", "salt", "zonefile", "query"]
+    )]
+    nsec3param: Option<String>,
+
+    /// Take the algorithm, iterations and salt from the NSEC3PARAM record of a zone file
+    #[arg(
+        long = "zonefile",
+        value_name = "ZONEFILE",
+        conflicts_with_all = ["algorithm", "iterations", "salt", "query"]
+    )]
+    zonefile: Option<PathBuf>,
+
+    /// Take the algorithm, iterations and salt from the NSEC3PARAM record of a zone,
+    /// queried from the name servers in /etc/resolv.conf
+    #[arg(
+        long = "query",
+        value_name = "ZONE",
+        value_parser = ValueParser::new(ZoneName::from_str),
+        conflicts_with_all = ["algorithm", "iterations", "salt"]
+    )]
+    query: Option<ZoneName>,
+
+    /// Read newline-separated domain names from a file, or stdin with `-`
+    #[arg(short = 'f', long = "file", value_name = "FILE")]
+    file: Option<PathBuf>,
+
+    /// Hash the owner names in a zone file instead
+    #[arg(
+        long = "names-from-zone",
+        value_name = "ZONEFILE",
+        conflicts_with_all = ["file", "names"]
+    )]
+    names_from_zone: Option<PathBuf>,
+
+    /// Print the NSEC3 chain of the names, sorted by hash
+    ///
+    /// Each line has the hash of a name, the name and the hash of the next
+    /// name in the chain. Empty non-terminals are added to the chain.
+    #[arg(long = "chain")]
+    chain: bool,
+
+    /// Leave insecure delegations out of the NSEC3 chain
+    #[arg(long = "opt-out", requires_all = ["chain", "names_from_zone"])]
+    opt_out: bool,
+
+    /// The domain names to hash
+    #[arg(
+        value_name = "DOMAIN_NAME",
+        required_unless_present_any = ["file", "names_from_zone"],
+        value_parser = ValueParser::new(Nsec3Hash::parse_name)
+    )]
+    names: Vec<Name<Vec<u8>>>,
+
+    /// Whether to check the parameters against RFC 9276, which ldns does not
+    #[arg(skip = true)]
+    lint: bool,
 }
 
 const LDNS_HELP: &str = "\
@@ -102,7 +174,15 @@ impl LdnsCommand for Nsec3Hash {
             algorithm,
             iterations,
             salt,
-            name,
+            nsec3param: None,
+            zonefile: None,
+            query: None,
+            file: None,
+            names_from_zone: None,
+            chain: false,
+            opt_out: false,
+            names: vec![name],
+            lint: false,
         })
     }
 }
@@ -113,80 +193,586 @@ impl Nsec3Hash {
     }
 
     pub fn parse_nsec_alg(arg: &str) -> Result<Nsec3HashAlg, &'static str> {
-        if let Ok(num) = arg.parse() {
-            let alg = Nsec3HashAlg::from_int(num);
-            // check for valid algorithm here, to be consistent with error messages
-            // if domain::validator::nsec::supported_nsec3_hash(alg) {
-            if alg.to_mnemonic().is_some() {
-                Ok(alg)
-            } else {
-                Err("unknown algorithm number")
-            }
+        let alg = if let Ok(num) = arg.parse() {
+            Nsec3HashAlg::from_int(num)
+        } else {
+            Nsec3HashAlg::from_mnemonic(arg.as_bytes()).ok_or("unknown algorithm mnemonic")?
+        };
+        // Check for a supported algorithm here, so that hashing cannot fail.
+        if Nsec3Digest::is_supported(alg) {
+            Ok(alg)
         } else {
-            Nsec3HashAlg::from_mnemonic(arg.as_bytes()).ok_or("unknown algorithm mnemonic")
+            Err("unsupported algorithm")
         }
     }
 }
 
 impl Nsec3Hash {
-    pub fn execute(self, env: impl Env) -> Result<(), Error> {
-        let hash = nsec3_hash(&self.name, self.algorithm, self.iterations, &self.salt)
-            .to_string()
-            .to_lowercase();
+    pub fn execute(mut self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let params = self.load_nsec3param(&env)?;
+        let from_zone = params.is_some();
+        if let Some(params) = params {
+            self.algorithm = params.hash_algorithm();
+            self.iterations = params.iterations();
+            self.salt = params.salt().clone();
+        }
+        if self.lint {
+            // The parameters of an existing zone are only warned about, so
+            // that such zones can still be inspected.
+            let warnings = if from_zone {
+                audit_nsec3_params(self.iterations, &self.salt)
+            } else {
+                lint_nsec3_params(self.iterations, &self.salt)?
+            };
+            for warning in warnings {
+                warning.pretty_print(&env, format);
+            }
+        }
+        let digest = Nsec3Digest::new(self.algorithm)?;
 
+        // A single name is printed without the name, like ldns does.
+        if let (None, None, false, [name]) = (
+            &self.file,
+            &self.names_from_zone,
+            self.chain,
+            &self.names[..],
+        ) {
+            self.print_hash(&env, format, digest, &name.to_bytes(), false);
+            return Ok(());
+        }
+
+        let mut names: Vec<ZoneName> = self.names.iter().map(ToName::to_bytes).collect();
+        let mut invalid = 0;
+        if let Some(path) = &self.file {
+            invalid = if path.as_os_str() == "-" {
+                read_names(&env, BufReader::new(env.stdin()), path, &mut names)
+            } else {
+                let file = env
+                    .open(path)
+                    .map_err(|e| format!("could not open {}: {e}", path.display()))?;
+                read_names(&env, BufReader::new(file), path, &mut names)
+            }
+            .map_err(|e| format!("could not read {}: {e}", path.display()))?;
+        }
+
+        if self.chain {
+            self.print_chain(&env, format, digest, names)?;
+        } else if let Some(path) = &self.names_from_zone {
+            let (_, nodes) = load_nodes(&env, path)?;
+            for node in nodes.iter().filter(|n| n.kind != NodeKind::Occluded) {
+                self.print_hash(&env, format, digest, &node.owner, true);
+            }
+        } else {
+            for name in &names {
+                self.print_hash(&env, format, digest, name, true);
+            }
+        }
+
+        if invalid > 0 {
+            let path = self
+                .file
+                .as_ref()
+                .expect("only names from a file are invalid");
+            return Err(format!("{invalid} invalid domain name(s) in {}", path.display()).into());
+        }
+        Ok(())
+    }
+
+    /// Print the NSEC3 chain of the given names or the names of the zone
+    ///
+    /// Without a zone, the closest common ancestor of the names is taken
+    /// as the apex.
+    fn print_chain(
+        &self,
+        env: &impl Env,
+        format: OutputFormat,
+        digest: Nsec3Digest,
+        names: Vec<ZoneName>,
+    ) -> Result<(), Error> {
+        let (apex, names) = match &self.names_from_zone {
+            Some(path) => {
+                let (apex, nodes) = load_nodes(env, path)?;
+                let names = nsec3_names(&nodes, self.opt_out);
+                (apex, names)
+            }
+            None => {
+                let apex = closest_common_ancestor(&names).ok_or("no names to hash")?;
+                let empty = RtypeBitmap::<Bytes>::builder().finalize();
+                let names = names.into_iter().map(|n| (n, empty.clone())).collect();
+                (apex, names)
+            }
+        };
+
+        let chain = nsec3_chain(names, &apex, digest, self.iterations, &self.salt)?;
         let mut out = env.stdout();
-        writeln!(out, "{}.", hash);
+        for (i, (hash, name, _)) in chain.iter().enumerate() {
+            let next = &chain[(i + 1) % chain.len()].0;
+            let (hash, next) = (
+                hash.to_string().to_lowercase(),
+                next.to_string().to_lowercase(),
+            );
+            match format {
+                OutputFormat::Text => writeln!(out, "{hash} {} {next}", name.fmt_with_dot()),
+                OutputFormat::Json => writeln!(
+                    out,
+                    "{}",
+                    serde_json::json!({
+                        "hash": hash,
+                        "name": name.fmt_with_dot().to_string(),
+                        "next": next,
+                    })
+                ),
+            }
+        }
         Ok(())
     }
+
+    /// Print the hash of a name
+    ///
+    /// In the text format, the name is only printed if `with_name` is set.
+    fn print_hash(
+        &self,
+        env: &impl Env,
+        format: OutputFormat,
+        digest: Nsec3Digest,
+        name: &ZoneName,
+        with_name: bool,
+    ) {
+        let hash = self.hash(digest, name);
+        match format {
+            OutputFormat::Text if with_name => {
+                writeln!(env.stdout(), "{} {hash}.", name.fmt_with_dot())
+            }
+            OutputFormat::Text => writeln!(env.stdout(), "{hash}."),
+            OutputFormat::Json => writeln!(
+                env.stdout(),
+                "{}",
+                serde_json::json!({
+                    "name": name.fmt_with_dot().to_string(),
+                    "hash": hash,
+                    "algorithm": self.algorithm.to_int(),
+                    "iterations": self.iterations,
+                    "salt": hex(self.salt.as_slice()),
+                })
+            ),
+        }
+    }
+
+    /// Get the NSEC3PARAM record to take the hash parameters from, if any
+    fn load_nsec3param(&self, env: &impl Env) -> Result<Option<Nsec3param<Vec<u8>>>, Error> {
+        if let Some(text) = &self.nsec3param {
+            // If the text is not a full record, it may be just the data.
+            let record = parse_record(text, &ZoneName::root()).or_else(|err| {
+                parse_record(&format!(". NSEC3PARAM {text}"), &ZoneName::root()).map_err(|_| err)
+            })?;
+            return match nsec3param_of(&record) {
+                Some(params) => Ok(Some(params)),
+                None => Err(format!("'{text}' is not an NSEC3PARAM record").into()),
+            };
+        }
+
+        if let Some(path) = &self.zonefile {
+            let records = read_zone_file(env, path, None)?;
+            let apex = records
+                .iter()
+                .find(|r| r.rtype() == Rtype::SOA)
+                .map(|r| r.owner().clone())
+                .ok_or_else(|| format!("no SOA record in {}", path.display()))?;
+            return records
+                .iter()
+                .filter(|r| r.owner() == &apex)
+                .find_map(nsec3param_of)
+                .map(Some)
+                .ok_or_else(|| {
+                    format!("no NSEC3PARAM record at the apex of {}", path.display()).into()
+                });
+        }
+
+        if let Some(zone) = &self.query {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| format!("could not start the runtime: {e}"))?;
+            return runtime
+                .block_on(query_nsec3param(env, zone))
+                .with_context(|| {
+                    format!("querying the NSEC3PARAM record of {}", zone.fmt_with_dot())
+                })
+                .map(Some);
+        }
+
+        Ok(None)
+    }
+
+    fn hash(&self, digest: Nsec3Digest, name: &impl ToName) -> String {
+        digest
+            .hash(name, self.iterations, &self.salt)
+            .to_string()
+            .to_lowercase()
+    }
 }
 
-// XXX: This is a verbatim copy of the nsec3_hash function from domain::validator::nsec.
-// TODO: when exposed/available, replace with implementation from domain::validator::nsec
-fn nsec3_hash<N, HashOcts>(
-    owner: N,
-    algorithm: Nsec3HashAlg,
-    iterations: u16,
-    salt: &Nsec3Salt<HashOcts>,
-) -> OwnerHash<Vec<u8>>
-where
-    N: ToName,
-    HashOcts: AsRef<[u8]>,
-{
-    let mut buf = Vec::new();
+/// Format bytes, e.g. a salt, in lowercase hex
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
 
-    owner.compose_canonical(&mut buf).expect("infallible");
-    buf.append_slice(salt.as_slice()).expect("infallible");
+/// Read the names on the lines of `reader` into `names`
+///
+/// Invalid names are reported on stderr and skipped. Returns the number of
+/// invalid names.
+fn read_names(
+    env: &impl Env,
+    reader: impl BufRead,
+    path: &Path,
+    names: &mut Vec<ZoneName>,
+) -> io::Result<usize> {
+    let mut invalid = 0;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match Nsec3Hash::parse_name(line) {
+            Ok(name) => names.push(name.to_bytes()),
+            Err(e) => {
+                invalid += 1;
+                writeln!(
+                    env.stderr(),
+                    "{}:{}: invalid domain name `{line}`: {e}",
+                    path.display(),
+                    i + 1
+                );
+            }
+        }
+    }
+    Ok(invalid)
+}
+
+/// Read a zone file and group its records into nodes
+fn load_nodes(env: &impl Env, path: &Path) -> Result<(ZoneName, Vec<Node>), Error> {
+    let mut records = read_zone_file(env, path, None)?;
+    let apex = records
+        .iter()
+        .find(|r| r.rtype() == Rtype::SOA)
+        .map(|r| r.owner().clone())
+        .ok_or_else(|| format!("no SOA record in {}", path.display()))?;
+    // The records of a signed zone are hashed as if it was not signed.
+    records.retain(|r| !is_dnssec_type(r.rtype()) && is_in_zone(r.owner(), &apex));
+    sort_records(&mut records);
+    let nodes = group_nodes(records, &apex);
+    Ok((apex, nodes))
+}
+
+/// Get the closest name that all names are at or below
+fn closest_common_ancestor(names: &[ZoneName]) -> Option<ZoneName> {
+    let (first, rest) = names.split_first()?;
+    let mut ancestor = first.clone();
+    for name in rest {
+        while !name.ends_with(&ancestor) {
+            ancestor = ancestor
+                .parent()
+                .expect("the root is an ancestor of all names");
+        }
+    }
+    Some(ancestor)
+}
 
-    let digest_type = if algorithm == Nsec3HashAlg::SHA1 {
-        &digest::SHA1_FOR_LEGACY_USE_ONLY
-    } else {
-        // totest, unsupported NSEC3 hash algorithm
-        // Unsupported.
-        panic!("should not be called with an unsupported algorithm");
+/// Get the data of a record if it is an NSEC3PARAM record
+pub fn nsec3param_of(record: &ZoneRecord) -> Option<Nsec3param<Vec<u8>>> {
+    let ZoneRecordData::Nsec3param(params) = record.data() else {
+        return None;
     };
+    Some(Nsec3param::new(
+        params.hash_algorithm(),
+        params.flags(),
+        params.iterations(),
+        Nsec3Salt::from_octets(params.salt().as_slice().to_vec()).expect("salt is not too long"),
+    ))
+}
+
+/// Query the name servers from `/etc/resolv.conf` for the NSEC3PARAM record of a zone
+async fn query_nsec3param(env: &impl Env, zone: &ZoneName) -> Result<Nsec3param<Vec<u8>>, Error> {
+    let response = env
+        .make_stub_resolver()
+        .query(zone, Rtype::NSEC3PARAM)
+        .await
+        .map_err(|e| format!("could not query the name servers: {e}"))?;
+
+    let rcode = response.header().rcode();
+    if rcode != Rcode::NOERROR {
+        return Err(format!("NSEC3PARAM query failed with {rcode}").into());
+    }
+
+    let answer = response
+        .answer()
+        .map_err(|e| format!("invalid response: {e}"))?;
+    for record in answer.limit_to::<Nsec3param<_>>().flatten() {
+        if record.owner() != zone {
+            continue;
+        }
+        let params = record.data();
+        let salt = Nsec3Salt::from_octets(params.salt().as_slice().to_vec())
+            .expect("salt is not too long");
+        return Ok(Nsec3param::new(
+            params.hash_algorithm(),
+            params.flags(),
+            params.iterations(),
+            salt,
+        ));
+    }
+
+    Err("no NSEC3PARAM record found".into())
+}
+
+/// An NSEC3 hash algorithm using SHA-256, which is not assigned by IANA
+///
+/// This is only meant for experimenting with other hash algorithms and must
+/// never be used for zones on the internet.
+#[cfg(feature = "unstable-nsec3-sha256")]
+pub const NSEC3_SHA256_EXPERIMENTAL: Nsec3HashAlg = Nsec3HashAlg::from_int(254);
+
+/// The digests of the supported NSEC3 hash algorithms
+///
+/// Adding an entry here is all it takes to support a new algorithm.
+const NSEC3_DIGESTS: &[(Nsec3HashAlg, &digest::Algorithm)] = &[
+    (Nsec3HashAlg::SHA1, &digest::SHA1_FOR_LEGACY_USE_ONLY),
+    #[cfg(feature = "unstable-nsec3-sha256")]
+    (NSEC3_SHA256_EXPERIMENTAL, &digest::SHA256),
+];
 
-    let mut ctx = digest::Context::new(digest_type);
-    ctx.update(&buf);
-    let mut h = ctx.finish();
+/// The digest of a supported NSEC3 hash algorithm
+///
+/// Looking up the digest once allows for hashing many names without
+/// checking the algorithm for each of them.
+#[derive(Clone, Copy, Debug)]
+pub struct Nsec3Digest(&'static digest::Algorithm);
 
-    for _ in 0..iterations {
-        buf.truncate(0);
-        buf.append_slice(h.as_ref()).expect("infallible");
+impl Nsec3Digest {
+    /// Get the digest of an NSEC3 hash algorithm
+    ///
+    /// Returns an error if the algorithm is not supported.
+    pub fn new(algorithm: Nsec3HashAlg) -> Result<Self, Error> {
+        NSEC3_DIGESTS
+            .iter()
+            .find(|(alg, _)| *alg == algorithm)
+            .map(|(_, digest)| Self(digest))
+            .ok_or_else(|| format!("unsupported NSEC3 hash algorithm {algorithm}").into())
+    }
+
+    /// Whether an NSEC3 hash algorithm is supported
+    pub fn is_supported(algorithm: Nsec3HashAlg) -> bool {
+        NSEC3_DIGESTS.iter().any(|(alg, _)| *alg == algorithm)
+    }
+
+    /// Compute the NSEC3 hash of an owner name (RFC 5155, section 5)
+    pub fn hash<N, HashOcts>(
+        self,
+        owner: N,
+        iterations: u16,
+        salt: &Nsec3Salt<HashOcts>,
+    ) -> OwnerHash<Vec<u8>>
+    where
+        N: ToName,
+        HashOcts: AsRef<[u8]>,
+    {
+        let mut buf = Vec::new();
+
+        owner.compose_canonical(&mut buf).expect("infallible");
         buf.append_slice(salt.as_slice()).expect("infallible");
 
-        let mut ctx = digest::Context::new(digest_type);
+        let mut ctx = digest::Context::new(self.0);
         ctx.update(&buf);
-        h = ctx.finish();
+        let mut h = ctx.finish();
+
+        for _ in 0..iterations {
+            buf.truncate(0);
+            buf.append_slice(h.as_ref()).expect("infallible");
+            buf.append_slice(salt.as_slice()).expect("infallible");
+
+            let mut ctx = digest::Context::new(self.0);
+            ctx.update(&buf);
+            h = ctx.finish();
+        }
+
+        // For normal hash algorithms this should not fail.
+        OwnerHash::from_octets(h.as_ref().to_vec()).expect("should not fail")
     }
+}
 
-    // For normal hash algorithms this should not fail.
-    OwnerHash::from_octets(h.as_ref().to_vec()).expect("should not fail")
+/// The most NSEC3 iterations that validators commonly accept
+///
+/// RFC 9276 allows validators to treat responses with more iterations as
+/// insecure or even bogus, and many of them do so above this number.
+pub const MAX_NSEC3_ITERATIONS: u16 = 150;
+
+/// Check NSEC3 parameters against the guidance of RFC 9276
+///
+/// Returns warnings for additional iterations and for a salt, which RFC 9276
+/// recommends against, and an error for more iterations than validators
+/// accept.
+pub fn lint_nsec3_params<SaltOcts: AsRef<[u8]>>(
+    iterations: u16,
+    salt: &Nsec3Salt<SaltOcts>,
+) -> Result<Vec<Warning>, Error> {
+    if iterations > MAX_NSEC3_ITERATIONS {
+        return Err(too_many_iterations(iterations).into());
+    }
+    Ok(audit_nsec3_params(iterations, salt))
+}
+
+/// Check the NSEC3 parameters of an existing zone against RFC 9276
+///
+/// Like [`lint_nsec3_params`], but more iterations than validators accept
+/// is a warning too, because the zone is only inspected.
+pub fn audit_nsec3_params<SaltOcts: AsRef<[u8]>>(
+    iterations: u16,
+    salt: &Nsec3Salt<SaltOcts>,
+) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    if iterations > MAX_NSEC3_ITERATIONS {
+        warnings.push(too_many_iterations(iterations).into());
+    } else if iterations > 0 {
+        warnings
+            .push(format!("using {iterations} NSEC3 iterations, but RFC 9276 recommends 0").into());
+    }
+    if !salt.as_slice().is_empty() {
+        let salt = hex(salt.as_slice());
+        warnings
+            .push(format!("using the NSEC3 salt {salt}, but RFC 9276 recommends no salt").into());
+    }
+    warnings
+}
+
+fn too_many_iterations(iterations: u16) -> String {
+    format!(
+        "using {iterations} NSEC3 iterations, but many validators do not accept \
+         more than {MAX_NSEC3_ITERATIONS} and RFC 9276 recommends 0"
+    )
+}
+
+/// Get the names of a zone that need an NSEC3 record, with their types
+///
+/// With opt-out, insecure delegations are left out. The empty non-terminals
+/// are added by [`nsec3_chain`].
+pub fn nsec3_names(nodes: &[Node], opt_out: bool) -> Vec<(ZoneName, RtypeBitmap<Bytes>)> {
+    let mut names = Vec::new();
+    for node in nodes {
+        let mut types = RtypeBitmap::<Bytes>::builder();
+        match node.kind {
+            NodeKind::Occluded => continue,
+            NodeKind::Delegation => {
+                let secure = node.has_rtype(Rtype::DS);
+                if opt_out && !secure {
+                    continue;
+                }
+                types.add(Rtype::NS).expect("infallible");
+                if secure {
+                    types.add(Rtype::DS).expect("infallible");
+                    types.add(Rtype::RRSIG).expect("infallible");
+                }
+            }
+            NodeKind::Apex | NodeKind::Authoritative => {
+                for rtype in node.rtypes() {
+                    types.add(rtype).expect("infallible");
+                }
+                types.add(Rtype::RRSIG).expect("infallible");
+            }
+        }
+        names.push((node.owner.clone(), types.finalize()));
+    }
+    names
+}
+
+/// Build the owner name of an NSEC3 record from the hash
+pub fn nsec3_owner(hash: &OwnerHash<Vec<u8>>, apex: &ZoneName) -> Result<ZoneName, Error> {
+    let label = base32::encode_string_hex(hash.as_slice()).to_lowercase();
+    let mut builder = NameBuilder::<BytesMut>::new();
+    builder
+        .append_label(label.as_bytes())
+        .map_err(|_| "invalid NSEC3 hash")?;
+    builder
+        .append_origin(apex)
+        .map_err(|_| "NSEC3 owner name too long".into())
+}
+
+/// A link of an NSEC3 chain: the hash of a name, the name and its types
+pub type Nsec3Link = (OwnerHash<Vec<u8>>, ZoneName, RtypeBitmap<Bytes>);
+
+/// Build the NSEC3 chain of the names of the zone at `apex`, sorted by hash
+///
+/// Every empty non-terminal between a name and the apex is added to the
+/// chain with an empty type bitmap. Returns an error if two names have the
+/// same hash.
+pub fn nsec3_chain<HashOcts: AsRef<[u8]>>(
+    mut names: Vec<(ZoneName, RtypeBitmap<Bytes>)>,
+    apex: &ZoneName,
+    digest: Nsec3Digest,
+    iterations: u16,
+    salt: &Nsec3Salt<HashOcts>,
+) -> Result<Vec<Nsec3Link>, Error> {
+    let existing: BTreeSet<_> = names.iter().map(|(n, _)| n.clone()).collect();
+    let mut empty_non_terminals = BTreeSet::new();
+    for (name, _) in &names {
+        let mut parent = name.parent();
+        while let Some(name) = parent {
+            if !is_in_zone(&name, apex) || existing.contains(&name) {
+                break;
+            }
+            parent = name.parent();
+            empty_non_terminals.insert(name);
+        }
+    }
+    let empty = RtypeBitmap::<Bytes>::builder().finalize();
+    names.extend(empty_non_terminals.into_iter().map(|n| (n, empty.clone())));
+
+    let mut chain: Vec<_> = names
+        .into_iter()
+        .map(|(name, types)| (digest.hash(&name, iterations, salt), name, types))
+        .collect();
+    chain.sort_by(|a, b| a.0.as_slice().cmp(b.0.as_slice()));
+    // The same name may have been given more than once.
+    chain.dedup_by(|a, b| a.1 == b.1);
+
+    if let Some(w) = chain.windows(2).find(|w| w[0].0 == w[1].0) {
+        return Err(format!(
+            "NSEC3 hash collision between {} and {}, try a different salt",
+            w[0].1.fmt_with_dot(),
+            w[1].1.fmt_with_dot()
+        )
+        .into());
+    }
+
+    Ok(chain)
+}
+
+// XXX: This started as a copy of the nsec3_hash function from
+// domain::validator::nsec, but returns an error for unsupported algorithms.
+// TODO: when exposed/available, replace with implementation from domain::validator::nsec
+pub fn nsec3_hash<N, HashOcts>(
+    owner: N,
+    algorithm: Nsec3HashAlg,
+    iterations: u16,
+    salt: &Nsec3Salt<HashOcts>,
+) -> Result<OwnerHash<Vec<u8>>, Error>
+where
+    N: ToName,
+    HashOcts: AsRef<[u8]>,
+{
+    Ok(Nsec3Digest::new(algorithm)?.hash(owner, iterations, salt))
 }
 
 #[cfg(test)]
 mod test {
+    use domain::base::iana::nsec3::Nsec3HashAlg;
+    use domain::base::name::Name;
+    use domain::rdata::nsec3::Nsec3Salt;
+
     use crate::env::fake::FakeCmd;
 
+    use super::nsec3_hash;
+
     #[test]
     fn dnst_parse() {
         let cmd = FakeCmd::new(["dnst", "nsec3-hash"]);
@@ -207,6 +793,284 @@ mod test {
         assert_eq!(res.stdout, "o09614ibh1cq1rcc86289olr22ea0fso.\n")
     }
 
+    #[test]
+    fn batch() {
+        let cmd = FakeCmd::new(["dnst", "nsec3-hash"]);
+
+        let res = cmd.args(["example.test", "Example.test."]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(
+            res.stdout,
+            "example.test. o09614ibh1cq1rcc86289olr22ea0fso.\n\
+             example.test. o09614ibh1cq1rcc86289olr22ea0fso.\n"
+        );
+
+        let res = cmd
+            .args(["--file", "names"])
+            .file("names", "example.test\n\nexample..test\nEXAMPLE.TEST.\n")
+            .run();
+        assert_eq!(res.exit_code, 1);
+        assert_eq!(
+            res.stdout,
+            "example.test. o09614ibh1cq1rcc86289olr22ea0fso.\n\
+             example.test. o09614ibh1cq1rcc86289olr22ea0fso.\n"
+        );
+        assert!(res
+            .stderr
+            .contains("names:3: invalid domain name `example..test`: "));
+        assert!(res.stderr.contains("1 invalid domain name(s) in names"));
+
+        let res = cmd.args(["-f", "-"]).stdin("example.test\n").run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(
+            res.stdout,
+            "example.test. o09614ibh1cq1rcc86289olr22ea0fso.\n"
+        );
+
+        let res = cmd.args(["-f", "missing"]).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("could not open missing"));
+    }
+
+    #[test]
+    fn chain() {
+        let cmd = FakeCmd::new(["dnst", "nsec3-hash", "--chain"]);
+
+        // The empty non-terminal b.example.test is added and the last link
+        // points back to the first.
+        let res = cmd.args(["a.b.example.test", "ns.example.test"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(
+            res.stdout,
+            "5035i5akpgniemhd3bdoectlmj2jm5r4 ns.example.test. ddvrrr9ucku0dauatlmab7n3nrgmtso3\n\
+             ddvrrr9ucku0dauatlmab7n3nrgmtso3 a.b.example.test. mqhlgbfmnm6uhfmk3p95ac7ojsihfcn0\n\
+             mqhlgbfmnm6uhfmk3p95ac7ojsihfcn0 b.example.test. o09614ibh1cq1rcc86289olr22ea0fso\n\
+             o09614ibh1cq1rcc86289olr22ea0fso example.test. 5035i5akpgniemhd3bdoectlmj2jm5r4\n"
+        );
+    }
+
+    #[test]
+    fn chain_from_zone() {
+        let zone = "\
+            example.test. 3600 IN SOA ns.example.test. admin.example.test. 1 3600 900 86400 300\n\
+            example.test. 3600 IN NS ns.example.test.\n\
+            ns.example.test. 3600 IN A 192.0.2.1\n\
+            a.b.example.test. 3600 IN A 192.0.2.2\n\
+            sub.example.test. 3600 IN NS ns.sub.example.test.\n\
+            ns.sub.example.test. 3600 IN A 192.0.2.3\n";
+        let cmd =
+            FakeCmd::new(["dnst", "nsec3-hash", "--names-from-zone", "zone"]).file("zone", zone);
+
+        // The glue below the delegation is not hashed.
+        let res = cmd.run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(
+            res.stdout,
+            "example.test. o09614ibh1cq1rcc86289olr22ea0fso.\n\
+             a.b.example.test. ddvrrr9ucku0dauatlmab7n3nrgmtso3.\n\
+             ns.example.test. 5035i5akpgniemhd3bdoectlmj2jm5r4.\n\
+             sub.example.test. qf7cl6nlcsroqh328ti5kmvq8dninis3.\n"
+        );
+
+        let res = cmd.args(["--chain"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res.stdout.contains(
+            "o09614ibh1cq1rcc86289olr22ea0fso example.test. qf7cl6nlcsroqh328ti5kmvq8dninis3\n\
+             qf7cl6nlcsroqh328ti5kmvq8dninis3 sub.example.test. 5035i5akpgniemhd3bdoectlmj2jm5r4\n"
+        ));
+
+        // The insecure delegation is left out with opt-out.
+        let res = cmd.args(["--chain", "--opt-out"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(!res.stdout.contains("sub.example.test."));
+        assert!(res.stdout.ends_with(
+            "o09614ibh1cq1rcc86289olr22ea0fso example.test. 5035i5akpgniemhd3bdoectlmj2jm5r4\n"
+        ));
+
+        let res = FakeCmd::new(["dnst", "nsec3-hash", "--opt-out", "example.test"]).parse();
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn lint() {
+        let cmd = FakeCmd::new(["dnst", "nsec3-hash"]);
+
+        let res = cmd.args(["-t", "0", "example.test"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stderr, "");
+
+        // Warnings do not change the output.
+        let res = cmd.args(["-t", "1", "-s", "CAFE", "example.test"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, "ffi36mroh02t7kpak68mfbsoerkjlp8u.\n");
+        assert!(res
+            .stderr
+            .contains("WARNING: using 1 NSEC3 iterations, but RFC 9276 recommends 0\n"));
+        assert!(res
+            .stderr
+            .contains("WARNING: using the NSEC3 salt cafe, but RFC 9276 recommends no salt\n"));
+
+        let res = cmd.args(["-t", "150", "example.test"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+
+        let res = cmd.args(["-t", "151", "example.test"]).run();
+        assert_eq!(res.exit_code, 1);
+        assert_eq!(res.stdout, "");
+        assert!(res
+            .stderr
+            .contains("many validators do not accept more than 150"));
+
+        // The parameters of an existing zone are only warned about.
+        let res = cmd
+            .args(["--nsec3param", "1 0 151 -", "example.test"])
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, "do1grupt87pbjuvqj0j4v3vgobt2q96q.\n");
+        assert!(res
+            .stderr
+            .contains("WARNING: using 151 NSEC3 iterations, but many validators do not accept"));
+
+        // ldns does not check the parameters.
+        let res = FakeCmd::new(["ldns-nsec3-hash", "-t", "151", "example.test"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stderr, "");
+    }
+
+    #[test]
+    fn nsec3param() {
+        let cmd = FakeCmd::new(["dnst", "nsec3-hash"]);
+        let expected = cmd.args(["-t", "10", "-s", "cafe", "example.test"]).run();
+        assert_eq!(expected.exit_code, 0, "{}", expected.stderr);
+
+        let res = cmd
+            .args(["--nsec3param", "1 0 10 CAFE", "example.test"])
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, expected.stdout);
+
+        let res = cmd
+            .args(["--nsec3param", "example.test. 0 IN NSEC3PARAM 1 0 10 cafe"])
+            .args(["example.test"])
+            .run();
+        assert_eq!(res.stdout, expected.stdout);
+
+        let res = cmd
+            .args(["--nsec3param", "example.test. A 192.0.2.1", "example.test"])
+            .run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("is not an NSEC3PARAM record"));
+
+        // The parameters cannot be given twice.
+        assert!(cmd
+            .args(["--nsec3param", "1 0 10 cafe", "-s", "cafe", "example.test"])
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn nsec3param_zonefile() {
+        let cmd = FakeCmd::new(["dnst", "nsec3-hash"]);
+        let expected = cmd.args(["-t", "10", "-s", "cafe", "example.test"]).run();
+
+        let res = cmd
+            .args(["--zonefile", "zone", "example.test"])
+            .file(
+                "zone",
+                "example.test. 3600 IN SOA ns1.example.test. hostmaster.example.test. 1 3600 600 86400 300\n\
+                 example.test. 0 IN NSEC3PARAM 1 0 10 cafe\n",
+            )
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, expected.stdout);
+
+        let res = cmd
+            .args(["--zonefile", "unsigned", "example.test"])
+            .file(
+                "unsigned",
+                "example.test. 3600 IN SOA ns1.example.test. hostmaster.example.test. 1 3600 600 86400 300\n",
+            )
+            .run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res
+            .stderr
+            .contains("no NSEC3PARAM record at the apex of unsigned"));
+    }
+
+    #[test]
+    fn nsec3param_query() {
+        let script = "\
+CONFIG_END
+
+SCENARIO_BEGIN A resolver knowing the NSEC3PARAM record of example.test
+
+RANGE_BEGIN 0 100
+ENTRY_BEGIN
+MATCH opcode qtype qname
+ADJUST copy_id
+REPLY QR RD RA NOERROR
+SECTION QUESTION
+example.test. IN NSEC3PARAM
+SECTION ANSWER
+example.test. 0 IN NSEC3PARAM 1 0 10 CAFE
+ENTRY_END
+RANGE_END
+
+SCENARIO_END
+";
+        let cmd = FakeCmd::new(["dnst", "nsec3-hash"]);
+        let expected = cmd.args(["-t", "10", "-s", "cafe", "example.test"]).run();
+
+        let res = cmd
+            .args(["--query", "example.test", "example.test"])
+            .stelline("nsec3param", script)
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, expected.stdout);
+    }
+
+    #[test]
+    fn unsupported_algorithm() {
+        let salt = Nsec3Salt::<Vec<u8>>::empty();
+        let name = Name::<Vec<u8>>::root();
+        assert!(nsec3_hash(&name, Nsec3HashAlg::SHA1, 0, &salt).is_ok());
+        let err = nsec3_hash(&name, Nsec3HashAlg::from_int(3), 0, &salt).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported NSEC3 hash algorithm 3");
+
+        let cmd = FakeCmd::new(["dnst", "nsec3-hash"]);
+        assert!(cmd.args(["-a", "3", "example.test"]).parse().is_err());
+
+        let res = cmd.args(["--nsec3param", "3 0 0 -", "example.test"]).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("unsupported NSEC3 hash algorithm 3"));
+    }
+
+    #[test]
+    fn json() {
+        let res = FakeCmd::new(["dnst", "--format", "json", "nsec3-hash", "-t", "1"])
+            .args(["example.test"])
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(
+            res.stdout,
+            "{\"algorithm\":1,\"hash\":\"o09614ibh1cq1rcc86289olr22ea0fso\",\"iterations\":1,\"name\":\"example.test.\",\"salt\":\"\"}\n"
+        );
+        assert_eq!(
+            res.stderr,
+            "{\"warning\":\"using 1 NSEC3 iterations, but RFC 9276 recommends 0\"}\n"
+        );
+    }
+
+    #[cfg(feature = "unstable-nsec3-sha256")]
+    #[test]
+    fn experimental_sha256() {
+        let res = FakeCmd::new(["dnst", "nsec3-hash", "-a", "254", "-t", "0"])
+            .args(["example.test"])
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        // SHA-256 hashes are 32 bytes, or 52 base32 characters.
+        assert_eq!(res.stdout.trim_end().len(), 53);
+    }
+
     #[test]
     fn ldns_parse() {
         let cmd = FakeCmd::new(["ldns-nsec3-hash"]);
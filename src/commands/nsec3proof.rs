@@ -0,0 +1,485 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use clap::builder::ValueParser;
+use domain::base::iana::nsec3::Nsec3HashAlg;
+use domain::base::iana::Rtype;
+use domain::base::name::{Name, RelativeName};
+use domain::base::ToName;
+use domain::rdata::nsec3::{Nsec3Salt, OwnerHash};
+use domain::rdata::ZoneRecordData;
+
+use crate::args::OutputFormat;
+use crate::env::Env;
+use crate::error::{Context, Error};
+use crate::zone::{group_nodes, is_dnssec_type, is_in_zone, read_zone_file, sort_records};
+use crate::zone::{NodeKind, ZoneName};
+
+use super::nsec3hash::{nsec3_chain, nsec3_hash, nsec3_names, nsec3_owner, nsec3param_of};
+use super::nsec3hash::{Nsec3Digest, Nsec3Hash, Nsec3Link};
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct Nsec3Proof {
+    /// The hashing algorithm [default: from the NSEC3PARAM record or SHA-1]
+    #[arg(
+        short = 'a',
+        long = "algorithm",
+        value_name = "NUMBER_OR_MNEMONIC",
+        value_parser = ValueParser::new(Nsec3Hash::parse_nsec_alg)
+    )]
+    algorithm: Option<Nsec3HashAlg>,
+
+    /// The number of hash iterations [default: from the NSEC3PARAM record or 0]
+    #[arg(
+        short = 'i',
+        visible_short_alias = 't',
+        long = "iterations",
+        value_name = "NUMBER"
+    )]
+    iterations: Option<u16>,
+
+    /// The salt in hex representation [default: from the NSEC3PARAM record or none]
+    #[arg(short = 's', long = "salt", value_name = "HEX_STRING")]
+    salt: Option<Nsec3Salt<Vec<u8>>>,
+
+    /// Leave insecure delegations out of the NSEC3 chain [default: from the NSEC3 records]
+    #[arg(short = 'p', long = "opt-out")]
+    opt_out: bool,
+
+    /// The zone file, signed or not
+    #[arg(value_name = "ZONEFILE")]
+    zonefile: PathBuf,
+
+    /// The query name to prove the response for
+    #[arg(
+        value_name = "DOMAIN_NAME",
+        value_parser = ValueParser::new(Nsec3Hash::parse_name)
+    )]
+    qname: Name<Vec<u8>>,
+}
+
+/// Why an NSEC3 record must be in a response
+struct Role {
+    /// The index of the NSEC3 record in the chain
+    link: usize,
+
+    /// What the record does, e.g. "matches the closest encloser"
+    reason: String,
+}
+
+impl Nsec3Proof {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        self.execute_inner(&env, format).with_context(|| {
+            format!(
+                "proving {} in {}",
+                self.qname.fmt_with_dot(),
+                self.zonefile.display()
+            )
+        })
+    }
+
+    fn execute_inner(&self, env: &impl Env, format: OutputFormat) -> Result<(), Error> {
+        let mut records = read_zone_file(env, &self.zonefile, None)?;
+        let apex = records
+            .iter()
+            .find(|r| r.rtype() == Rtype::SOA)
+            .map(|r| r.owner().clone())
+            .ok_or("zone has no SOA record")?;
+        let qname: ZoneName = self.qname.to_bytes();
+        if !is_in_zone(&qname, &apex) {
+            return Err(format!(
+                "{} is not in the zone {}",
+                qname.fmt_with_dot(),
+                apex.fmt_with_dot()
+            )
+            .into());
+        }
+
+        // A signed zone brings its own parameters and NSEC3 records, which
+        // are checked for the records that the proof needs.
+        let params = records
+            .iter()
+            .filter(|r| r.owner().name_eq(&apex))
+            .find_map(nsec3param_of);
+        let mut signed = BTreeSet::new();
+        let mut opt_out = self.opt_out;
+        for record in &records {
+            if let ZoneRecordData::Nsec3(nsec3) = record.data() {
+                signed.insert(record.owner().clone());
+                opt_out |= nsec3.opt_out();
+            }
+        }
+
+        let algorithm = self
+            .algorithm
+            .or(params.as_ref().map(|p| p.hash_algorithm()))
+            .unwrap_or(Nsec3HashAlg::SHA1);
+        let iterations = self
+            .iterations
+            .or(params.as_ref().map(|p| p.iterations()))
+            .unwrap_or(0);
+        let salt = self
+            .salt
+            .clone()
+            .or(params.map(|p| p.salt().clone()))
+            .unwrap_or_else(Nsec3Salt::empty);
+
+        records.retain(|r| !is_dnssec_type(r.rtype()) && is_in_zone(r.owner(), &apex));
+        sort_records(&mut records);
+        let nodes = group_nodes(records, &apex);
+
+        let names = nsec3_names(&nodes, opt_out);
+        let digest = Nsec3Digest::new(algorithm)?;
+        let chain = nsec3_chain(names, &apex, digest, iterations, &salt)?;
+        let hash = |name: &ZoneName| nsec3_hash(name, algorithm, iterations, &salt);
+        let in_chain = |name: &ZoneName| chain.iter().any(|(_, n, _)| n == name);
+
+        // The names that the proof is about, with their hashes.
+        let mut names = vec![("query name", qname.clone(), hash(&qname)?)];
+
+        // Names at or below a delegation are answered with a referral.
+        let delegation = nodes
+            .iter()
+            .find(|n| n.kind == NodeKind::Delegation && qname.ends_with(&n.owner));
+        let target = delegation.map_or(&qname, |n| &n.owner);
+
+        let result;
+        let mut roles = Vec::new();
+        if in_chain(target) {
+            let link = find_link(&chain, &hash(target)?).expect("the name is in the chain");
+            match delegation {
+                Some(node) => {
+                    let kind = if node.has_rtype(Rtype::DS) {
+                        "secure"
+                    } else {
+                        "insecure"
+                    };
+                    result = format!(
+                        "referral to the {kind} delegation {}",
+                        target.fmt_with_dot()
+                    );
+                    roles.push(Role::matching(link, "the delegation"));
+                }
+                None => {
+                    result = "NODATA, for the types that do not exist".into();
+                    roles.push(Role::matching(link, "the query name"));
+                }
+            }
+        } else {
+            // The closest provable encloser is the closest ancestor in the
+            // chain. The apex always is.
+            let mut next_closer = target.clone();
+            let mut encloser = target.parent().expect("the name is below the apex");
+            while !in_chain(&encloser) {
+                next_closer = encloser;
+                encloser = next_closer.parent().expect("the apex is in the chain");
+            }
+            let wildcard: ZoneName = RelativeName::wildcard_bytes()
+                .chain(&encloser)
+                .expect("the wildcard is not longer than the query name")
+                .to_bytes();
+
+            let (encloser_hash, next_closer_hash) = (hash(&encloser)?, hash(&next_closer)?);
+            let wildcard_hash = hash(&wildcard)?;
+            let encloser_link =
+                find_link(&chain, &encloser_hash).expect("the encloser is in the chain");
+            let next_closer_link = find_link(&chain, &next_closer_hash)
+                .expect_err("the next closer name is not in the chain");
+
+            if let Some(node) = delegation {
+                result = format!(
+                    "referral to the opted-out delegation {}",
+                    node.owner.fmt_with_dot()
+                );
+                roles.push(Role::matching(
+                    encloser_link,
+                    "the closest provable encloser",
+                ));
+                roles.push(Role::covering(
+                    &chain,
+                    next_closer_link,
+                    "the next closer name",
+                ));
+            } else if let Ok(wildcard_link) = find_link(&chain, &wildcard_hash) {
+                result = format!(
+                    "answer or NODATA from the wildcard {}",
+                    wildcard.fmt_with_dot()
+                );
+                // RFC 5155 section 7.2.6: a wildcard answer only needs the
+                // next closer name to be covered, a wildcard NODATA response
+                // also needs the matches of section 7.2.5.
+                roles.push(Role::covering(
+                    &chain,
+                    next_closer_link,
+                    "the next closer name",
+                ));
+                roles.push(Role::matching(
+                    encloser_link,
+                    "the closest encloser, for a NODATA response",
+                ));
+                roles.push(Role::matching(
+                    wildcard_link,
+                    "the wildcard, for a NODATA response",
+                ));
+            } else {
+                result = "NXDOMAIN".into();
+                roles.push(Role::matching(encloser_link, "the closest encloser"));
+                roles.push(Role::covering(
+                    &chain,
+                    next_closer_link,
+                    "the next closer name",
+                ));
+                let wildcard_link = find_link(&chain, &wildcard_hash)
+                    .expect_err("the wildcard is not in the chain");
+                roles.push(Role::covering(&chain, wildcard_link, "the wildcard"));
+            }
+
+            names.push(("closest encloser", encloser, encloser_hash));
+            names.push(("next closer name", next_closer, next_closer_hash));
+            if delegation.is_none() {
+                names.push(("wildcard", wildcard, wildcard_hash));
+            }
+        }
+
+        // The NSEC3 records, with the reasons they are needed and whether
+        // they are missing from a signed zone.
+        let mut links: Vec<usize> = roles.iter().map(|r| r.link).collect();
+        links.sort();
+        links.dedup();
+        let mut records = Vec::new();
+        for link in links {
+            let owner = nsec3_owner(&chain[link].0, &apex)?;
+            let reasons: Vec<_> = roles
+                .iter()
+                .filter(|r| r.link == link)
+                .map(|r| r.reason.as_str())
+                .collect();
+            let missing = !signed.is_empty() && !signed.contains(&owner);
+            records.push((owner, reasons, missing));
+        }
+
+        let mut out = env.stdout();
+        match format {
+            OutputFormat::Text => {
+                for (i, (what, name, hash)) in names.iter().enumerate() {
+                    writeln!(out, "{what}: {} {}", name.fmt_with_dot(), fmt_hash(hash));
+                    if i == 0 {
+                        writeln!(out, "result: {result}");
+                    }
+                }
+                writeln!(out, "NSEC3 records:");
+                for (owner, reasons, missing) in records {
+                    writeln!(
+                        out,
+                        "{} {}{}",
+                        owner.fmt_with_dot(),
+                        reasons.join(", "),
+                        if missing {
+                            " (missing from the zone)"
+                        } else {
+                            ""
+                        }
+                    );
+                }
+            }
+            OutputFormat::Json => {
+                let mut json = serde_json::Map::new();
+                for (what, name, hash) in names {
+                    json.insert(
+                        what.replace(' ', "_"),
+                        serde_json::json!({
+                            "name": name.fmt_with_dot().to_string(),
+                            "hash": fmt_hash(&hash),
+                        }),
+                    );
+                }
+                json.insert("result".into(), result.into());
+                let records: Vec<_> = records
+                    .into_iter()
+                    .map(|(owner, reasons, missing)| {
+                        serde_json::json!({
+                            "owner": owner.fmt_with_dot().to_string(),
+                            "reasons": reasons,
+                            "missing": missing,
+                        })
+                    })
+                    .collect();
+                json.insert("records".into(), records.into());
+                writeln!(out, "{}", serde_json::Value::Object(json));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Role {
+    fn matching(link: usize, what: &str) -> Self {
+        Self {
+            link,
+            reason: format!("matches {what}"),
+        }
+    }
+
+    fn covering(chain: &[Nsec3Link], link: usize, what: &str) -> Self {
+        let next = &chain[(link + 1) % chain.len()].1;
+        Self {
+            link,
+            reason: format!(
+                "covers {what}, from {} to {}",
+                chain[link].1.fmt_with_dot(),
+                next.fmt_with_dot()
+            ),
+        }
+    }
+}
+
+/// Find the link of the chain that matches or covers a hash
+///
+/// Returns `Ok` with the index of the matching link or `Err` with the index
+/// of the covering link. The last link covers the hashes after it and
+/// before the first link.
+fn find_link(chain: &[Nsec3Link], hash: &OwnerHash<Vec<u8>>) -> Result<usize, usize> {
+    match chain.binary_search_by(|(h, _, _)| h.as_slice().cmp(hash.as_slice())) {
+        Ok(i) => Ok(i),
+        Err(i) => Err((i + chain.len() - 1) % chain.len()),
+    }
+}
+
+/// Format a hash the way it appears in owner names
+fn fmt_hash(hash: &OwnerHash<impl AsRef<[u8]>>) -> String {
+    hash.to_string().to_lowercase()
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use domain::base::Name;
+
+    use crate::env::fake::FakeCmd;
+    use crate::keys::{KeyAlgorithm, KeyPair, ZSK_FLAGS};
+
+    const ZONE: &str = "\
+$ORIGIN example.test.
+$TTL 3600
+@       IN SOA ns1 hostmaster 2024010101 7200 3600 1209600 300
+@       IN NS  ns1
+ns1     IN A   192.0.2.1
+www     IN A   192.0.2.2
+*.wild  IN TXT \"wildcard\"
+sub     IN NS  ns.sub
+ns.sub  IN A   192.0.2.3
+a.b.c   IN A   192.0.2.4
+";
+
+    fn prove(qname: &str) -> String {
+        let cmd = FakeCmd::new(["dnst", "nsec3-proof", "zone", qname]).file("zone", ZONE);
+        let res = cmd.run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        res.stdout
+    }
+
+    #[test]
+    fn nxdomain() {
+        assert_eq!(
+            prove("nope.example.test"),
+            "\
+query name: nope.example.test. sj17kdbvolalfri8d481s05jtgear5r1
+result: NXDOMAIN
+closest encloser: example.test. jbas736chung3bb701jkjdhqkqlhvug7
+next closer name: nope.example.test. sj17kdbvolalfri8d481s05jtgear5r1
+wildcard: *.example.test. 6can7n9ison37dm8pil7ts42f3kfoo3n
+NSEC3 records:
+jbas736chung3bb701jkjdhqkqlhvug7.example.test. matches the closest encloser
+qbo34rofet3c8cnf259de5odtlg8oj5o.example.test. covers the next closer name, from sub.example.test. to a.b.c.example.test.
+v3n2r9chqlp7tdetolmmakfms81udcje.example.test. covers the wildcard, from www.example.test. to b.c.example.test.
+"
+        );
+    }
+
+    #[test]
+    fn other_results() {
+        let out = prove("www.example.test");
+        assert!(out.contains("result: NODATA"));
+        assert!(
+            out.contains("v3n2r9chqlp7tdetolmmakfms81udcje.example.test. matches the query name")
+        );
+
+        // The empty non-terminal c.example.test exists too.
+        let out = prove("c.example.test");
+        assert!(out.contains("result: NODATA"));
+
+        let out = prove("x.y.wild.example.test");
+        assert!(out.contains("result: answer or NODATA from the wildcard *.wild.example.test."));
+        assert!(out.contains("closest encloser: wild.example.test. "));
+        assert!(out.contains("next closer name: y.wild.example.test. "));
+        assert!(
+            out.contains(" covers the next closer name, from ns1.example.test. to example.test.")
+        );
+
+        let out = prove("a.sub.example.test");
+        assert!(out.contains("result: referral to the insecure delegation sub.example.test."));
+        assert!(
+            out.contains("qbo34rofet3c8cnf259de5odtlg8oj5o.example.test. matches the delegation")
+        );
+
+        let cmd = FakeCmd::new([
+            "dnst",
+            "nsec3-proof",
+            "--opt-out",
+            "zone",
+            "a.sub.example.test",
+        ]);
+        let res = cmd.file("zone", ZONE).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res
+            .stdout
+            .contains("result: referral to the opted-out delegation sub.example.test."));
+        assert!(res.stdout.contains("matches the closest provable encloser"));
+        assert!(!res.stdout.contains("wildcard"));
+
+        let cmd = FakeCmd::new(["dnst", "nsec3-proof", "zone", "example.org"]);
+        let res = cmd.file("zone", ZONE).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res
+            .stderr
+            .contains("example.org. is not in the zone example.test."));
+    }
+
+    #[test]
+    fn signed() {
+        let cmd = FakeCmd::new(["dnst", "signzone", "-n", "-s", "cafe", "-t", "2", "-f", "-"]);
+        let owner = Name::from_str("example.test").unwrap();
+        let key = KeyPair::generate(owner, KeyAlgorithm::Ed25519, ZSK_FLAGS, 0).unwrap();
+        let base = key.base_name();
+        let res = cmd
+            .file("zone", ZONE)
+            .file(format!("{base}.key"), key.display_public().to_string())
+            .file(format!("{base}.private"), key.display_private().to_string())
+            .args(["zone", &base])
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        let signed = res.stdout;
+
+        // The parameters are taken from the zone.
+        let cmd = FakeCmd::new(["dnst", "nsec3-proof", "signed", "nope.example.test"]);
+        let res = cmd.file("signed", &signed).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res
+            .stdout
+            .contains("closest encloser: example.test. 9pvv643sdep3p3euj32irslpd0gh2c00\n"));
+        assert!(!res.stdout.contains("missing"));
+
+        let broken: String = signed
+            .lines()
+            .filter(|l| !l.starts_with("9pvv643sdep3p3euj32irslpd0gh2c00"))
+            .map(|l| format!("{l}\n"))
+            .collect();
+        let res = cmd.file("signed", broken).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res.stdout.contains(
+            "9pvv643sdep3p3euj32irslpd0gh2c00.example.test. matches the closest encloser, \
+             covers the wildcard, from example.test. to a.b.c.example.test. (missing from the zone)"
+        ));
+    }
+}
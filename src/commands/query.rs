@@ -0,0 +1,1059 @@
+use std::ffi::OsString;
+use std::fmt::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use domain::base::iana::{Class, Rtype};
+use domain::base::{Message, MessageBuilder, Name};
+use domain::net::client::request::{ComposeRequest, RequestMessage, SendRequest};
+use domain::net::client::{dgram, dgram_stream, multi_stream};
+use domain::resolv::stub::conf::ResolvConf;
+use lexopt::Arg;
+
+use crate::args::OutputFormat;
+use crate::chain::{self, Validation, ROOT_ANCHORS};
+use crate::env::{Env, Stream};
+use crate::error::Error;
+use crate::message::{message_json, print_edns, print_message};
+use crate::zone::{read_zone, read_zone_file, ZoneRecord};
+
+use super::{parse_os, resolve_server, LdnsCommand};
+
+/// The EDNS UDP payload size used if only the DO bit is asked for
+///
+/// This is the size recommended by DNS Flag Day 2020.
+const DEFAULT_UDP_PAYLOAD_SIZE: u16 = 1232;
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct Query {
+    /// The domain name to look up, or the IP address with --reverse
+    #[arg(value_name = "NAME")]
+    name: String,
+
+    /// The record type to query for [default: A, or PTR with --reverse]
+    #[arg(value_name = "TYPE")]
+    rtype: Option<Rtype>,
+
+    /// The class to query for
+    #[arg(short = 'c', long = "class", value_name = "CLASS", default_value_t = Class::IN)]
+    class: Class,
+
+    /// The server to send the query to [default: the servers in /etc/resolv.conf]
+    #[arg(short = 's', long = "server", value_name = "SERVER")]
+    server: Option<String>,
+
+    /// The port to send the query to
+    #[arg(short = 'p', long = "port", value_name = "PORT", default_value_t = 53)]
+    port: u16,
+
+    /// Send the query over TCP instead of UDP
+    #[arg(short = 't', long = "tcp")]
+    tcp: bool,
+
+    /// Use EDNS with the given UDP payload size
+    #[arg(short = 'b', long = "buffer-size", value_name = "SIZE")]
+    buffer_size: Option<u16>,
+
+    /// Set the DO bit to request DNSSEC records, implies EDNS
+    #[arg(short = 'D', long = "do")]
+    dnssec_ok: bool,
+
+    /// Set the CD (checking disabled) bit
+    #[arg(long = "cd")]
+    checking_disabled: bool,
+
+    /// Clear the RD (recursion desired) bit
+    #[arg(long = "no-rd")]
+    no_recursion: bool,
+
+    /// Look up the PTR record of an IP address
+    #[arg(short = 'x', long = "reverse")]
+    reverse: bool,
+
+    /// The number of seconds to wait for a response before retrying
+    #[arg(long = "timeout", value_name = "SECONDS", default_value_t = 5)]
+    timeout: u64,
+
+    /// The maximum number of retries over UDP
+    #[arg(long = "retries", value_name = "NUMBER", default_value_t = 2)]
+    retries: u8,
+
+    /// Validate the response with DNSSEC, implies --do and --cd
+    #[arg(long = "validate")]
+    validate: bool,
+
+    /// A file with DS or DNSKEY records to use as trust anchors [default:
+    /// the trust anchors of the root zone]
+    #[arg(
+        short = 'k',
+        long = "trust-anchor",
+        value_name = "FILE",
+        requires = "validate"
+    )]
+    trust_anchors: Vec<PathBuf>,
+}
+
+const LDNS_HELP: &str = "\
+Usage: drill name [@server] [type] [class]
+\t<name>  can be a domain name or an IP address (-x lookups)
+\t<type>  defaults to A
+\t<class> defaults to IN
+
+\targuments may be placed in random order
+
+Options:
+\t-D\t\tenable DNSSEC (DO bit)
+\t-t\t\tsend the query with tcp (connected)
+\t-u\t\tsend the query with udp (the default)
+\t-x\t\tdo a reverse lookup
+\t-S\t\tchase the signatures to a trusted key
+\t-k <file>\tread trusted keys from <file>
+\t\t\t(defaults to the trust anchors of the root zone)
+\t-b <bufsize>\tuse <bufsize> as the buffer size
+\t-o <mnemonic>\tset flags to: [RD|rd][CD|cd]
+\t\t\tlowercase: unset bit, uppercase: set bit
+\t-p <port>\tuse <port> as remote port number\
+";
+
+impl LdnsCommand for Query {
+    const HELP: &'static str = LDNS_HELP;
+
+    fn parse_ldns<I: IntoIterator<Item = OsString>>(args: I) -> Result<Self, Error> {
+        let mut name = None;
+        let mut rtype = None;
+        let mut class = None;
+        let mut server = None;
+        let mut port = 53;
+        let mut tcp = false;
+        let mut buffer_size = None;
+        let mut dnssec_ok = false;
+        let mut checking_disabled = false;
+        let mut no_recursion = false;
+        let mut reverse = false;
+        let mut validate = false;
+        let mut trust_anchors = Vec::new();
+
+        let mut parser = lexopt::Parser::from_args(args);
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Arg::Short('D') => dnssec_ok = true,
+                Arg::Short('t') => tcp = true,
+                Arg::Short('u') => tcp = false,
+                Arg::Short('x') => reverse = true,
+                Arg::Short('S') => validate = true,
+                Arg::Short('k') => trust_anchors.push(parser.value()?.into()),
+                Arg::Short('b') => {
+                    let val = parser.value()?;
+                    buffer_size = Some(parse_os("buffer size (-b)", &val)?);
+                }
+                Arg::Short('o') => {
+                    let val = parser.value()?;
+                    let Some(val) = val.to_str() else {
+                        return Err(format!("Invalid value for -o: {val:?}").into());
+                    };
+                    for flag in val.split('|') {
+                        match flag {
+                            "RD" => no_recursion = false,
+                            "rd" => no_recursion = true,
+                            "CD" => checking_disabled = true,
+                            "cd" => checking_disabled = false,
+                            _ => return Err(format!("Unsupported header flag: {flag}").into()),
+                        }
+                    }
+                }
+                Arg::Short('p') => {
+                    let val = parser.value()?;
+                    port = parse_os("port (-p)", &val)?;
+                }
+                Arg::Value(val) => {
+                    let Some(val) = val.to_str() else {
+                        return Err(
+                            format!("Invalid argument: {val:?} is not valid unicode").into()
+                        );
+                    };
+
+                    // Like drill, take anything that looks like a type or
+                    // class as one.
+                    if let Some(val) = val.strip_prefix('@') {
+                        server = Some(val.to_string());
+                    } else if let (None, Ok(val)) = (rtype, Rtype::from_str(val)) {
+                        rtype = Some(val);
+                    } else if let (None, Ok(val)) = (class, Class::from_str(val)) {
+                        class = Some(val);
+                    } else if name.is_none() {
+                        name = Some(val.to_string());
+                    } else {
+                        return Err(format!("Unexpected argument: {val}").into());
+                    }
+                }
+                Arg::Short(x) => return Err(format!("Invalid short option: -{x}").into()),
+                Arg::Long(x) => {
+                    return Err(format!("Long options are not supported, but `--{x}` given").into())
+                }
+            }
+        }
+
+        let Some(name) = name else {
+            return Err("No name given".into());
+        };
+
+        Ok(Self {
+            name,
+            rtype,
+            class: class.unwrap_or(Class::IN),
+            server,
+            port,
+            tcp,
+            buffer_size,
+            dnssec_ok,
+            checking_disabled,
+            no_recursion,
+            reverse,
+            timeout: 5,
+            retries: 2,
+            validate,
+            trust_anchors,
+        })
+    }
+}
+
+impl Query {
+    pub fn execute(mut self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        // The validator needs the signatures, also if the server considers
+        // them bogus.
+        if self.validate {
+            self.dnssec_ok = true;
+            self.checking_disabled = true;
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| format!("could not start the runtime: {e}"))?;
+        runtime.block_on(self.run(env, format))
+    }
+
+    async fn run(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let msg = self.create_message()?;
+        let servers = self.servers(&env)?;
+        let anchors = match self.validate {
+            true => Some(self.trust_anchors(&env)?),
+            false => None,
+        };
+
+        // Like the stub resolver, try the servers in order until one
+        // responds.
+        let mut errors = Vec::new();
+        for addr in servers {
+            let start = Instant::now();
+            match self.send(&env, addr, msg.clone()).await {
+                Ok(response) => {
+                    let time = start.elapsed();
+                    let validation = match &anchors {
+                        Some(anchors) => {
+                            let upstream = self.upstream(&env, addr);
+                            Some(chain::validate(anchors, upstream, &response).await?)
+                        }
+                        None => None,
+                    };
+                    self.print(&env, format, addr, &response, time, validation.as_ref());
+                    return Ok(());
+                }
+                Err(e) => errors.push(format!("error querying {addr}: {e}")),
+            }
+        }
+
+        if errors.is_empty() {
+            return Err("no servers to query".into());
+        }
+        Err(errors.join("\n").into())
+    }
+
+    /// Create the query message
+    fn create_message(&self) -> Result<Message<Vec<u8>>, Error> {
+        let qname = if self.reverse {
+            let addr = self
+                .name
+                .parse()
+                .map_err(|e| format!("invalid IP address '{}': {e}", self.name))?;
+            reverse_name(addr)
+        } else {
+            self.name.clone()
+        };
+        let qname = Name::<Vec<u8>>::from_str(&qname)
+            .map_err(|e| format!("invalid domain name '{qname}': {e}"))?;
+        let qtype = match self.rtype {
+            Some(rtype) => rtype,
+            None if self.reverse => Rtype::PTR,
+            None => Rtype::A,
+        };
+
+        let mut msg = MessageBuilder::new_vec();
+        let header = msg.header_mut();
+        header.set_rd(!self.no_recursion);
+        header.set_cd(self.checking_disabled);
+
+        let mut msg = msg.question();
+        msg.push((&qname, qtype, self.class))
+            .map_err(|e| format!("could not create query: {e}"))?;
+        Ok(msg.into_message())
+    }
+
+    /// Get the socket addresses of the servers to query
+    fn servers(&self, env: &impl Env) -> Result<Vec<SocketAddr>, Error> {
+        if let Some(server) = &self.server {
+            return resolve_server(env, server, self.port);
+        }
+
+        // Like the stub resolver, fall back to 127.0.0.1 if there is no
+        // configuration.
+        let mut conf = ResolvConf::new();
+        if let Ok(contents) = env.read_to_string(Path::new("/etc/resolv.conf")) {
+            conf.parse(&mut contents.as_bytes())
+                .map_err(|e| format!("could not read /etc/resolv.conf: {e}"))?;
+        }
+        conf.finalize();
+
+        let mut servers = Vec::new();
+        for server in &conf.servers {
+            let addr = SocketAddr::new(server.addr.ip(), self.port);
+            if !servers.contains(&addr) {
+                servers.push(addr);
+            }
+        }
+        Ok(servers)
+    }
+
+    /// Read the trust anchors for validation
+    fn trust_anchors(&self, env: &impl Env) -> Result<Vec<ZoneRecord>, Error> {
+        if self.trust_anchors.is_empty() {
+            return read_zone(
+                env,
+                &mut ROOT_ANCHORS.as_bytes(),
+                "root trust anchors",
+                None,
+            );
+        }
+
+        let mut anchors = Vec::new();
+        for path in &self.trust_anchors {
+            anchors.extend(read_zone_file(env, path, None)?);
+        }
+        Ok(anchors)
+    }
+
+    /// Create the connection for the queries of the validator to a server
+    ///
+    /// Unlike the query itself, these fall back to TCP if a response over
+    /// UDP is truncated.
+    fn upstream(
+        &self,
+        env: &impl Env,
+        addr: SocketAddr,
+    ) -> Box<dyn SendRequest<RequestMessage<Vec<u8>>> + Send + Sync> {
+        let timeout = Duration::from_secs(self.timeout);
+        let mut stream_config = multi_stream::Config::default();
+        stream_config.stream_mut().set_response_timeout(timeout);
+
+        if self.tcp {
+            let (conn, transport) = multi_stream::Connection::with_config(
+                env.make_stream_connection(addr),
+                stream_config,
+            );
+            tokio::spawn(transport.run());
+            return Box::new(conn);
+        }
+
+        let mut dgram_config = dgram::Config::new();
+        dgram_config.set_read_timeout(timeout);
+        dgram_config.set_max_retries(self.retries);
+        let (conn, transport) = dgram_stream::Connection::with_config(
+            env.make_connection(addr),
+            env.make_stream_connection(addr),
+            dgram_stream::Config::from_parts(dgram_config, stream_config),
+        );
+        tokio::spawn(transport.run());
+        Box::new(conn)
+    }
+
+    /// Send the query to a server and wait for the response
+    async fn send(
+        &self,
+        env: &impl Env,
+        addr: SocketAddr,
+        msg: Message<Vec<u8>>,
+    ) -> Result<Message<Bytes>, Error> {
+        let mut request =
+            RequestMessage::new(msg).map_err(|e| format!("could not create query: {e}"))?;
+
+        // Like drill, only use EDNS if it is asked for.
+        let udp_payload_size = self
+            .buffer_size
+            .or(self.dnssec_ok.then_some(DEFAULT_UDP_PAYLOAD_SIZE));
+        if let Some(size) = udp_payload_size {
+            request.set_udp_payload_size(size);
+            request.set_dnssec_ok(self.dnssec_ok);
+        }
+
+        let timeout = Duration::from_secs(self.timeout);
+        let response = if self.tcp {
+            let mut config = multi_stream::Config::default();
+            config.stream_mut().set_response_timeout(timeout);
+            let (conn, transport) =
+                multi_stream::Connection::with_config(env.make_stream_connection(addr), config);
+            tokio::spawn(transport.run());
+            conn.send_request(request).get_response().await
+        } else {
+            let mut config = dgram::Config::new();
+            config.set_read_timeout(timeout);
+            config.set_max_retries(self.retries);
+            config.set_udp_payload_size(None);
+            if let Some(size) = udp_payload_size {
+                config.set_recv_size(config.recv_size().max(size.into()));
+            }
+            let conn = dgram::Connection::with_config(env.make_connection(addr), config);
+            conn.send_request(request).get_response().await
+        };
+
+        response.map_err(|e| e.to_string().into())
+    }
+
+    /// Print the response in the requested format
+    fn print(
+        &self,
+        env: &impl Env,
+        format: OutputFormat,
+        addr: SocketAddr,
+        response: &Message<Bytes>,
+        time: Duration,
+        validation: Option<&Validation>,
+    ) {
+        let mut out = env.stdout();
+        let transport = if self.tcp { "TCP" } else { "UDP" };
+        match format {
+            OutputFormat::Text => {
+                print_message(&mut out, response);
+                writeln!(out, ";; Query time: {} msec", time.as_millis());
+                print_edns(&mut out, response);
+                writeln!(out, ";; SERVER: {addr} ({transport})");
+                writeln!(out, ";; MSG SIZE  rcvd: {}", response.as_slice().len());
+                if let Some(validation) = validation {
+                    print_validation(&mut out, validation);
+                }
+            }
+            OutputFormat::Json => {
+                let mut json = serde_json::json!({
+                    "server": addr.to_string(),
+                    "transport": transport.to_lowercase(),
+                    "time_ms": time.as_millis() as u64,
+                    "size": response.as_slice().len(),
+                    "reply": message_json(response),
+                });
+                if let Some(validation) = validation {
+                    json["validation"] = validation_json(validation);
+                }
+                writeln!(out, "{json}");
+            }
+        }
+    }
+}
+
+/// Print the chain of trust and the outcome of the validation
+fn print_validation(out: &mut Stream<impl std::fmt::Write>, validation: &Validation) {
+    writeln!(out, "\n;; CHAIN OF TRUST:");
+    for link in &validation.links {
+        let from = match (link.anchor, link.key_tag) {
+            (true, Some(key_tag)) => format!("trust anchor {key_tag}"),
+            (false, Some(key_tag)) => format!("DS {key_tag}"),
+            (_, None) => "no DS".into(),
+        };
+        let status = match (&link.problem, link.key_tag) {
+            (Some(problem), _) => format!("bogus: {problem}"),
+            (None, Some(_)) => "valid".into(),
+            (None, None) => "insecure delegation".into(),
+        };
+        writeln!(out, ";; {}\t{from}\t{status}", link.zone.fmt_with_dot());
+    }
+
+    let state = chain::state_name(validation.state);
+    match &validation.reason {
+        Some(reason) => writeln!(out, ";; VALIDATION: {state} ({reason})"),
+        None => writeln!(out, ";; VALIDATION: {state}"),
+    }
+}
+
+/// The chain of trust and the outcome of the validation as a JSON object
+fn validation_json(validation: &Validation) -> serde_json::Value {
+    let chain: Vec<_> = validation
+        .links
+        .iter()
+        .map(|link| {
+            serde_json::json!({
+                "zone": link.zone.fmt_with_dot().to_string(),
+                "anchor": link.anchor,
+                "key_tag": link.key_tag,
+                "problem": link.problem,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "state": chain::state_name(validation.state),
+        "reason": validation.reason,
+        "chain": chain,
+    })
+}
+
+/// The name for the reverse lookup of an IP address
+fn reverse_name(addr: IpAddr) -> String {
+    match addr {
+        IpAddr::V4(addr) => {
+            let [a, b, c, d] = addr.octets();
+            format!("{d}.{c}.{b}.{a}.in-addr.arpa.")
+        }
+        IpAddr::V6(addr) => {
+            let mut name = String::new();
+            for byte in addr.octets().iter().rev() {
+                write!(name, "{:x}.{:x}.", byte & 0xf, byte >> 4).unwrap();
+            }
+            name.push_str("ip6.arpa.");
+            name
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use domain::base::iana::{Class, Rcode, Rtype};
+    use domain::base::rdata::UnknownRecordData;
+    use domain::base::{Message, MessageBuilder, Name, ToName, Ttl};
+    use domain::rdata::{Ptr, ZoneRecordData, A};
+
+    use crate::env::fake::FakeCmd;
+    use crate::keys::{KeyAlgorithm, KeyPair, KSK_FLAGS};
+    use crate::zone::{parse_record, ZoneName, ZoneRecord};
+
+    /// Answer queries for A records of example.test. with 192.0.2.1
+    fn respond(msg: &Message<[u8]>) -> Option<Message<Vec<u8>>> {
+        let question = msg.sole_question().unwrap();
+        let mut builder = MessageBuilder::new_vec()
+            .start_answer(msg, Rcode::NOERROR)
+            .unwrap();
+        builder.header_mut().set_ra(true);
+        if question.qtype() == Rtype::A {
+            builder
+                .push((
+                    question.qname(),
+                    Ttl::from_secs(3600),
+                    A::from_str("192.0.2.1").unwrap(),
+                ))
+                .unwrap();
+        }
+        let mut builder = builder.additional();
+        if let Some(opt) = msg.opt() {
+            builder
+                .opt(|new| {
+                    new.set_udp_payload_size(1232);
+                    new.set_dnssec_ok(opt.dnssec_ok());
+                    Ok(())
+                })
+                .unwrap();
+        }
+        Some(builder.into_message())
+    }
+
+    const TEST_ZONE: &str = "\
+$ORIGIN test.
+$TTL 3600
+@         IN SOA ns hostmaster 1 7200 3600 1209600 300
+@         IN NS  ns
+ns        IN A   192.0.2.53
+example   IN NS  ns.example
+insecure  IN NS  ns.insecure
+";
+
+    const EXAMPLE_ZONE: &str = "\
+$ORIGIN example.test.
+$TTL 3600
+@       IN SOA ns hostmaster 1 7200 3600 1209600 300
+@       IN NS  ns
+www     IN A   192.0.2.1
+";
+
+    const INSECURE_ZONE: &str = "\
+insecure.test. 3600 IN SOA ns.insecure.test. hostmaster.insecure.test. 1 7200 3600 1209600 300
+www.insecure.test. 3600 IN A 192.0.2.2
+";
+
+    /// The zones of a small DNSSEC signed hierarchy, with their records
+    ///
+    /// The zones test. and example.test. are signed, insecure.test. is not.
+    /// Returns the zones and the DS record for the key of test. to use as
+    /// trust anchor.
+    fn signed_zones() -> (Vec<(ZoneName, Vec<ZoneRecord>)>, String) {
+        let cmd = FakeCmd::new(["dnst"]);
+        let mut keys = Vec::new();
+        for name in ["test", "example.test"] {
+            let owner = Name::from_str(name).unwrap();
+            // The validator does not support Ed25519.
+            let key =
+                KeyPair::generate(owner, KeyAlgorithm::EcdsaP256Sha256, KSK_FLAGS, 0).unwrap();
+            let base = key.base_name();
+            cmd.file(format!("{base}.key"), key.display_public().to_string());
+            cmd.file(format!("{base}.private"), key.display_private().to_string());
+            keys.push(base);
+        }
+
+        let ds = |base: &str| {
+            let res = cmd.args(["key2ds", "-n", &format!("{base}.key")]).run();
+            assert_eq!(res.exit_code, 0, "{}", res.stderr);
+            res.stdout
+        };
+        let sign = |zone: &str, base: &str| {
+            cmd.file("zone", zone);
+            let res = cmd.args(["signzone", "-f", "-", "zone", base]).run();
+            assert_eq!(res.exit_code, 0, "{}", res.stderr);
+            parse_records(&res.stdout)
+        };
+
+        let example = sign(EXAMPLE_ZONE, &keys[1]);
+        let test = sign(&format!("{TEST_ZONE}{}", ds(&keys[1])), &keys[0]);
+        let zones = vec![
+            (ZoneName::from_str("test.").unwrap(), test),
+            (ZoneName::from_str("example.test.").unwrap(), example),
+            (
+                ZoneName::from_str("insecure.test.").unwrap(),
+                parse_records(INSECURE_ZONE),
+            ),
+        ];
+        (zones, ds(&keys[0]))
+    }
+
+    fn parse_records(text: &str) -> Vec<ZoneRecord> {
+        text.lines()
+            .filter(|line| !line.is_empty() && !line.starts_with(';'))
+            .map(|line| parse_record(line, &ZoneName::root_bytes()).unwrap())
+            .collect()
+    }
+
+    /// Answer queries from the zones like a resolver with the CD bit set
+    ///
+    /// The answer comes from the closest enclosing zone, or for DS records
+    /// from the zone above that. Without records, it is a NODATA response
+    /// with the NSEC record for the name. Queries outside the zones are
+    /// refused.
+    fn resolve(zones: &[(ZoneName, Vec<ZoneRecord>)], msg: &Message<[u8]>) -> Message<Vec<u8>> {
+        let question = msg.sole_question().unwrap();
+        let qname: ZoneName = question.qname().to_name();
+        let qtype = question.qtype();
+        let zone = zones
+            .iter()
+            .filter(|(zone, _)| qname.ends_with(zone))
+            .filter(|(zone, _)| qtype != Rtype::DS || *zone != qname)
+            .max_by_key(|(zone, _)| zone.label_count());
+        let Some((_, records)) = zone else {
+            return MessageBuilder::new_vec()
+                .start_answer(msg, Rcode::REFUSED)
+                .unwrap()
+                .into_message();
+        };
+
+        let rrset = |owner: ZoneName, rtype: Rtype| {
+            records.iter().filter(move |r| {
+                *r.owner() == owner
+                    && match r.data() {
+                        ZoneRecordData::Rrsig(rrsig) => rrsig.type_covered() == rtype,
+                        _ => r.rtype() == rtype,
+                    }
+            })
+        };
+
+        let mut builder = MessageBuilder::new_vec()
+            .start_answer(msg, Rcode::NOERROR)
+            .unwrap();
+        builder.header_mut().set_ra(true);
+        let answer: Vec<_> = rrset(qname.clone(), qtype).collect();
+        for record in &answer {
+            builder.push(*record).unwrap();
+        }
+
+        let mut builder = builder.authority();
+        if answer.is_empty() {
+            let soa = records.iter().find(|r| r.rtype() == Rtype::SOA).unwrap();
+            for record in rrset(soa.owner().clone(), Rtype::SOA).chain(rrset(qname, Rtype::NSEC)) {
+                builder.push(record).unwrap();
+            }
+        }
+
+        let mut builder = builder.additional();
+        builder
+            .opt(|opt| {
+                opt.set_udp_payload_size(1232);
+                opt.set_dnssec_ok(true);
+                Ok(())
+            })
+            .unwrap();
+        builder.into_message()
+    }
+
+    #[test]
+    fn dnst_parse() {
+        let cmd = FakeCmd::new(["dnst", "query"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["example.test"]).parse().is_ok());
+        assert!(cmd.args(["example.test", "AAAA"]).parse().is_ok());
+        assert!(cmd.args(["example.test", "NOTATYPE"]).parse().is_err());
+        assert!(cmd
+            .args(["-s", "192.0.2.1", "-p", "5353", "-t", "-b", "4096", "-D"])
+            .args(["--cd", "--no-rd", "-c", "CH", "example.test", "TXT"])
+            .parse()
+            .is_ok());
+        assert!(cmd.args(["-x", "192.0.2.1"]).parse().is_ok());
+        assert!(cmd.args(["--validate", "example.test"]).parse().is_ok());
+        assert!(cmd
+            .args(["--validate", "-k", "root.key", "-k", "example.ds"])
+            .args(["example.test"])
+            .parse()
+            .is_ok());
+        assert!(cmd
+            .args(["-k", "root.key", "example.test"])
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn ldns_parse() {
+        let cmd = FakeCmd::new(["drill"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["example.test"]).parse().is_ok());
+        assert!(cmd
+            .args(["MX", "@192.0.2.1", "example.test", "CH"])
+            .parse()
+            .is_ok());
+        assert!(cmd
+            .args(["-D", "-t", "-b", "4096", "-o", "CD", "-o", "rd", "-p", "53"])
+            .args(["example.test"])
+            .parse()
+            .is_ok());
+        assert!(cmd.args(["-o", "QR", "example.test"]).parse().is_err());
+        assert!(cmd.args(["one.test", "two.test"]).parse().is_err());
+        assert!(cmd.args(["--tcp", "example.test"]).parse().is_err());
+        assert!(cmd
+            .args(["-S", "-k", "root.key", "example.test"])
+            .parse()
+            .is_ok());
+    }
+
+    #[test]
+    fn query() {
+        let res = FakeCmd::new(["dnst", "query", "-s", "192.0.2.53", "-p", "5353"])
+            .args(["example.test"])
+            .server(|addr, msg| {
+                assert_eq!(addr.to_string(), "192.0.2.53:5353");
+                assert!(msg.header().rd());
+                assert!(!msg.header().cd());
+                assert!(msg.opt().is_none());
+                let question = msg.sole_question().unwrap();
+                assert_eq!(question.qtype(), Rtype::A);
+                assert_eq!(question.qclass(), Class::IN);
+                respond(msg)
+            })
+            .run();
+
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res
+            .stdout
+            .starts_with(";; ->>HEADER<<- opcode: QUERY, rcode: NOERROR, id: "));
+        assert!(res
+            .stdout
+            .contains(";; flags: qr rd ra ; QUERY: 1, ANSWER: 1, AUTHORITY: 0, ADDITIONAL: 0 \n"));
+        assert!(res
+            .stdout
+            .contains(";; ANSWER SECTION:\nexample.test.\t3600\tIN\tA\t192.0.2.1\n"));
+        assert!(res.stdout.contains(";; SERVER: 192.0.2.53:5353 (UDP)\n"));
+        assert!(!res.stdout.contains(";; EDNS:"));
+    }
+
+    #[test]
+    fn flags() {
+        let res = FakeCmd::new(["dnst", "query", "-s", "192.0.2.53", "-D", "--cd"])
+            .args(["--no-rd", "-c", "CH", "example.test", "TXT"])
+            .server(|_, msg| {
+                assert!(!msg.header().rd());
+                assert!(msg.header().cd());
+                let opt = msg.opt().unwrap();
+                assert!(opt.dnssec_ok());
+                assert_eq!(opt.udp_payload_size(), 1232);
+                let question = msg.sole_question().unwrap();
+                assert_eq!(question.qtype(), Rtype::TXT);
+                assert_eq!(question.qclass(), Class::CH);
+                respond(msg)
+            })
+            .run();
+
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res
+            .stdout
+            .contains(";; EDNS: version 0; flags: do ; udp: 1232\n"));
+        // The OPT record is not printed as a record.
+        assert!(res
+            .stdout
+            .contains(";; ADDITIONAL SECTION:\n\n;; Query time: "));
+
+        let res = FakeCmd::new(["drill", "-b", "4096", "example.test", "@192.0.2.53"])
+            .server(|_, msg| {
+                let opt = msg.opt().unwrap();
+                assert!(!opt.dnssec_ok());
+                assert_eq!(opt.udp_payload_size(), 4096);
+                respond(msg)
+            })
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res
+            .stdout
+            .contains(";; EDNS: version 0; flags: ; udp: 1232\n"));
+    }
+
+    #[test]
+    fn reverse() {
+        let cmd = FakeCmd::new(["dnst", "query", "-s", "192.0.2.53", "-x"]).server(|_, msg| {
+            let question = msg.sole_question().unwrap();
+            assert_eq!(question.qtype(), Rtype::PTR);
+            let ptr = Ptr::new(Name::<Vec<u8>>::from_str("host.example.test").unwrap());
+            let mut builder = MessageBuilder::new_vec()
+                .start_answer(msg, Rcode::NOERROR)
+                .unwrap();
+            builder
+                .push((question.qname(), Ttl::from_secs(3600), ptr))
+                .unwrap();
+            Some(builder.into_message())
+        });
+
+        let res = cmd.args(["192.0.2.1"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res
+            .stdout
+            .contains("\n1.2.0.192.in-addr.arpa.\t3600\tIN\tPTR\thost.example.test.\n"));
+
+        let res = cmd.args(["2001:db8::1"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res.stdout.contains(&format!(
+            "\n1.0.0.0.{}8.b.d.0.1.0.0.2.ip6.arpa.\t",
+            "0.0.0.0.".repeat(5)
+        )));
+
+        let res = cmd.args(["example.test"]).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("invalid IP address 'example.test'"));
+    }
+
+    #[test]
+    fn resolv_conf() {
+        let cmd = FakeCmd::new(["dnst", "query", "example.test"])
+            .file(
+                "/etc/resolv.conf",
+                "nameserver 192.0.2.2\nnameserver 192.0.2.3\n",
+            )
+            .server(|addr, msg| {
+                // The first server does not respond.
+                if addr.ip().to_string() == "192.0.2.2" {
+                    return None;
+                }
+                respond(msg)
+            });
+
+        let res = cmd.args(["--timeout", "1", "--retries", "0"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res.stdout.contains(";; SERVER: 192.0.2.3:53 (UDP)\n"));
+
+        let res = cmd
+            .args(["--timeout", "1", "--retries", "0"])
+            .server(|_, _| None)
+            .run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("error querying 192.0.2.2:53: "));
+        assert!(res.stderr.contains("error querying 192.0.2.3:53: "));
+    }
+
+    #[test]
+    fn server_name() {
+        let cmd = FakeCmd::new(["dnst", "query", "-s", "ns.example.test", "example.test"])
+            .file("/etc/hosts", "# comment\n192.0.2.53 ns.example.test ns\n")
+            .server(|addr, msg| {
+                assert_eq!(addr.to_string(), "192.0.2.53:53");
+                respond(msg)
+            });
+
+        let res = cmd.run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res.stdout.contains(";; SERVER: 192.0.2.53:53 (UDP)\n"));
+
+        let res = FakeCmd::new(["dnst", "query", "-s", "ns.invalid", "example.test"]).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("could not resolve ns.invalid: "));
+    }
+
+    #[test]
+    fn tcp() {
+        let script = "\
+CONFIG_END
+
+SCENARIO_BEGIN A recursive resolver for example.test
+
+RANGE_BEGIN 0 100
+ENTRY_BEGIN
+MATCH opcode qtype qname
+ADJUST copy_id
+REPLY QR RD RA NOERROR
+SECTION QUESTION
+example.test. IN A
+SECTION ANSWER
+example.test. 3600 IN A 192.0.2.1
+ENTRY_END
+RANGE_END
+
+SCENARIO_END
+";
+        let res = FakeCmd::new(["drill", "-t", "example.test", "@192.0.2.53"])
+            .stelline("query", script)
+            .run();
+
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res
+            .stdout
+            .contains("example.test.\t3600\tIN\tA\t192.0.2.1\n"));
+        assert!(res.stdout.contains(";; SERVER: 192.0.2.53:53 (TCP)\n"));
+    }
+
+    #[test]
+    fn json() {
+        let res = FakeCmd::new([
+            "dnst",
+            "--format",
+            "json",
+            "query",
+            "-s",
+            "192.0.2.53",
+            "-D",
+        ])
+        .args(["example.test"])
+        .server(|_, msg| respond(msg))
+        .run();
+
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        let json: serde_json::Value = serde_json::from_str(&res.stdout).unwrap();
+        assert_eq!(json["server"], "192.0.2.53:53");
+        assert_eq!(json["transport"], "udp");
+        assert_eq!(json["reply"]["rcode"], "NOERROR");
+        assert_eq!(json["reply"]["answer"][0]["data"], "192.0.2.1");
+        assert_eq!(json["reply"]["additional"], serde_json::json!([]));
+        assert_eq!(json["reply"]["edns"]["dnssec_ok"], true);
+    }
+
+    #[test]
+    fn validate() {
+        let (zones, anchor) = signed_zones();
+        let cmd = FakeCmd::new(["dnst", "query", "-s", "192.0.2.53", "--validate"])
+            .file("test.ds", &anchor)
+            .server(move |_, msg| {
+                assert!(msg.header().cd());
+                assert!(msg.opt().unwrap().dnssec_ok());
+                Some(resolve(&zones, msg))
+            });
+
+        let res = cmd.args(["-k", "test.ds", "www.example.test."]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        let key_tag = anchor.split_whitespace().nth(4).unwrap();
+        assert!(res.stdout.contains(&format!(
+            "\n;; CHAIN OF TRUST:\n;; test.\ttrust anchor {key_tag}\tvalid\n;; example.test.\tDS "
+        )));
+        assert!(res.stdout.ends_with("\tvalid\n;; VALIDATION: secure\n"));
+
+        let res = cmd.args(["-k", "test.ds", "www.insecure.test."]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res
+            .stdout
+            .contains(";; insecure.test.\tno DS\tinsecure delegation\n"));
+        assert!(res.stdout.ends_with(";; VALIDATION: insecure\n"));
+
+        // With the built-in trust anchors, the chain starts at the root zone,
+        // which the server does not know.
+        let res = cmd.args(["www.example.test."]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res
+            .stdout
+            .contains(";; .\ttrust anchor 20326\tbogus: there are no DNSKEY records\n"));
+        assert!(res.stdout.contains(";; VALIDATION: bogus ("));
+    }
+
+    #[test]
+    fn validate_bogus() {
+        let (mut zones, anchor) = signed_zones();
+
+        // Change the address of www.example.test. without signing it again.
+        for record in &mut zones[1].1 {
+            if record.rtype() == Rtype::A {
+                *record = parse_record(
+                    "www.example.test. 3600 IN A 192.0.2.99",
+                    &ZoneName::root_bytes(),
+                )
+                .unwrap();
+            }
+        }
+
+        let res = FakeCmd::new(["dnst", "--format", "json", "query", "-s", "192.0.2.53"])
+            .args(["--validate", "-k", "test.ds", "www.example.test."])
+            .file("test.ds", &anchor)
+            .server(move |_, msg| Some(resolve(&zones, msg)))
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+
+        let json: serde_json::Value = serde_json::from_str(&res.stdout).unwrap();
+        assert_eq!(json["reply"]["answer"][0]["data"], "192.0.2.99");
+        let validation = &json["validation"];
+        assert_eq!(validation["state"], "bogus");
+        assert!(validation["reason"].is_string());
+        // The chain of trust itself holds.
+        assert_eq!(validation["chain"][0]["zone"], "test.");
+        assert_eq!(validation["chain"][0]["anchor"], true);
+        assert_eq!(validation["chain"][1]["zone"], "example.test.");
+        assert_eq!(validation["chain"][1]["anchor"], false);
+        assert_eq!(validation["chain"][1]["problem"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn validate_error() {
+        let (_, anchor) = signed_zones();
+
+        // The validator cannot parse an A record of three octets.
+        let res = FakeCmd::new(["dnst", "query", "-s", "192.0.2.53"])
+            .args(["--validate", "-k", "test.ds", "www.example.test."])
+            .file("test.ds", &anchor)
+            .server(|_, msg| {
+                let question = msg.sole_question().unwrap();
+                let mut builder = MessageBuilder::new_vec()
+                    .start_answer(msg, Rcode::NOERROR)
+                    .unwrap();
+                let data = UnknownRecordData::from_octets(Rtype::A, vec![192, 0, 2]).unwrap();
+                builder
+                    .push((question.qname(), Ttl::from_secs(3600), data))
+                    .unwrap();
+                Some(builder.into_message())
+            })
+            .run();
+
+        // The response is still printed.
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res.stdout.contains(";; SERVER: 192.0.2.53:53 (UDP)\n"));
+        assert!(res.stdout.contains(
+            ";; VALIDATION: indeterminate (could not validate the response: ParseError)\n"
+        ));
+    }
+}
@@ -0,0 +1,527 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bytes::Bytes;
+use clap::builder::ValueParser;
+use domain::base::iana::Rtype;
+use domain::base::{Name, Record, Serial};
+use domain::rdata::{Soa, ZoneRecordData};
+use lexopt::Arg;
+
+use crate::args::OutputFormat;
+use crate::env::Env;
+use crate::error::Error;
+use crate::zone::{display_record_as, is_dnssec_type, read_zone, read_zone_file, sort_records};
+use crate::zone::{ZoneName, ZoneRecord};
+
+use super::{parse_os, parse_os_with, LdnsCommand};
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct ReadZone {
+    /// Lowercase all owner names
+    #[arg(short = 'c', long = "canonicalize")]
+    canonicalize: bool,
+
+    /// Only print DNSSEC records
+    #[arg(short = 'd', long = "only-dnssec", conflicts_with = "strip_dnssec")]
+    only_dnssec: bool,
+
+    /// Do not print records of the given type
+    #[arg(
+        short = 'e',
+        long = "exclude-type",
+        value_name = "TYPE",
+        value_parser = ValueParser::new(ReadZone::parse_rtype),
+        conflicts_with = "only_types"
+    )]
+    exclude_types: Vec<Rtype>,
+
+    /// Only print records of the given type
+    #[arg(
+        short = 'E',
+        long = "only-type",
+        value_name = "TYPE",
+        value_parser = ValueParser::new(ReadZone::parse_rtype)
+    )]
+    only_types: Vec<Rtype>,
+
+    /// Do not print the SOA record
+    #[arg(short = 'n', long = "no-soa")]
+    no_soa: bool,
+
+    /// Only print the SOA record
+    #[arg(long = "soa-only", conflicts_with = "no_soa")]
+    soa_only: bool,
+
+    /// Remove DNSSEC records (RRSIG, NSEC, NSEC3 and NSEC3PARAM)
+    #[arg(short = 's', long = "strip-dnssec")]
+    strip_dnssec: bool,
+
+    /// Set the SOA serial: `[+|-]NUMBER`, `YYYYMMDDxx` or `unixtime` (implies --strip-dnssec)
+    #[arg(
+        short = 'S',
+        long = "serial",
+        value_name = "SERIAL",
+        value_parser = ValueParser::new(SerialArg::from_str),
+        allow_hyphen_values = true
+    )]
+    serial: Option<SerialArg>,
+
+    /// Sort the records in canonical order (implies --canonicalize)
+    #[arg(short = 'z', long = "sort")]
+    sort: bool,
+
+    /// The zone file to read, or `-` for stdin [default: stdin]
+    #[arg(value_name = "ZONEFILE")]
+    zonefile: Option<PathBuf>,
+}
+
+/// How to change the SOA serial
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SerialArg {
+    /// Set the serial to a number
+    Set(u32),
+
+    /// Add a number to the serial
+    Increment(u32),
+
+    /// Subtract a number from the serial
+    Decrement(u32),
+
+    /// Use the `YYYYMMDDxx` date counter format
+    DateCounter,
+
+    /// Use the number of seconds since the epoch
+    UnixTime,
+}
+
+const LDNS_HELP: &str = "\
+Usage: ldns-read-zone [OPTIONS] <zonefile>
+\tReads the zonefile and prints it.
+\t-c canonicalize all rrs in the zone.
+\t-d only show DNSSEC data from the zone
+\t-e <rr type>
+\t\tDo not print RRs of the given <rr type>.
+\t\tThis option may be given multiple times.
+\t\t-e is not meant to be used together with -E.
+\t-E <rr type>
+\t\tPrint only RRs of the given <rr type>.
+\t\tThis option may be given multiple times.
+\t\t-E is not meant to be used together with -e.
+\t-h show this text
+\t-n do not print the SOA record
+\t-s strip DNSSEC data from the zone
+\t-S [[+|-]<number> | YYYYMMDDxx | unixtime ]
+\t\tSet serial number to <number> or, when preceded by a sign,
+\t\toffset the existing number with <number>.  With YYYYMMDDxx
+\t\tthe serial is formatted as a datecounter, and with unixtime as
+\t\tthe number of seconds since 1-1-1970.  However, on serial
+\t\tnumber decrease, +1 is used in stead.  (implies -s)
+\t-z sort the zone (implies -c).
+
+  If no file is given standard input is read\
+";
+
+impl LdnsCommand for ReadZone {
+    const HELP: &'static str = LDNS_HELP;
+
+    fn parse_ldns<I: IntoIterator<Item = OsString>>(args: I) -> Result<Self, Error> {
+        let mut canonicalize = false;
+        let mut only_dnssec = false;
+        let mut exclude_types = Vec::new();
+        let mut only_types = Vec::new();
+        let mut no_soa = false;
+        let mut strip_dnssec = false;
+        let mut serial = None;
+        let mut sort = false;
+        let mut zonefile = None;
+
+        let mut parser = lexopt::Parser::from_args(args);
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Arg::Short('c') => canonicalize = true,
+                Arg::Short('d') => only_dnssec = true,
+                Arg::Short('e') => {
+                    let val = parser.value()?;
+                    exclude_types.push(parse_os_with("rr type (-e)", &val, ReadZone::parse_rtype)?);
+                }
+                Arg::Short('E') => {
+                    let val = parser.value()?;
+                    only_types.push(parse_os_with("rr type (-E)", &val, ReadZone::parse_rtype)?);
+                }
+                Arg::Short('n') => no_soa = true,
+                Arg::Short('s') => strip_dnssec = true,
+                Arg::Short('S') => {
+                    let val = parser.value()?;
+                    serial = Some(parse_os("serial (-S)", &val)?);
+                }
+                Arg::Short('z') => sort = true,
+                Arg::Value(val) => {
+                    if zonefile.is_some() {
+                        return Err("Only one zone file can be given".into());
+                    }
+                    zonefile = Some(parse_os("zone file", &val)?);
+                }
+                Arg::Short(x) => return Err(format!("Invalid short option: -{x}").into()),
+                Arg::Long(x) => {
+                    return Err(format!("Long options are not supported, but `--{x}` given").into())
+                }
+            }
+        }
+
+        Ok(Self {
+            canonicalize,
+            only_dnssec,
+            exclude_types,
+            only_types,
+            no_soa,
+            // ldns-read-zone has no option for this, but -E SOA does the same
+            soa_only: false,
+            strip_dnssec,
+            serial,
+            sort,
+            zonefile,
+        })
+    }
+}
+
+impl ReadZone {
+    pub fn parse_rtype(arg: &str) -> Result<Rtype, &'static str> {
+        Rtype::from_str(&arg.to_uppercase()).map_err(|_| "unknown record type")
+    }
+}
+
+impl FromStr for SerialArg {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = "expected [+|-]<number>, YYYYMMDDxx or unixtime";
+        match s {
+            "YYYYMMDDxx" => Ok(Self::DateCounter),
+            "unixtime" => Ok(Self::UnixTime),
+            _ => {
+                if let Some(n) = s.strip_prefix('+') {
+                    n.parse().map(Self::Increment).map_err(|_| invalid)
+                } else if let Some(n) = s.strip_prefix('-') {
+                    n.parse().map(Self::Decrement).map_err(|_| invalid)
+                } else {
+                    s.parse().map(Self::Set).map_err(|_| invalid)
+                }
+            }
+        }
+    }
+}
+
+impl SerialArg {
+    /// Compute the new serial from the current one
+    fn apply(self, current: Serial) -> Serial {
+        let now = time::OffsetDateTime::now_utc();
+        let new = match self {
+            Self::Set(n) => return Serial(n),
+            Self::Increment(n) => return current.add(n),
+            Self::Decrement(n) => return Serial(current.into_int().wrapping_sub(n)),
+            Self::DateCounter => {
+                let date = now.year() as u32 * 10000
+                    + u8::from(now.month()) as u32 * 100
+                    + now.day() as u32;
+                Serial(date.wrapping_mul(100))
+            }
+            Self::UnixTime => Serial(now.unix_timestamp() as u32),
+        };
+
+        // The serial must increase for secondaries to pick up the change.
+        if new > current {
+            new
+        } else {
+            current.add(1)
+        }
+    }
+}
+
+impl ReadZone {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let mut records = match &self.zonefile {
+            Some(path) if path.as_os_str() != "-" => read_zone_file(&env, path, None)?,
+            _ => read_zone(&env, &mut env.stdin(), "-", None)?,
+        };
+
+        let strip_dnssec = self.strip_dnssec || self.serial.is_some();
+        records.retain(|r| {
+            let rtype = r.rtype();
+            if strip_dnssec && is_dnssec_type(rtype) || self.only_dnssec && !is_dnssec_type(rtype) {
+                return false;
+            }
+            if self.exclude_types.contains(&rtype) || self.soa_only && rtype != Rtype::SOA {
+                return false;
+            }
+            self.only_types.is_empty() || self.only_types.contains(&rtype)
+        });
+
+        if self.canonicalize || self.sort {
+            for record in &mut records {
+                let owner = lowercase_name(record.owner());
+                *record = Record::new(owner, record.class(), record.ttl(), record.data().clone());
+            }
+        }
+
+        if self.sort {
+            sort_records(&mut records);
+        }
+
+        // The SOA record is printed first, like ldns does.
+        let soa_index = records.iter().position(|r| r.rtype() == Rtype::SOA);
+        let soa = soa_index.map(|i| records.remove(i));
+
+        let mut out = env.stdout();
+        if let Some(mut soa) = soa {
+            if let Some(serial) = self.serial {
+                soa = set_serial(soa, serial);
+            }
+            if !self.no_soa {
+                writeln!(out, "{}", display_record_as(&soa, format));
+            }
+        }
+
+        for record in &records {
+            writeln!(out, "{}", display_record_as(record, format));
+        }
+
+        Ok(())
+    }
+}
+
+fn set_serial(record: ZoneRecord, serial: SerialArg) -> ZoneRecord {
+    let ZoneRecordData::Soa(soa) = record.data() else {
+        return record;
+    };
+    let soa = Soa::new(
+        soa.mname().clone(),
+        soa.rname().clone(),
+        serial.apply(soa.serial()),
+        soa.refresh(),
+        soa.retry(),
+        soa.expire(),
+        soa.minimum(),
+    );
+    Record::new(
+        record.owner().clone(),
+        record.class(),
+        record.ttl(),
+        soa.into(),
+    )
+}
+
+fn lowercase_name(name: &ZoneName) -> ZoneName {
+    // Label lengths are at most 63, so they are not touched by this.
+    let mut octets = name.as_slice().to_vec();
+    octets.make_ascii_lowercase();
+    Name::from_octets(Bytes::from(octets)).expect("lowercasing keeps the name valid")
+}
+
+#[cfg(test)]
+mod test {
+    use super::SerialArg;
+    use crate::env::fake::FakeCmd;
+    use domain::base::Serial;
+
+    #[test]
+    fn dnst_parse() {
+        let cmd = FakeCmd::new(["dnst", "read-zone"]);
+
+        assert!(cmd.parse().is_ok());
+        assert!(cmd.args(["zonefile"]).parse().is_ok());
+        assert!(cmd.args(["-E", "soa", "zonefile"]).parse().is_ok());
+        assert!(cmd.args(["-E", "foo", "zonefile"]).parse().is_err());
+        assert!(cmd.args(["--soa-only", "zonefile"]).parse().is_ok());
+        assert!(cmd.args(["--soa-only", "-n", "zonefile"]).parse().is_err());
+        assert!(cmd.args(["-S", "-5", "zonefile"]).parse().is_ok());
+        assert!(cmd.args(["-S", "x", "zonefile"]).parse().is_err());
+        assert!(cmd.args(["-s", "-d", "zonefile"]).parse().is_err());
+    }
+
+    #[test]
+    fn ldns_parse() {
+        let cmd = FakeCmd::new(["ldns-read-zone"]);
+
+        assert!(cmd.parse().is_ok());
+        assert!(cmd
+            .args(["-c", "-e", "TXT", "-e", "A", "-n", "-S", "+1", "-z", "zonefile"])
+            .parse()
+            .is_ok());
+        assert!(cmd.args(["-x", "zonefile"]).parse().is_err());
+        assert!(cmd.args(["zonefile", "zonefile"]).parse().is_err());
+    }
+
+    #[test]
+    fn serial() {
+        let current = Serial(2024010100);
+        assert_eq!(SerialArg::Set(5).apply(current), Serial(5));
+        assert_eq!(SerialArg::Increment(5).apply(current), Serial(2024010105));
+        assert_eq!(SerialArg::Decrement(5).apply(current), Serial(2024010095));
+        assert!(SerialArg::DateCounter.apply(current) > current);
+        // On a decrease, the serial is incremented instead.
+        assert_eq!(
+            SerialArg::UnixTime.apply(Serial(u32::MAX / 2 + 1)),
+            Serial(u32::MAX / 2 + 2)
+        );
+    }
+
+    #[test]
+    fn read() {
+        let zone = "\
+$ORIGIN example.test.
+$TTL 600
+WWW A 192.0.2.1
+@ SOA ns hostmaster 2024010101 7200 3600 1209600 300
+  NS ns
+$INCLUDE zones/include sub.example.test.
+www RRSIG A 13 3 600 20300101000000 20240101000000 1234 example.test. AAAA
+";
+
+        let cmd = FakeCmd::new(["dnst", "read-zone"])
+            .file("zones/zone", zone)
+            .file("zones/include", "Mail 300 MX 10 mail.example.test.\n");
+
+        let res = cmd.args(["zones/zone"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(
+            res.stdout,
+            "\
+example.test.\t600\tIN\tSOA\tns.example.test. hostmaster.example.test. 2024010101 7200 3600 1209600 300
+WWW.example.test.\t600\tIN\tA\t192.0.2.1
+example.test.\t600\tIN\tNS\tns.example.test.
+Mail.sub.example.test.\t300\tIN\tMX\t10 mail.example.test.
+www.example.test.\t600\tIN\tRRSIG\tA 13 3 600 20300101000000 20240101000000 1234 example.test. AAAA
+"
+        );
+
+        // Without a zone file, the zone is read from stdin.
+        let res = cmd.stdin(zone).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout.lines().count(), 5);
+
+        let res = FakeCmd::new(["ldns-read-zone", "-z", "-S", "+1", "-"])
+            .file("zones/include", "Mail 300 MX 10 mail.example.test.\n")
+            .stdin(zone)
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(
+            res.stdout,
+            "\
+example.test.\t600\tIN\tSOA\tns.example.test. hostmaster.example.test. 2024010102 7200 3600 1209600 300
+example.test.\t600\tIN\tNS\tns.example.test.
+mail.sub.example.test.\t300\tIN\tMX\t10 mail.example.test.
+www.example.test.\t600\tIN\tA\t192.0.2.1
+"
+        );
+
+        let res = cmd.args(["-E", "SOA", "-E", "NS", "zones/zone"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout.lines().count(), 2);
+
+        let soa = "example.test.\t600\tIN\tSOA\tns.example.test. hostmaster.example.test. 2024010102 7200 3600 1209600 300\n";
+        let res = cmd.args(["--soa-only", "-S", "+1", "zones/zone"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, soa);
+
+        // ldns-read-zone does the same with -E SOA.
+        let res = FakeCmd::new(["ldns-read-zone", "-E", "SOA", "-S", "+1", "zones/zone"])
+            .file("zones/zone", zone)
+            .file("zones/include", "")
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, soa);
+
+        let res = cmd.args(["-d", "-n", "zones/zone"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res
+            .stdout
+            .starts_with("www.example.test.\t600\tIN\tRRSIG\t"));
+        assert_eq!(res.stdout.lines().count(), 1);
+    }
+
+    #[test]
+    fn parse_error() {
+        let res = FakeCmd::new(["dnst", "read-zone", "zone"])
+            .file("zone", "$ORIGIN example.test.\n$INCLUDE include\n")
+            .file("include", "www A 192.0.2.1\nwww A not-an-address\n")
+            .run();
+        assert_eq!(res.exit_code, 1, "{}", res.stderr);
+        assert!(res.stderr.contains("parse error at include:2: "));
+        assert!(res
+            .stderr
+            .contains("\n... while reading include\n\n... while reading zone"));
+
+        // The line is where the failing entry starts, also after comments
+        // and entries that span lines.
+        let res = FakeCmd::new(["dnst", "read-zone", "zone"])
+            .file(
+                "zone",
+                "$ORIGIN example.test.\n\
+                 ; comment (\n\
+                 @ SOA ns hostmaster (\n    1 2 3 4 5 )\n\
+                 www A bad\n\
+                 mail A 192.0.2.2\n",
+            )
+            .run();
+        assert_eq!(res.exit_code, 1, "{}", res.stderr);
+        assert!(
+            res.stderr.contains("parse error at zone:5: "),
+            "{}",
+            res.stderr
+        );
+    }
+
+    #[test]
+    fn no_final_newline() {
+        let res = FakeCmd::new(["dnst", "read-zone", "zone"])
+            .file(
+                "zone",
+                "$ORIGIN example.test.\nwww 3600 IN A 192.0.2.1 ; no newline",
+            )
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, "www.example.test.\t3600\tIN\tA\t192.0.2.1\n");
+    }
+
+    #[test]
+    fn missing_include() {
+        let res = FakeCmd::new(["dnst", "read-zone", "zone"])
+            .file("zone", "$ORIGIN example.test.\n$INCLUDE missing\n")
+            .run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("could not open file: "));
+        assert!(res.stderr.contains("... while reading missing"));
+    }
+
+    #[test]
+    fn json() {
+        let cmd = FakeCmd::new(["dnst", "--format", "json", "read-zone", "zone"]).file(
+            "zone",
+            "$ORIGIN example.test.\n@ 600 SOA ns hostmaster 1 2 3 4 5\nwww 60 A 192.0.2.1\n",
+        );
+
+        let res = cmd.run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(
+            res.stdout,
+            "\
+{\"class\":\"IN\",\"data\":\"ns.example.test. hostmaster.example.test. 1 2 3 4 5\",\"owner\":\"example.test.\",\"ttl\":600,\"type\":\"SOA\"}
+{\"class\":\"IN\",\"data\":\"192.0.2.1\",\"owner\":\"www.example.test.\",\"ttl\":60,\"type\":\"A\"}
+"
+        );
+
+        // Errors are reported as JSON on stderr.
+        let res = FakeCmd::new(["dnst", "--format", "json", "read-zone", "missing"]).run();
+        assert_eq!(res.exit_code, 1);
+        assert_eq!(res.stdout, "");
+        let error: serde_json::Value = serde_json::from_str(&res.stderr).unwrap();
+        assert!(error["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("could not open file: "));
+        assert_eq!(error["context"], serde_json::json!(["reading missing"]));
+    }
+}
@@ -0,0 +1,807 @@
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bytes::Bytes;
+use clap::builder::ValueParser;
+use domain::base::iana::{Class, Nsec3HashAlg, Rtype};
+use domain::base::name::{Name, ToName};
+use domain::base::{Record, Serial, Ttl};
+use domain::rdata::dnssec::{RtypeBitmap, Timestamp};
+use domain::rdata::nsec3::{Nsec3Salt, OwnerHash};
+use domain::rdata::{Dnskey, Nsec, Nsec3, Nsec3param, Rrsig, Soa, ZoneRecordData};
+use domain::validate::RrsigExt;
+use lexopt::Arg;
+
+use crate::args::OutputFormat;
+use crate::env::Env;
+use crate::error::{Context, Error};
+use crate::keys::{KeyPair, Signer};
+use crate::zone::{
+    display_record_as, group_nodes, is_dnssec_type, is_in_zone, read_zone_file, sort_records, Node,
+    NodeKind, ZoneName, ZoneRecord,
+};
+
+use super::nsec3hash::{lint_nsec3_params, nsec3_chain, nsec3_names, nsec3_owner};
+use super::nsec3hash::{Nsec3Digest, Nsec3Hash};
+use super::{parse_os, parse_os_with, LdnsCommand};
+
+/// The default validity period of signatures: four weeks
+const DEFAULT_VALIDITY: u32 = 4 * 7 * 24 * 60 * 60;
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct SignZone {
+    /// Use NSEC3 instead of NSEC
+    #[arg(short = 'n', long = "nsec3")]
+    use_nsec3: bool,
+
+    /// The NSEC3 hashing algorithm
+    #[arg(
+        short = 'a',
+        long = "algorithm",
+        value_name = "NUMBER_OR_MNEMONIC",
+        default_value_t = Nsec3HashAlg::SHA1,
+        value_parser = ValueParser::new(Nsec3Hash::parse_nsec_alg),
+        requires = "use_nsec3"
+    )]
+    algorithm: Nsec3HashAlg,
+
+    /// The number of NSEC3 hash iterations
+    #[arg(
+        short = 't',
+        long = "iterations",
+        value_name = "NUMBER",
+        default_value_t = 0,
+        requires = "use_nsec3"
+    )]
+    iterations: u16,
+
+    /// The NSEC3 salt in hex representation
+    #[arg(
+        short = 's',
+        long = "salt",
+        value_name = "HEX_STRING",
+        default_value_t = Nsec3Salt::empty(),
+        requires = "use_nsec3"
+    )]
+    salt: Nsec3Salt<Vec<u8>>,
+
+    /// Set the opt-out flag on all NSEC3 records and skip insecure delegations
+    #[arg(short = 'p', long = "opt-out", requires = "use_nsec3")]
+    opt_out: bool,
+
+    /// The expiration time of the signatures [default: four weeks from now]
+    #[arg(
+        short = 'e',
+        long = "expiration",
+        value_name = "DATE",
+        value_parser = ValueParser::new(SignZone::parse_timestamp)
+    )]
+    expiration: Option<Timestamp>,
+
+    /// The inception time of the signatures [default: now]
+    #[arg(
+        short = 'i',
+        long = "inception",
+        value_name = "DATE",
+        value_parser = ValueParser::new(SignZone::parse_timestamp)
+    )]
+    inception: Option<Timestamp>,
+
+    /// The origin of the zone, by default the owner of the SOA record
+    #[arg(
+        short = 'o',
+        long = "origin",
+        value_name = "DOMAIN_NAME",
+        value_parser = ValueParser::new(Nsec3Hash::parse_name)
+    )]
+    origin: Option<Name<Vec<u8>>>,
+
+    /// The file to write the signed zone to, `-` for stdout [default: <ZONEFILE>.signed]
+    #[arg(short = 'f', long = "out-file", value_name = "FILE")]
+    out_file: Option<PathBuf>,
+
+    /// Do not add the DNSKEY records of the keys to the zone
+    #[arg(short = 'd', long = "no-dnskeys")]
+    no_dnskeys: bool,
+
+    /// Sign the DNSKEY RRset with all keys instead of only the key signing keys
+    #[arg(short = 'A', long = "sign-dnskeys-with-all-keys")]
+    all_keys_for_dnskeys: bool,
+
+    /// Set the SOA serial to the number of seconds since 1-1-1970
+    #[arg(short = 'u', long = "set-soa-serial-to-epoch-time")]
+    epoch_serial: bool,
+
+    /// The zone file to sign
+    #[arg(value_name = "ZONEFILE")]
+    zonefile: PathBuf,
+
+    /// The keys to sign the zone with, given by their base name K<name>+<alg>+<id>
+    #[arg(value_name = "KEY", required = true)]
+    keys: Vec<PathBuf>,
+
+    /// Whether to check the NSEC3 parameters against RFC 9276, which ldns does not
+    #[arg(skip = true)]
+    lint: bool,
+}
+
+const LDNS_HELP: &str = "\
+ldns-signzone [OPTIONS] zonefile key [key [key]]
+  signs the zone with the given key(s)
+  -d\t\tused keys are not added to the zone
+  -e <date>\texpiration date
+  -f <file>\toutput zone to file (default <name>.signed)
+  -i <date>\tinception date
+  -o <domain>\torigin for the zone
+  -u\t\tset SOA serial to the number of seconds since 1-1-1970
+  -A\t\tsign DNSKEY with all keys instead of minimal
+  -n\t\tuse NSEC3 instead of NSEC.
+\t\tIf you use NSEC3, you can specify the following extra options:
+\t\t-a [algorithm] hashing algorithm
+\t\t-t [number] number of hash iterations
+\t\t-s [string] salt
+\t\t-p set the opt-out flag on all nsec3 rrs
+
+  keys must be specified by their base name (usually K<name>+<alg>+<id>),
+  i.e. WITHOUT the .private extension.
+  The DNSKEY RR is read from the file called <base name>.key.
+  A date can be a timestamp (seconds since the epoch), or of
+  the form <YYYYMMdd[hhmmss]>\
+";
+
+impl LdnsCommand for SignZone {
+    const HELP: &'static str = LDNS_HELP;
+
+    fn parse_ldns<I: IntoIterator<Item = OsString>>(args: I) -> Result<Self, Error> {
+        let mut use_nsec3 = false;
+        let mut algorithm = Nsec3HashAlg::SHA1;
+        // ldns defaults to a single extra iteration
+        let mut iterations = 1;
+        let mut salt = Nsec3Salt::empty();
+        let mut opt_out = false;
+        let mut expiration = None;
+        let mut inception = None;
+        let mut origin = None;
+        let mut out_file = None;
+        let mut no_dnskeys = false;
+        let mut all_keys_for_dnskeys = false;
+        let mut epoch_serial = false;
+        let mut zonefile = None;
+        let mut keys = Vec::new();
+
+        let mut parser = lexopt::Parser::from_args(args);
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Arg::Short('n') => use_nsec3 = true,
+                Arg::Short('a') => {
+                    let val = parser.value()?;
+                    algorithm = parse_os_with("algorithm (-a)", &val, Nsec3Hash::parse_nsec_alg)?;
+                }
+                Arg::Short('t') => {
+                    let val = parser.value()?;
+                    iterations = parse_os("iterations (-t)", &val)?;
+                }
+                Arg::Short('s') => {
+                    let val = parser.value()?;
+                    salt = parse_os("salt (-s)", &val)?;
+                }
+                Arg::Short('p') => opt_out = true,
+                Arg::Short('e') => {
+                    let val = parser.value()?;
+                    expiration = Some(parse_os_with(
+                        "expiration (-e)",
+                        &val,
+                        SignZone::parse_timestamp,
+                    )?);
+                }
+                Arg::Short('i') => {
+                    let val = parser.value()?;
+                    inception = Some(parse_os_with(
+                        "inception (-i)",
+                        &val,
+                        SignZone::parse_timestamp,
+                    )?);
+                }
+                Arg::Short('o') => {
+                    let val = parser.value()?;
+                    origin = Some(parse_os_with("origin (-o)", &val, Nsec3Hash::parse_name)?);
+                }
+                Arg::Short('f') => {
+                    let val = parser.value()?;
+                    out_file = Some(parse_os("output file (-f)", &val)?);
+                }
+                Arg::Short('d') => no_dnskeys = true,
+                Arg::Short('A') => all_keys_for_dnskeys = true,
+                Arg::Short('u') => epoch_serial = true,
+                Arg::Value(val) => {
+                    if zonefile.is_none() {
+                        zonefile = Some(parse_os("zone file", &val)?);
+                    } else {
+                        keys.push(parse_os("key", &val)?);
+                    }
+                }
+                Arg::Short(x) => return Err(format!("Invalid short option: -{x}").into()),
+                Arg::Long(x) => {
+                    return Err(format!("Long options are not supported, but `--{x}` given").into())
+                }
+            }
+        }
+
+        let Some(zonefile) = zonefile else {
+            return Err("Missing zone file argument".into());
+        };
+
+        if keys.is_empty() {
+            return Err("Missing key argument".into());
+        }
+
+        Ok(Self {
+            use_nsec3,
+            algorithm,
+            iterations,
+            salt,
+            opt_out,
+            expiration,
+            inception,
+            origin,
+            out_file,
+            no_dnskeys,
+            all_keys_for_dnskeys,
+            epoch_serial,
+            zonefile,
+            keys,
+            lint: false,
+        })
+    }
+}
+
+impl SignZone {
+    /// Parse a timestamp in seconds since the epoch or as `YYYYMMdd[hhmmss]`
+    pub fn parse_timestamp(arg: &str) -> Result<Timestamp, &'static str> {
+        // Like ldns, we interpret 8 digits as a date, not as a number.
+        let res = if arg.len() == 8 {
+            Timestamp::from_str(&format!("{arg}000000"))
+        } else {
+            Timestamp::from_str(arg)
+        };
+        res.map_err(|_| "expected seconds since the epoch or a date as YYYYMMdd[hhmmss]")
+    }
+}
+
+impl SignZone {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        self.execute_inner(env, format)
+            .with_context(|| format!("signing {}", self.zonefile.display()))
+    }
+
+    fn execute_inner(&self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        if self.use_nsec3 && self.lint {
+            for warning in lint_nsec3_params(self.iterations, &self.salt)? {
+                warning.pretty_print(&env, format);
+            }
+        }
+
+        let origin = self.origin.as_ref().map(|o| o.to_bytes());
+        let mut records = read_zone_file(&env, &self.zonefile, origin.clone())?;
+
+        // Signing an already signed zone replaces the old signatures.
+        records.retain(|r| !is_dnssec_type(r.rtype()));
+
+        let soa_index = records
+            .iter()
+            .position(|r| r.rtype() == Rtype::SOA)
+            .ok_or("zone has no SOA record")?;
+        let apex = records[soa_index].owner().clone();
+        let class = records[soa_index].class();
+        if let Some(origin) = &origin {
+            if !apex.name_eq(origin) {
+                return Err(format!(
+                    "the SOA record is at {} instead of at the origin {}",
+                    apex.fmt_with_dot(),
+                    origin.fmt_with_dot()
+                )
+                .into());
+            }
+        }
+
+        if self.epoch_serial {
+            let soa = &mut records[soa_index];
+            if let ZoneRecordData::Soa(data) = soa.data() {
+                let data = Soa::new(
+                    data.mname().clone(),
+                    data.rname().clone(),
+                    Serial::now(),
+                    data.refresh(),
+                    data.retry(),
+                    data.expire(),
+                    data.minimum(),
+                );
+                *soa = Record::new(soa.owner().clone(), soa.class(), soa.ttl(), data.into());
+            }
+        }
+
+        let (soa_ttl, soa_minimum) = match records[soa_index].data() {
+            ZoneRecordData::Soa(soa) => (records[soa_index].ttl(), soa.minimum()),
+            _ => unreachable!(),
+        };
+        // RFC 9077: the TTL of denial records is the minimum of the TTL of
+        // the SOA record and its MINIMUM field.
+        let denial_ttl = soa_ttl.min(soa_minimum);
+
+        if let Some(record) = records.iter().find(|r| !is_in_zone(r.owner(), &apex)) {
+            return Err(format!(
+                "{} is not in the zone {}",
+                record.owner().fmt_with_dot(),
+                apex.fmt_with_dot()
+            )
+            .into());
+        }
+
+        let keys = self.load_keys(&env, &apex)?;
+
+        if !self.no_dnskeys {
+            for (key, _) in &keys {
+                let dnskey = Dnskey::new(
+                    key.dnskey().flags(),
+                    key.dnskey().protocol(),
+                    key.dnskey().algorithm(),
+                    Bytes::copy_from_slice(key.dnskey().public_key()),
+                )
+                .map_err(|_| "public key too long")?;
+                records.push(Record::new(apex.clone(), class, soa_ttl, dnskey.into()));
+            }
+        }
+
+        if self.use_nsec3 {
+            let param = Nsec3param::new(self.algorithm, 0, self.iterations, self.salt_bytes());
+            records.push(Record::new(
+                apex.clone(),
+                class,
+                Ttl::from_secs(0),
+                param.into(),
+            ));
+        }
+
+        sort_records(&mut records);
+        let nodes = group_nodes(records, &apex);
+
+        let denial = if self.use_nsec3 {
+            self.nsec3_records(&nodes, &apex, class, denial_ttl)?
+        } else {
+            nsec_records(&nodes, class, denial_ttl)?
+        };
+
+        let mut records: Vec<_> = nodes.into_iter().flat_map(|n| n.rrsets).flatten().collect();
+        records.extend(denial);
+        sort_records(&mut records);
+        let nodes = group_nodes(records, &apex);
+
+        let inception = self.inception.unwrap_or_else(Timestamp::now);
+        let expiration = self
+            .expiration
+            .unwrap_or_else(|| inception.into_int().wrapping_add(DEFAULT_VALIDITY).into());
+
+        let (ksks, zsks) = select_keys(&keys, self.all_keys_for_dnskeys);
+
+        let out_file = self.out_file.clone().unwrap_or_else(|| {
+            let mut path = OsString::from(&self.zonefile);
+            path.push(".signed");
+            path.into()
+        });
+        let to_stdout = out_file.as_os_str() == "-";
+
+        // A zone file is always written as text.
+        let record_format = if to_stdout {
+            format
+        } else {
+            OutputFormat::Text
+        };
+
+        let mut output = String::new();
+        for node in &nodes {
+            // Print the SOA first, like ldns does.
+            let rrsets = node
+                .rrsets
+                .iter()
+                .filter(|rrset| rrset[0].rtype() == Rtype::SOA)
+                .chain(
+                    node.rrsets
+                        .iter()
+                        .filter(|rrset| rrset[0].rtype() != Rtype::SOA),
+                );
+
+            for rrset in rrsets {
+                for record in rrset {
+                    writeln!(output, "{}", display_record_as(record, record_format))
+                        .expect("infallible");
+                }
+
+                let rtype = rrset[0].rtype();
+                let signed = match node.kind {
+                    NodeKind::Apex | NodeKind::Authoritative => true,
+                    NodeKind::Delegation => matches!(rtype, Rtype::DS | Rtype::NSEC),
+                    NodeKind::Occluded => false,
+                };
+                if !signed {
+                    continue;
+                }
+
+                let signers = if rtype == Rtype::DNSKEY { &ksks } else { &zsks };
+                for (key, signer) in signers {
+                    let rrsig = sign_rrset(rrset, &apex, key, signer, inception, expiration)
+                        .with_context(|| {
+                            format!("signing the {rtype} RRset of {}", node.owner.fmt_with_dot())
+                        })?;
+                    writeln!(output, "{}", display_record_as(&rrsig, record_format))
+                        .expect("infallible");
+                }
+            }
+        }
+
+        if to_stdout {
+            write!(env.stdout(), "{output}");
+        } else {
+            env.write(&out_file, output.as_bytes())
+                .map_err(|e| format!("could not write {}: {e}", out_file.display()))?;
+            if format == OutputFormat::Json {
+                let json = serde_json::json!({ "file": out_file.display().to_string() });
+                writeln!(env.stdout(), "{json}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load_keys(&self, env: &impl Env, apex: &ZoneName) -> Result<Vec<(KeyPair, Signer)>, Error> {
+        let mut keys = Vec::new();
+        for path in &self.keys {
+            let key = KeyPair::load(env, path)?;
+            if !key.owner().name_eq(apex) {
+                return Err(format!(
+                    "key {} is for {} instead of {}",
+                    path.display(),
+                    key.owner().fmt_with_dot(),
+                    apex.fmt_with_dot()
+                )
+                .into());
+            }
+            let signer = key.signer()?;
+            keys.push((key, signer));
+        }
+        Ok(keys)
+    }
+
+    fn salt_bytes(&self) -> Nsec3Salt<Bytes> {
+        Nsec3Salt::from_octets(Bytes::copy_from_slice(self.salt.as_slice()))
+            .expect("salt was valid before")
+    }
+
+    /// Create the NSEC3 chain for the zone
+    fn nsec3_records(
+        &self,
+        nodes: &[Node],
+        apex: &ZoneName,
+        class: Class,
+        ttl: Ttl,
+    ) -> Result<Vec<ZoneRecord>, Error> {
+        let names = nsec3_names(nodes, self.opt_out);
+        let digest = Nsec3Digest::new(self.algorithm)?;
+        let hashed = nsec3_chain(names, apex, digest, self.iterations, &self.salt)?;
+
+        let flags = u8::from(self.opt_out);
+        let mut records = Vec::with_capacity(hashed.len());
+        for (i, (hash, _, types)) in hashed.iter().enumerate() {
+            let next = &hashed[(i + 1) % hashed.len()].0;
+            let owner = nsec3_owner(hash, apex)?;
+            let next = OwnerHash::from_octets(Bytes::copy_from_slice(next.as_slice()))
+                .map_err(|_| "invalid NSEC3 hash")?;
+            let data = Nsec3::new(
+                self.algorithm,
+                flags,
+                self.iterations,
+                self.salt_bytes(),
+                next,
+                types.clone(),
+            );
+            records.push(Record::new(owner, class, ttl, data.into()));
+        }
+
+        Ok(records)
+    }
+}
+
+/// Create the NSEC chain for the zone
+fn nsec_records(nodes: &[Node], class: Class, ttl: Ttl) -> Result<Vec<ZoneRecord>, Error> {
+    let nodes: Vec<_> = nodes
+        .iter()
+        .filter(|n| n.kind != NodeKind::Occluded)
+        .collect();
+
+    let mut records = Vec::with_capacity(nodes.len());
+    for (i, node) in nodes.iter().enumerate() {
+        let next = &nodes[(i + 1) % nodes.len()].owner;
+
+        let mut types = RtypeBitmap::<Bytes>::builder();
+        if node.kind == NodeKind::Delegation {
+            types.add(Rtype::NS).expect("infallible");
+            if node.has_rtype(Rtype::DS) {
+                types.add(Rtype::DS).expect("infallible");
+            }
+        } else {
+            for rtype in node.rtypes() {
+                types.add(rtype).expect("infallible");
+            }
+        }
+        types.add(Rtype::RRSIG).expect("infallible");
+        types.add(Rtype::NSEC).expect("infallible");
+
+        let data = Nsec::new(next.clone(), types.finalize());
+        records.push(Record::new(node.owner.clone(), class, ttl, data.into()));
+    }
+
+    Ok(records)
+}
+
+/// A loaded key with the signer created from it
+type SigningKey = (KeyPair, Signer);
+
+/// Select the keys to sign the DNSKEY RRset and the other RRsets with
+///
+/// Key signing keys only sign the DNSKEY RRset, unless there are no zone
+/// signing keys. If there are only zone signing keys, they sign everything.
+fn select_keys(
+    keys: &[SigningKey],
+    all_keys_for_dnskeys: bool,
+) -> (Vec<&SigningKey>, Vec<&SigningKey>) {
+    let (ksks, zsks): (Vec<_>, Vec<_>) = keys
+        .iter()
+        .partition(|(key, _)| key.dnskey().is_secure_entry_point());
+
+    let zsks = if zsks.is_empty() { ksks.clone() } else { zsks };
+    let ksks = if ksks.is_empty() || all_keys_for_dnskeys {
+        keys.iter().collect()
+    } else {
+        ksks
+    };
+    (ksks, zsks)
+}
+
+/// Create the RRSIG record for an RRset
+fn sign_rrset(
+    rrset: &[ZoneRecord],
+    apex: &ZoneName,
+    key: &KeyPair,
+    signer: &Signer,
+    inception: Timestamp,
+    expiration: Timestamp,
+) -> Result<ZoneRecord, Error> {
+    let first = &rrset[0];
+    let owner = first.owner();
+
+    // The labels field does not count the root and a leading wildcard.
+    let mut labels = owner.label_count() - 1;
+    if owner.first().is_wildcard() {
+        labels -= 1;
+    }
+
+    let mut rrsig = Rrsig::new(
+        first.rtype(),
+        key.dnskey().algorithm(),
+        labels as u8,
+        first.ttl(),
+        expiration,
+        inception,
+        key.key_tag(),
+        apex.clone(),
+        Bytes::new(),
+    )
+    .map_err(|_| "invalid RRSIG")?;
+
+    let mut records: Vec<_> = rrset.iter().collect();
+    let mut signed_data = Vec::new();
+    rrsig
+        .signed_data(&mut signed_data, &mut records)
+        .expect("infallible");
+    rrsig.set_signature(signer.sign(&signed_data)?.into());
+
+    Ok(Record::new(
+        owner.clone(),
+        first.class(),
+        first.ttl(),
+        rrsig.into(),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use domain::base::Name;
+
+    use crate::env::fake::FakeCmd;
+    use crate::keys::{KeyAlgorithm, KeyPair, KSK_FLAGS, ZSK_FLAGS};
+
+    const ZONE: &str = "\
+$ORIGIN example.test.
+$TTL 3600
+@       IN SOA ns1 hostmaster 2024010101 7200 3600 1209600 300
+@       IN NS  ns1
+ns1     IN A   192.0.2.1
+www     IN A   192.0.2.2
+*.wild  IN TXT \"wildcard\"
+sub     IN NS  ns.sub
+ns.sub  IN A   192.0.2.3
+a.b.c   IN A   192.0.2.4
+";
+
+    /// Write a zone and a key pair for `cmd`, returning the key base names
+    fn setup(cmd: &FakeCmd) -> (String, String) {
+        cmd.file("zone", ZONE);
+
+        let owner = Name::from_str("example.test").unwrap();
+        let mut names = Vec::new();
+        for flags in [KSK_FLAGS, ZSK_FLAGS] {
+            let key = KeyPair::generate(owner.clone(), KeyAlgorithm::Ed25519, flags, 0).unwrap();
+            let base = key.base_name();
+            cmd.file(format!("{base}.key"), key.display_public().to_string());
+            cmd.file(format!("{base}.private"), key.display_private().to_string());
+            names.push(base);
+        }
+        (names.remove(0), names.remove(0))
+    }
+
+    fn count(output: &str, rtype: &str) -> usize {
+        output
+            .lines()
+            .filter(|l| l.split('\t').nth(3) == Some(rtype))
+            .count()
+    }
+
+    #[test]
+    fn dnst_parse() {
+        let cmd = FakeCmd::new(["dnst", "signzone"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["zonefile"]).parse().is_err());
+        assert!(cmd.args(["zonefile", "key"]).parse().is_ok());
+        assert!(cmd.args(["-t", "1", "zonefile", "key"]).parse().is_err());
+        assert!(cmd
+            .args(["-n", "-t", "1", "zonefile", "key"])
+            .parse()
+            .is_ok());
+        assert!(cmd.args(["-e", "2024", "zonefile", "key"]).parse().is_ok());
+        assert!(cmd
+            .args(["-e", "20240101", "zonefile", "key"])
+            .parse()
+            .is_ok());
+        assert!(cmd
+            .args(["-e", "2024x", "zonefile", "key"])
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn ldns_parse() {
+        let cmd = FakeCmd::new(["ldns-signzone"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["zonefile"]).parse().is_err());
+        assert!(cmd
+            .args([
+                "-n",
+                "-a",
+                "1",
+                "-s",
+                "abcd",
+                "-t",
+                "5",
+                "-o",
+                "example.test"
+            ])
+            .args([
+                "-e",
+                "20300101",
+                "-i",
+                "20240101120000",
+                "zonefile",
+                "key1",
+                "key2"
+            ])
+            .parse()
+            .is_ok());
+        assert!(cmd.args(["-x", "zonefile", "key"]).parse().is_err());
+    }
+
+    #[test]
+    fn sign_nsec() {
+        let cmd = FakeCmd::new(["dnst", "signzone", "-f", "-"]);
+        let (ksk, zsk) = setup(&cmd);
+
+        let res = cmd.args(["zone", &ksk, &zsk]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+
+        let out = &res.stdout;
+        assert!(out.starts_with("example.test.\t3600\tIN\tSOA\t"));
+        assert_eq!(count(out, "DNSKEY"), 2);
+        // apex, ns1, www, *.wild, sub, a.b.c
+        assert_eq!(count(out, "NSEC"), 6);
+        // DNSKEY, SOA, NS, ns1 A, www A, *.wild TXT, a.b.c A and 6 NSEC
+        assert_eq!(count(out, "RRSIG"), 7 + 6);
+        // The wildcard signature has one label less than the owner name.
+        assert!(out.contains("*.wild.example.test.\t3600\tIN\tRRSIG\tTXT 15 3 3600 "));
+        // The delegation and its glue are not signed.
+        assert!(!out.contains("RRSIG\tNS 15 3 "));
+        assert!(!out.contains("RRSIG\tA 15 4 "));
+        assert!(
+            out.contains("sub.example.test.\t300\tIN\tNSEC\t*.wild.example.test. NS RRSIG NSEC")
+        );
+    }
+
+    #[test]
+    fn sign_nsec3() {
+        let cmd = FakeCmd::new(["ldns-signzone", "-n", "-s", "cafe", "-t", "0"]);
+        let (ksk, zsk) = setup(&cmd);
+
+        let res = cmd.args(["zone", &ksk, &zsk]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, "");
+
+        let out = cmd.read_file("zone.signed").unwrap();
+        assert_eq!(count(&out, "NSEC3PARAM"), 1);
+        assert!(out.contains("\tNSEC3PARAM\t1 0 0 cafe\n"));
+        // apex, ns1, www, *.wild, wild, sub, a.b.c, b.c, c
+        assert_eq!(count(&out, "NSEC3"), 9);
+        assert_eq!(count(&out, "NSEC"), 0);
+
+        // The hash of the apex matches the nsec3-hash command.
+        let res = FakeCmd::new(["dnst", "nsec3-hash", "-t", "0", "-s", "cafe"])
+            .args(["example.test"])
+            .run();
+        let hash = res.stdout.trim();
+        assert!(out.contains(&format!(
+            "\n{hash}example.test.\t300\tIN\tNSEC3\t1 0 0 cafe "
+        )));
+
+        // With opt-out, the insecure delegation is left out.
+        let cmd = FakeCmd::new(["dnst", "signzone", "-n", "-p", "-f", "-"]);
+        let (ksk, zsk) = setup(&cmd);
+        let res = cmd.args(["zone", &ksk, &zsk]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(count(&res.stdout, "NSEC3"), 8);
+        assert!(res.stdout.contains("\tNSEC3\t1 1 0 - "));
+    }
+
+    #[test]
+    fn lint_nsec3() {
+        let cmd = FakeCmd::new(["dnst", "signzone", "-n", "-f", "-"]);
+        let (ksk, zsk) = setup(&cmd);
+
+        let res = cmd.args(["-s", "cafe", "zone", &ksk, &zsk]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert!(res.stderr.contains("WARNING: using the NSEC3 salt cafe"));
+
+        let res = cmd.args(["-t", "500", "zone", &ksk, &zsk]).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("using 500 NSEC3 iterations"));
+
+        // ldns does not check the parameters.
+        let cmd = FakeCmd::new(["ldns-signzone", "-n", "-t", "500", "-f", "-"]);
+        let (ksk, zsk) = setup(&cmd);
+        let res = cmd.args(["zone", &ksk, &zsk]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stderr, "");
+    }
+
+    #[test]
+    fn wrong_origin() {
+        let cmd = FakeCmd::new(["dnst", "signzone", "-o", "other.test"]);
+        let (ksk, _) = setup(&cmd);
+
+        let res = cmd.args(["zone", &ksk]).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("instead of at the origin"));
+    }
+}
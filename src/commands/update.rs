@@ -0,0 +1,790 @@
+use std::ffi::OsString;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+
+use bytes::Bytes;
+use clap::builder::ValueParser;
+use domain::base::iana::{Class, Opcode, Rcode, Rtype};
+use domain::base::message::CopyRecordsError;
+use domain::base::message_builder::{AdditionalBuilder, PushError};
+use domain::base::name::ParsedName;
+use domain::base::opt::{ComposeOptData, LongOptData};
+use domain::base::rdata::ComposeRecordData;
+use domain::base::wire::Composer;
+use domain::base::{Header, Message, MessageBuilder, Record, ToName, Ttl, UnknownRecordData};
+use domain::net::client::dgram;
+use domain::net::client::request::{ComposeRequest, Error as RequestError, SendRequest};
+use domain::net::client::tsig;
+use domain::rdata::{Aaaa, Soa, A};
+use lexopt::Arg;
+
+use crate::args::OutputFormat;
+use crate::env::{Env, StubResolve};
+use crate::error::{Context, Error};
+use crate::tsig::TsigKey;
+use crate::zone::{parse_name, parse_record, ZoneName, ZoneRecord};
+
+use super::{parse_os, resolve_server, LdnsCommand};
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct Update {
+    /// The zone to update [default: the zone of the first updated name]
+    #[arg(
+        short = 'z',
+        long = "zone",
+        value_name = "DOMAIN_NAME",
+        value_parser = ValueParser::new(ZoneName::from_str)
+    )]
+    zone: Option<ZoneName>,
+
+    /// The server to send the update to [default: the primary from the SOA record]
+    #[arg(short = 's', long = "server", value_name = "SERVER")]
+    server: Option<String>,
+
+    /// The port to send the update to
+    #[arg(short = 'p', long = "port", value_name = "PORT", default_value_t = 53)]
+    port: u16,
+
+    /// A TSIG key to sign the update with
+    #[arg(short = 'y', long = "tsig", value_name = "NAME:SECRET[:ALGORITHM]")]
+    tsig: Option<TsigKey>,
+
+    /// The maximum number of retries
+    #[arg(
+        short = 'r',
+        long = "retries",
+        value_name = "NUMBER",
+        default_value_t = 3
+    )]
+    retries: u8,
+
+    /// The number of seconds to wait for a response before retrying
+    #[arg(
+        short = 't',
+        long = "timeout",
+        value_name = "SECONDS",
+        default_value_t = 5
+    )]
+    timeout: u64,
+
+    /// Add a record, given in zone file format
+    #[arg(long = "add", value_name = "RECORD")]
+    add: Vec<String>,
+
+    /// Delete a record, given in zone file format
+    #[arg(long = "delete", value_name = "RECORD")]
+    delete: Vec<String>,
+
+    /// Delete the RRset of a type at a name
+    #[arg(long = "delete-rrset", value_name = "NAME TYPE")]
+    delete_rrset: Vec<String>,
+
+    /// Delete all RRsets at a name
+    #[arg(long = "delete-name", value_name = "NAME")]
+    delete_name: Vec<String>,
+
+    /// Require that a record exists
+    #[arg(long = "require-rr", value_name = "RECORD")]
+    require_rr: Vec<String>,
+
+    /// Require that an RRset of a type exists at a name
+    #[arg(long = "require-rrset", value_name = "NAME TYPE")]
+    require_rrset: Vec<String>,
+
+    /// Require that no RRset of a type exists at a name
+    #[arg(long = "forbid-rrset", value_name = "NAME TYPE")]
+    forbid_rrset: Vec<String>,
+
+    /// Require that a name exists
+    #[arg(long = "require-name", value_name = "NAME")]
+    require_name: Vec<String>,
+
+    /// Require that a name does not exist
+    #[arg(long = "forbid-name", value_name = "NAME")]
+    forbid_name: Vec<String>,
+}
+
+const LDNS_HELP: &str = "\
+usage: ldns-update domain [zone] ip tsig_name tsig_alg tsig_hmac
+  send a dynamic update packet to <ip>
+
+  Use 'none' instead of ip to remove any previous address
+  If 'zone'  is not specified, try to figure it out from the zone's SOA
+  Example: ldns-update my.example.org 1.2.3.4\
+";
+
+impl LdnsCommand for Update {
+    const HELP: &'static str = LDNS_HELP;
+
+    fn parse_ldns<I: IntoIterator<Item = OsString>>(args: I) -> Result<Self, Error> {
+        let mut port = 53;
+        let mut values: Vec<String> = Vec::new();
+
+        let mut parser = lexopt::Parser::from_args(args);
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Arg::Short('p') => {
+                    let val = parser.value()?;
+                    port = parse_os("port (-p)", &val)?;
+                }
+                Arg::Value(val) => values.push(parse_os("argument", &val)?),
+                Arg::Short(x) => return Err(format!("Invalid short option: -{x}").into()),
+                Arg::Long(x) => {
+                    return Err(format!("Long options are not supported, but `--{x}` given").into())
+                }
+            }
+        }
+
+        let (domain, zone, ip, tsig) = match values.as_slice() {
+            [domain, ip] => (domain, None, ip, None),
+            [domain, zone, ip] => (domain, Some(zone), ip, None),
+            [domain, ip, name, alg, hmac] => (domain, None, ip, Some((name, alg, hmac))),
+            [domain, zone, ip, name, alg, hmac] => {
+                (domain, Some(zone), ip, Some((name, alg, hmac)))
+            }
+            _ => return Err("Wrong number of arguments".into()),
+        };
+
+        let zone = zone
+            .map(|zone| ZoneName::from_str(zone))
+            .transpose()
+            .map_err(|e| format!("Invalid zone: {e}"))?;
+
+        // The domain is absolute, also if the records are read relative to
+        // the zone.
+        let domain = ZoneName::from_str(domain).map_err(|e| format!("Invalid domain: {e}"))?;
+        let domain = domain.fmt_with_dot();
+
+        let tsig = tsig
+            .map(|(name, alg, hmac)| TsigKey::from_str(&format!("{name}:{hmac}:{alg}")))
+            .transpose()
+            .map_err(|e| format!("Invalid TSIG key: {e}"))?;
+
+        // The address replaces all previous addresses of the same family.
+        let (delete_rrset, add) = match ip.as_str() {
+            "none" => (
+                vec![format!("{domain} A"), format!("{domain} AAAA")],
+                vec![],
+            ),
+            ip => match IpAddr::from_str(ip).map_err(|e| format!("Invalid ip: {e}"))? {
+                IpAddr::V4(ip) => (
+                    vec![format!("{domain} A")],
+                    vec![format!("{domain} 3600 IN A {ip}")],
+                ),
+                IpAddr::V6(ip) => (
+                    vec![format!("{domain} AAAA")],
+                    vec![format!("{domain} 3600 IN AAAA {ip}")],
+                ),
+            },
+        };
+
+        Ok(Self {
+            zone,
+            server: None,
+            port,
+            tsig,
+            retries: 3,
+            timeout: 5,
+            add,
+            delete: Vec::new(),
+            delete_rrset,
+            delete_name: Vec::new(),
+            require_rr: Vec::new(),
+            require_rrset: Vec::new(),
+            forbid_rrset: Vec::new(),
+            require_name: Vec::new(),
+            forbid_name: Vec::new(),
+        })
+    }
+}
+
+/// A record for the prerequisite or update section of an UPDATE message
+///
+/// The record data is kept in wire format, because prerequisites and
+/// deletions use records with empty record data.
+type UpdateRecord = Record<ZoneName, UnknownRecordData<Bytes>>;
+
+impl Update {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| format!("could not start the runtime: {e}"))?;
+        runtime.block_on(self.run(env, format))
+    }
+
+    async fn run(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        // Without a zone, names are absolute until we know the zone.
+        let origin = self.zone.clone().unwrap_or_else(ZoneName::root);
+        let (prerequisites, updates) = self.records(&origin)?;
+
+        let Some(first) = updates.first().map(|r| r.owner().clone()) else {
+            return Err("nothing to update".into());
+        };
+
+        let resolver = env.make_stub_resolver();
+
+        let mut soa = None;
+        let zone = match &self.zone {
+            Some(zone) => zone.clone(),
+            None => {
+                let (zone, data) = find_soa(&resolver, &first)
+                    .await
+                    .with_context(|| format!("finding the zone of {}", first.fmt_with_dot()))?;
+                soa = Some(data);
+                zone
+            }
+        };
+
+        if let Some(record) = prerequisites
+            .iter()
+            .chain(&updates)
+            .find(|r| !r.owner().ends_with(&zone))
+        {
+            return Err(format!(
+                "{} is not in the zone {}",
+                record.owner().fmt_with_dot(),
+                zone.fmt_with_dot()
+            )
+            .into());
+        }
+
+        let addrs = match &self.server {
+            Some(server) => resolve_server(&env, server, self.port)?,
+            None => {
+                let soa = match soa {
+                    Some(soa) => soa,
+                    None => {
+                        find_soa(&resolver, &zone)
+                            .await
+                            .with_context(|| {
+                                format!("finding the SOA record of {}", zone.fmt_with_dot())
+                            })?
+                            .1
+                    }
+                };
+                let primary = soa.mname();
+                lookup_addrs(&resolver, primary, self.port)
+                    .await
+                    .with_context(|| {
+                        format!("finding the address of primary {}", primary.fmt_with_dot())
+                    })?
+            }
+        };
+
+        let request = UpdateRequest::new(zone.clone(), prerequisites, updates);
+        let tsig_key = self.tsig.as_ref().map(TsigKey::to_key).transpose()?;
+
+        let mut config = dgram::Config::new();
+        config.set_max_retries(self.retries);
+        config.set_read_timeout(Duration::from_secs(self.timeout));
+        config.set_udp_payload_size(None);
+
+        let mut last_error = None;
+        for addr in addrs {
+            let context = format!("updating zone {} at {addr}", zone.fmt_with_dot());
+
+            let conn = dgram::Connection::with_config(env.make_connection(addr), config.clone());
+            let request = request.clone();
+
+            let response = match &tsig_key {
+                Some(key) => {
+                    let conn = tsig::Connection::new(key.clone(), conn);
+                    conn.send_request(request).get_response().await
+                }
+                None => conn.send_request(request).get_response().await,
+            };
+
+            let response = match response {
+                Ok(response) => response,
+                Err(e) => {
+                    // Try the next address of the server, if there is any.
+                    last_error = Some(Error::from(e.to_string()).context(&context));
+                    continue;
+                }
+            };
+
+            let rcode = response.header().rcode();
+            if rcode != Rcode::NOERROR {
+                return Err(Error::from(format!("server responded with {rcode}")).context(&context));
+            }
+
+            match format {
+                OutputFormat::Text => writeln!(env.stdout(), ";; UPDATE response was {rcode}"),
+                OutputFormat::Json => writeln!(
+                    env.stdout(),
+                    "{}",
+                    serde_json::json!({ "rcode": rcode.to_string() })
+                ),
+            }
+            return Ok(());
+        }
+
+        Err(last_error.unwrap_or_else(|| "no address found for the server".into()))
+    }
+
+    /// Build the records of the prerequisite and update sections
+    ///
+    /// Deletions come before additions, so that RRsets can be replaced.
+    fn records(&self, origin: &ZoneName) -> Result<(Vec<UpdateRecord>, Vec<UpdateRecord>), Error> {
+        let mut prerequisites = Vec::new();
+        for rr in &self.require_rr {
+            let record = parse_record(rr, origin)?;
+            let class = record.class();
+            prerequisites.push(full_record(record, class, Ttl::ZERO));
+        }
+        for spec in &self.require_rrset {
+            let (name, rtype) = parse_name_type(spec, origin)?;
+            prerequisites.push(empty_record(name, rtype, Class::ANY));
+        }
+        for spec in &self.forbid_rrset {
+            let (name, rtype) = parse_name_type(spec, origin)?;
+            prerequisites.push(empty_record(name, rtype, Class::NONE));
+        }
+        for name in &self.require_name {
+            let name = parse_name(name, origin)?;
+            prerequisites.push(empty_record(name, Rtype::ANY, Class::ANY));
+        }
+        for name in &self.forbid_name {
+            let name = parse_name(name, origin)?;
+            prerequisites.push(empty_record(name, Rtype::ANY, Class::NONE));
+        }
+
+        let mut updates = Vec::new();
+        for name in &self.delete_name {
+            let name = parse_name(name, origin)?;
+            updates.push(empty_record(name, Rtype::ANY, Class::ANY));
+        }
+        for spec in &self.delete_rrset {
+            let (name, rtype) = parse_name_type(spec, origin)?;
+            updates.push(empty_record(name, rtype, Class::ANY));
+        }
+        for rr in &self.delete {
+            let record = parse_record(rr, origin)?;
+            updates.push(full_record(record, Class::NONE, Ttl::ZERO));
+        }
+        for rr in &self.add {
+            let record = parse_record(rr, origin)?;
+            let (class, ttl) = (record.class(), record.ttl());
+            updates.push(full_record(record, class, ttl));
+        }
+
+        Ok((prerequisites, updates))
+    }
+}
+
+fn full_record(record: ZoneRecord, class: Class, ttl: Ttl) -> UpdateRecord {
+    let mut data = Vec::new();
+    record
+        .data()
+        .compose_rdata(&mut data)
+        .expect("composing into a Vec cannot fail");
+    let data = UnknownRecordData::from_octets(record.rtype(), Bytes::from(data))
+        .expect("record data was valid before");
+    Record::new(record.owner().clone(), class, ttl, data)
+}
+
+fn empty_record(name: ZoneName, rtype: Rtype, class: Class) -> UpdateRecord {
+    let data = UnknownRecordData::from_octets(rtype, Bytes::new()).expect("empty data fits");
+    Record::new(name, class, Ttl::ZERO, data)
+}
+
+/// Parse a `NAME TYPE` pair
+fn parse_name_type(spec: &str, origin: &ZoneName) -> Result<(ZoneName, Rtype), Error> {
+    let mut parts = spec.split_whitespace();
+    let (Some(name), Some(rtype), None) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(format!("expected 'NAME TYPE', but got '{spec}'").into());
+    };
+    let name = parse_name(name, origin)?;
+    let rtype = Rtype::from_str(rtype).map_err(|_| format!("invalid record type '{rtype}'"))?;
+    Ok((name, rtype))
+}
+
+/// An UPDATE message to send with a [`SendRequest`] implementation
+///
+/// [`RequestMessage`] cannot be used for this, because it parses the records
+/// in the message, which fails for the empty record data of deletions.
+#[derive(Clone, Debug)]
+struct UpdateRequest {
+    header: Header,
+    zone: ZoneName,
+    prerequisites: Vec<UpdateRecord>,
+    updates: Vec<UpdateRecord>,
+}
+
+impl UpdateRequest {
+    fn new(zone: ZoneName, prerequisites: Vec<UpdateRecord>, updates: Vec<UpdateRecord>) -> Self {
+        let mut header = Header::new();
+        header.set_opcode(Opcode::UPDATE);
+        Self {
+            header,
+            zone,
+            prerequisites,
+            updates,
+        }
+    }
+}
+
+impl ComposeRequest for UpdateRequest {
+    fn append_message<Target: Composer>(
+        &self,
+        target: Target,
+    ) -> Result<AdditionalBuilder<Target>, CopyRecordsError> {
+        let mut msg = MessageBuilder::from_target(target)
+            .map_err(|_| CopyRecordsError::Push(PushError::ShortBuf))?;
+        *msg.header_mut() = self.header;
+
+        let mut msg = msg.question();
+        msg.push((&self.zone, Rtype::SOA, Class::IN))?;
+
+        // The prerequisite section is the answer section and the update
+        // section is the authority section.
+        let mut msg = msg.answer();
+        for record in &self.prerequisites {
+            msg.push(record)?;
+        }
+
+        let mut msg = msg.authority();
+        for record in &self.updates {
+            msg.push(record)?;
+        }
+
+        Ok(msg.additional())
+    }
+
+    fn to_message(&self) -> Result<Message<Vec<u8>>, RequestError> {
+        let msg = self.append_message(Vec::new())?;
+        Ok(msg.into_message())
+    }
+
+    fn to_vec(&self) -> Result<Vec<u8>, RequestError> {
+        Ok(self.to_message()?.into_octets())
+    }
+
+    fn header(&self) -> &Header {
+        &self.header
+    }
+
+    fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
+    // UPDATE messages are sent without EDNS, so the options are ignored.
+
+    fn set_udp_payload_size(&mut self, _value: u16) {}
+
+    fn set_dnssec_ok(&mut self, _value: bool) {}
+
+    fn add_opt(&mut self, _opt: &impl ComposeOptData) -> Result<(), LongOptData> {
+        Ok(())
+    }
+
+    fn is_answer(&self, answer: &Message<[u8]>) -> bool {
+        let header = answer.header();
+        header.qr() && header.id() == self.header.id() && header.opcode() == Opcode::UPDATE
+    }
+
+    fn dnssec_ok(&self) -> bool {
+        false
+    }
+}
+
+/// Send a query to the name servers of the stub resolver
+async fn query(
+    resolver: &impl StubResolve,
+    qname: &ZoneName,
+    qtype: Rtype,
+) -> Result<Message<Bytes>, Error> {
+    resolver
+        .query(qname, qtype)
+        .await
+        .map_err(|e| format!("could not query the name servers: {e}").into())
+}
+
+/// Find the zone containing a name and its SOA record
+///
+/// The SOA record is in the answer section if the name is the apex of the
+/// zone and in the authority section otherwise.
+async fn find_soa(
+    resolver: &impl StubResolve,
+    name: &ZoneName,
+) -> Result<(ZoneName, Soa<ZoneName>), Error> {
+    let response = query(resolver, name, Rtype::SOA).await?;
+
+    let rcode = response.header().rcode();
+    if rcode != Rcode::NOERROR && rcode != Rcode::NXDOMAIN {
+        return Err(format!("SOA query failed with {rcode}").into());
+    }
+
+    let answer = response
+        .answer()
+        .map_err(|e| format!("invalid response: {e}"))?;
+    let authority = answer
+        .next_section()
+        .map_err(|e| format!("invalid response: {e}"))?
+        .ok_or("invalid response: missing authority section")?;
+
+    for section in [answer, authority] {
+        for record in section.limit_to::<Soa<ParsedName<_>>>().flatten() {
+            let zone = record.owner().to_name::<Bytes>();
+            if !name.ends_with(&zone) {
+                continue;
+            }
+            let soa = record.data();
+            let soa = Soa::new(
+                soa.mname().to_name(),
+                soa.rname().to_name(),
+                soa.serial(),
+                soa.refresh(),
+                soa.retry(),
+                soa.expire(),
+                soa.minimum(),
+            );
+            return Ok((zone, soa));
+        }
+    }
+
+    Err("no SOA record found".into())
+}
+
+/// Look up the IPv4 and IPv6 addresses of a host
+async fn lookup_addrs(
+    resolver: &impl StubResolve,
+    host: &ZoneName,
+    port: u16,
+) -> Result<Vec<SocketAddr>, Error> {
+    let mut addrs = Vec::new();
+
+    let response = query(resolver, host, Rtype::A).await?;
+    let answer = response
+        .answer()
+        .map_err(|e| format!("invalid response: {e}"))?;
+    for record in answer.limit_to::<A>().flatten() {
+        addrs.push(SocketAddr::new(record.data().addr().into(), port));
+    }
+
+    let response = query(resolver, host, Rtype::AAAA).await?;
+    let answer = response
+        .answer()
+        .map_err(|e| format!("invalid response: {e}"))?;
+    for record in answer.limit_to::<Aaaa>().flatten() {
+        addrs.push(SocketAddr::new(record.data().addr().into(), port));
+    }
+
+    if addrs.is_empty() {
+        return Err("no addresses found".into());
+    }
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    use domain::base::iana::{Class, Opcode, Rcode, Rtype};
+    use domain::base::{
+        Message, MessageBuilder, Name, Record, Serial, ToName, Ttl, UnknownRecordData,
+    };
+    use domain::rdata::{Soa, A};
+
+    use crate::env::fake::FakeCmd;
+
+    /// The records in the update section of received UPDATE messages
+    type Received = Arc<Mutex<Vec<(SocketAddr, Vec<String>)>>>;
+
+    /// A server for the zone example.test with primary ns1.example.test
+    fn server(
+        rcode: Rcode,
+        received: Received,
+    ) -> impl Fn(SocketAddr, &Message<[u8]>) -> Option<Message<Vec<u8>>> {
+        move |addr, msg| {
+            let zone = Name::<Vec<u8>>::from_str("example.test").unwrap();
+            let question = msg.sole_question().unwrap();
+
+            if msg.header().opcode() == Opcode::UPDATE {
+                let updates = msg
+                    .authority()
+                    .unwrap()
+                    .limit_to::<UnknownRecordData<_>>()
+                    .map(|r| {
+                        let r = r.unwrap();
+                        format!(
+                            "{} {} {} {}",
+                            r.owner(),
+                            r.class(),
+                            r.rtype(),
+                            r.ttl().as_secs()
+                        )
+                    })
+                    .collect();
+                received.lock().unwrap().push((addr, updates));
+                let builder = MessageBuilder::new_vec().start_answer(msg, rcode).unwrap();
+                return Some(builder.into_message());
+            }
+
+            let mut builder = MessageBuilder::new_vec()
+                .start_answer(msg, Rcode::NOERROR)
+                .unwrap();
+            match question.qtype() {
+                Rtype::SOA => {
+                    let soa = Soa::new(
+                        Name::<Vec<u8>>::from_str("ns1.example.test").unwrap(),
+                        Name::<Vec<u8>>::from_str("hostmaster.example.test").unwrap(),
+                        Serial(1),
+                        Ttl::from_secs(3600),
+                        Ttl::from_secs(3600),
+                        Ttl::from_secs(3600),
+                        Ttl::from_secs(3600),
+                    );
+                    let record = Record::new(&zone, Class::IN, Ttl::from_secs(3600), soa);
+                    if question.qname().name_eq(&zone) {
+                        builder.push(record).unwrap();
+                    } else {
+                        let mut builder = builder.authority();
+                        builder.push(record).unwrap();
+                        return Some(builder.into_message());
+                    }
+                }
+                Rtype::A => {
+                    let a = A::from_str("192.0.2.53").unwrap();
+                    builder
+                        .push((question.qname(), Ttl::from_secs(3600), a))
+                        .unwrap();
+                }
+                _ => {}
+            }
+            Some(builder.into_message())
+        }
+    }
+
+    #[test]
+    fn dnst_parse() {
+        let cmd = FakeCmd::new(["dnst", "update"]);
+
+        assert!(cmd.parse().is_ok());
+        assert!(cmd
+            .args(["-z", "example.test", "-s", "192.0.2.1"])
+            .args(["--add", "www 300 A 192.0.2.2", "--delete-rrset", "www AAAA"])
+            .parse()
+            .is_ok());
+        assert!(cmd.args(["www.example.test"]).parse().is_err());
+    }
+
+    #[test]
+    fn ldns_parse() {
+        let cmd = FakeCmd::new(["ldns-update"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["www.example.test"]).parse().is_err());
+        assert!(cmd.args(["www.example.test", "192.0.2.1"]).parse().is_ok());
+        assert!(cmd.args(["www.example.test", "none"]).parse().is_ok());
+        assert!(cmd
+            .args(["www.example.test", "example.test", "2001:db8::1"])
+            .parse()
+            .is_ok());
+        assert!(cmd
+            .args(["www.example.test", "192.0.2.1", "key", "hmac-sha256"])
+            .args(["bm90IGEga2V5"])
+            .parse()
+            .is_ok());
+        assert!(cmd
+            .args(["www.example.test", "192.0.2.1", "key", "hmac-md5"])
+            .args(["bm90IGEga2V5"])
+            .parse()
+            .is_err());
+        assert!(cmd.args(["www.example.test", "not-an-ip"]).parse().is_err());
+    }
+
+    #[test]
+    fn update() {
+        let received = Received::default();
+        let res = FakeCmd::new(["dnst", "update", "-z", "example.test", "-s", "192.0.2.1"])
+            .args(["--forbid-name", "new", "--add", "new 300 IN A 192.0.2.2"])
+            .args(["--delete", "old A 192.0.2.3", "--delete-name", "older"])
+            .server(server(Rcode::NOERROR, received.clone()))
+            .run();
+
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, ";; UPDATE response was NOERROR\n");
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].0, "192.0.2.1:53".parse().unwrap());
+        assert_eq!(
+            received[0].1,
+            [
+                "older.example.test * ANY 0",
+                "old.example.test NONE A 0",
+                "new.example.test IN A 300"
+            ]
+        );
+    }
+
+    #[test]
+    fn find_primary() {
+        let received = Received::default();
+        let res = FakeCmd::new(["ldns-update", "www.example.test", "192.0.2.2"])
+            .server(server(Rcode::NOERROR, received.clone()))
+            .run();
+
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].0, "192.0.2.53:53".parse().unwrap());
+        assert_eq!(
+            received[0].1,
+            ["www.example.test * A 0", "www.example.test IN A 3600"]
+        );
+    }
+
+    #[test]
+    fn ldns_zone() {
+        let received = Received::default();
+        let cmd = FakeCmd::new(["ldns-update", "www.example.test", "example.test"])
+            .server(server(Rcode::NOERROR, received.clone()));
+
+        let res = cmd.args(["192.0.2.2"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        let res = cmd.args(["none"]).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].0, "192.0.2.53:53".parse().unwrap());
+        assert_eq!(
+            received[0].1,
+            ["www.example.test * A 0", "www.example.test IN A 3600"]
+        );
+        assert_eq!(
+            received[1].1,
+            ["www.example.test * A 0", "www.example.test * AAAA 0"]
+        );
+    }
+
+    #[test]
+    fn refused() {
+        let res = FakeCmd::new(["dnst", "update", "-z", "example.test"])
+            .args(["--add", "www A 192.0.2.2"])
+            .server(server(Rcode::REFUSED, Default::default()))
+            .run();
+
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("server responded with REFUSED"));
+        assert!(res
+            .stderr
+            .contains("while updating zone example.test. at 192.0.2.53:53"));
+
+        // Names outside of the zone are rejected before sending anything.
+        let res = FakeCmd::new(["dnst", "update", "-z", "example.test"])
+            .args(["--add", "www.other.test. A 192.0.2.2"])
+            .server(server(Rcode::NOERROR, Default::default()))
+            .run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("www.other.test. is not in the zone"));
+    }
+}
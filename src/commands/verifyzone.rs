@@ -0,0 +1,886 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bytes::Bytes;
+use clap::builder::ValueParser;
+use domain::base::iana::Rtype;
+use domain::base::ToName;
+use domain::rdata::dnssec::Timestamp;
+use domain::rdata::{Dnskey, Rrsig, ZoneRecordData};
+use domain::utils::base32;
+use domain::validate::{AlgorithmError, RrsigExt};
+use lexopt::Arg;
+
+use crate::args::OutputFormat;
+use crate::env::Env;
+use crate::error::{Context, Error, Warning};
+use crate::zone::{group_nodes, is_in_zone, read_zone_file, sort_records};
+use crate::zone::{Node, NodeKind, ZoneName, ZoneRecord};
+
+use super::nsec3hash::{audit_nsec3_params, Nsec3Digest};
+use super::{parse_os, parse_os_with, LdnsCommand};
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct VerifyZone {
+    /// Signatures may not expire within this period
+    #[arg(
+        short = 'e',
+        long = "expire-window",
+        value_name = "PERIOD",
+        value_parser = ValueParser::new(VerifyZone::parse_period)
+    )]
+    expire_window: Option<u32>,
+
+    /// Signatures must have been valid for at least this period
+    #[arg(
+        short = 'i',
+        long = "inception-window",
+        value_name = "PERIOD",
+        value_parser = ValueParser::new(VerifyZone::parse_period)
+    )]
+    inception_window: Option<u32>,
+
+    /// The time to verify the signatures at, as `YYYYMMDDhhmmss` or `[+|-]SECONDS` from now [default: now]
+    #[arg(
+        short = 't',
+        long = "time",
+        value_name = "TIME",
+        value_parser = ValueParser::new(ValidationTime::from_str),
+        allow_hyphen_values = true
+    )]
+    time: Option<ValidationTime>,
+
+    /// The signed zone file to verify
+    #[arg(value_name = "ZONEFILE")]
+    zonefile: PathBuf,
+
+    /// Whether to check the NSEC3 parameters against RFC 9276, which ldns
+    /// does not
+    #[arg(skip = true)]
+    lint: bool,
+}
+
+/// The time at which signatures are checked
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValidationTime {
+    /// An absolute point in time
+    Absolute(Timestamp),
+
+    /// An offset in seconds from now
+    Offset(i64),
+}
+
+const LDNS_HELP: &str = "\
+Usage: ldns-verify-zone [OPTIONS] <zonefile>
+\tReads the zonefile and checks for DNSSEC errors.
+
+It checks whether NSEC(3)s are present, and verifies all signatures
+It also checks the NSEC(3) chain, but it will error on opted-out delegations
+
+OPTIONS:
+\t-h\t\tshow this text
+\t-e <period>\tsignatures may not expire within this period.
+\t\t\t(default no period is used)
+\t-i <period>\tsignatures must have been valid at least this long.
+\t\t\t(default signatures should just be valid now)
+\t-t YYYYMMDDhhmmss | [+|-]offset
+\t\t\tset the validation time either by an absolute time
+\t\t\tvalue or as an offset in seconds from <now>.
+
+<period>s are given in ISO 8601 duration format: P[n]Y[n]M[n]DT[n]H[n]M[n]S\
+";
+
+impl LdnsCommand for VerifyZone {
+    const HELP: &'static str = LDNS_HELP;
+
+    fn parse_ldns<I: IntoIterator<Item = OsString>>(args: I) -> Result<Self, Error> {
+        let mut expire_window = None;
+        let mut inception_window = None;
+        let mut time = None;
+        let mut zonefile = None;
+
+        let mut parser = lexopt::Parser::from_args(args);
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Arg::Short('e') => {
+                    let val = parser.value()?;
+                    expire_window = Some(parse_os_with(
+                        "period (-e)",
+                        &val,
+                        VerifyZone::parse_period,
+                    )?);
+                }
+                Arg::Short('i') => {
+                    let val = parser.value()?;
+                    inception_window = Some(parse_os_with(
+                        "period (-i)",
+                        &val,
+                        VerifyZone::parse_period,
+                    )?);
+                }
+                Arg::Short('t') => {
+                    let val = parser.value()?;
+                    time = Some(parse_os("time (-t)", &val)?);
+                }
+                Arg::Value(val) => {
+                    if zonefile.is_some() {
+                        return Err("Only one zone file can be given".into());
+                    }
+                    zonefile = Some(parse_os("zone file", &val)?);
+                }
+                Arg::Short(x) => return Err(format!("Invalid short option: -{x}").into()),
+                Arg::Long(x) => {
+                    return Err(format!("Long options are not supported, but `--{x}` given").into())
+                }
+            }
+        }
+
+        let Some(zonefile) = zonefile else {
+            return Err("Missing zone file argument".into());
+        };
+
+        Ok(Self {
+            expire_window,
+            inception_window,
+            time,
+            zonefile,
+            lint: false,
+        })
+    }
+}
+
+impl VerifyZone {
+    /// Parse an ISO 8601 duration like `P1DT12H` or a number of seconds
+    ///
+    /// Like ldns, a month counts as 31 days and a year as 365 days.
+    pub fn parse_period(arg: &str) -> Result<u32, &'static str> {
+        let invalid = "expected an ISO 8601 duration (P[n]Y[n]M[n]DT[n]H[n]M[n]S) or seconds";
+
+        let Some(period) = arg.strip_prefix('P') else {
+            return arg.parse().map_err(|_| invalid);
+        };
+
+        let mut seconds: u32 = 0;
+        let mut number = String::new();
+        let mut in_time = false;
+        for c in period.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            if c == 'T' && !in_time && number.is_empty() {
+                in_time = true;
+                continue;
+            }
+            let factor = match (c, in_time) {
+                ('Y', false) => 365 * 24 * 60 * 60,
+                ('M', false) => 31 * 24 * 60 * 60,
+                ('W', false) => 7 * 24 * 60 * 60,
+                ('D', false) => 24 * 60 * 60,
+                ('H', true) => 60 * 60,
+                ('M', true) => 60,
+                ('S', true) => 1,
+                _ => return Err(invalid),
+            };
+            let n: u32 = number.parse().map_err(|_| invalid)?;
+            seconds = n
+                .checked_mul(factor)
+                .and_then(|n| seconds.checked_add(n))
+                .ok_or("period is too long")?;
+            number.clear();
+        }
+
+        if !number.is_empty() || period.is_empty() {
+            return Err(invalid);
+        }
+        Ok(seconds)
+    }
+}
+
+impl FromStr for ValidationTime {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = "expected YYYYMMDDhhmmss or [+|-]<seconds>";
+        if let Some(offset) = s.strip_prefix('+') {
+            offset.parse().map(Self::Offset).map_err(|_| invalid)
+        } else if s.starts_with('-') {
+            s.parse().map(Self::Offset).map_err(|_| invalid)
+        } else if s.len() == 14 {
+            Timestamp::from_str(s)
+                .map(Self::Absolute)
+                .map_err(|_| invalid)
+        } else {
+            Err(invalid)
+        }
+    }
+}
+
+impl ValidationTime {
+    fn timestamp(self) -> Timestamp {
+        match self {
+            Self::Absolute(time) => time,
+            Self::Offset(offset) => {
+                let now = i64::from(Timestamp::now().into_int());
+                Timestamp::from((now + offset) as u32)
+            }
+        }
+    }
+}
+
+impl VerifyZone {
+    pub fn execute(self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        self.execute_inner(env, format)
+            .with_context(|| format!("verifying {}", self.zonefile.display()))
+    }
+
+    fn execute_inner(&self, env: impl Env, format: OutputFormat) -> Result<(), Error> {
+        let mut records = read_zone_file(&env, &self.zonefile, None)?;
+
+        let apex = records
+            .iter()
+            .find(|r| r.rtype() == Rtype::SOA)
+            .map(|r| r.owner().clone())
+            .ok_or("zone has no SOA record")?;
+
+        let mut problems = Vec::new();
+        records.retain(|r| {
+            let in_zone = is_in_zone(r.owner(), &apex);
+            if !in_zone {
+                problems.push(format!(
+                    "{} {}: not in the zone {}",
+                    r.owner().fmt_with_dot(),
+                    r.rtype(),
+                    apex.fmt_with_dot()
+                ));
+            }
+            in_zone
+        });
+
+        sort_records(&mut records);
+        let nodes = group_nodes(records, &apex);
+
+        let dnskeys: Vec<_> = nodes
+            .iter()
+            .find(|n| n.kind == NodeKind::Apex)
+            .and_then(|n| n.rrset(Rtype::DNSKEY))
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|r| match r.data() {
+                ZoneRecordData::Dnskey(dnskey) => Some(dnskey),
+                _ => None,
+            })
+            .collect();
+        if dnskeys.is_empty() {
+            return Err("zone has no DNSKEY records at the apex".into());
+        }
+
+        let checker = Checker {
+            apex: &apex,
+            dnskeys,
+            now: self
+                .time
+                .map_or_else(Timestamp::now, ValidationTime::timestamp),
+            expire_window: self.expire_window.unwrap_or(0),
+            inception_window: self.inception_window.unwrap_or(0),
+        };
+
+        let mut warnings = Vec::new();
+        checker.check_signatures(&nodes, &mut problems);
+        if nodes.iter().any(|n| n.has_rtype(Rtype::NSEC3)) {
+            checker.check_nsec3_chain(&nodes, &mut problems, &mut warnings);
+        } else if nodes.iter().any(|n| n.has_rtype(Rtype::NSEC)) {
+            checker.check_nsec_chain(&nodes, &mut problems);
+        } else {
+            problems.push("zone has no NSEC or NSEC3 records".into());
+        }
+
+        if self.lint {
+            for warning in warnings {
+                warning.pretty_print(&env, format);
+            }
+        }
+
+        let mut out = env.stdout();
+        match format {
+            OutputFormat::Text => {
+                for problem in &problems {
+                    writeln!(out, "{problem}");
+                }
+            }
+            OutputFormat::Json => {
+                let json = serde_json::json!({
+                    "verified": problems.is_empty(),
+                    "problems": problems,
+                });
+                writeln!(out, "{json}");
+            }
+        }
+
+        if !problems.is_empty() {
+            return Err(format!(
+                "zone verification failed with {} problem(s)",
+                problems.len()
+            )
+            .into());
+        }
+
+        if format == OutputFormat::Text {
+            writeln!(out, "Zone is verified and complete");
+        }
+        Ok(())
+    }
+}
+
+/// The context for checking the DNSSEC records of a zone
+struct Checker<'a> {
+    apex: &'a ZoneName,
+    dnskeys: Vec<&'a Dnskey<Bytes>>,
+    now: Timestamp,
+    expire_window: u32,
+    inception_window: u32,
+}
+
+impl Checker<'_> {
+    /// Check that every authoritative RRset has valid signatures
+    ///
+    /// Signatures for types that have no records at their owner are a
+    /// problem too.
+    fn check_signatures(&self, nodes: &[Node], problems: &mut Vec<String>) {
+        for node in nodes {
+            let rrsigs: Vec<_> = node
+                .rrset(Rtype::RRSIG)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|r| match r.data() {
+                    ZoneRecordData::Rrsig(rrsig) => Some(rrsig),
+                    _ => None,
+                })
+                .collect();
+
+            for rrset in &node.rrsets {
+                let rtype = rrset[0].rtype();
+                let signed = match node.kind {
+                    NodeKind::Apex | NodeKind::Authoritative => rtype != Rtype::RRSIG,
+                    NodeKind::Delegation => matches!(rtype, Rtype::DS | Rtype::NSEC),
+                    NodeKind::Occluded => false,
+                };
+                if !signed {
+                    continue;
+                }
+
+                let owner = node.owner.fmt_with_dot();
+                let sigs: Vec<_> = rrsigs
+                    .iter()
+                    .filter(|rrsig| rrsig.type_covered() == rtype)
+                    .collect();
+                if sigs.is_empty() {
+                    problems.push(format!("{owner} {rtype}: no signatures"));
+                }
+
+                for rrsig in sigs {
+                    if let Err(e) = self.check_rrsig(rrset, rrsig) {
+                        problems.push(format!(
+                            "{owner} {rtype}: signature with key tag {} {e}",
+                            rrsig.key_tag()
+                        ));
+                    }
+                }
+            }
+
+            if node.kind == NodeKind::Occluded {
+                continue;
+            }
+            for rrsig in &rrsigs {
+                let rtype = rrsig.type_covered();
+                if node.rrset(rtype).is_none() {
+                    problems.push(format!(
+                        "{} {rtype}: signature with key tag {} for a type without records",
+                        node.owner.fmt_with_dot(),
+                        rrsig.key_tag()
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Check a single signature of an RRset
+    fn check_rrsig(
+        &self,
+        rrset: &[ZoneRecord],
+        rrsig: &Rrsig<Bytes, ZoneName>,
+    ) -> Result<(), String> {
+        if !rrsig.signer_name().name_eq(self.apex) {
+            return Err(format!(
+                "has signer name {} instead of {}",
+                rrsig.signer_name().fmt_with_dot(),
+                self.apex.fmt_with_dot()
+            ));
+        }
+
+        if rrsig.inception() > self.now {
+            return Err("is not yet valid".into());
+        }
+        if rrsig.expiration() < self.now {
+            return Err("has expired".into());
+        }
+        let earliest = Timestamp::from(self.now.into_int().wrapping_sub(self.inception_window));
+        if rrsig.inception() > earliest {
+            return Err("has not been valid long enough".into());
+        }
+        let latest = Timestamp::from(self.now.into_int().wrapping_add(self.expire_window));
+        if rrsig.expiration() < latest {
+            return Err("expires within the expiration window".into());
+        }
+
+        let mut records: Vec<_> = rrset.iter().collect();
+        let mut signed_data = Vec::new();
+        rrsig
+            .signed_data(&mut signed_data, &mut records)
+            .expect("infallible");
+
+        let mut result = Err("has no matching DNSKEY".to_string());
+        let keys = self
+            .dnskeys
+            .iter()
+            .filter(|k| k.algorithm() == rrsig.algorithm() && k.key_tag() == rrsig.key_tag());
+        for key in keys {
+            result = match rrsig.verify_signed_data(key, &signed_data) {
+                Ok(()) => return Ok(()),
+                Err(AlgorithmError::Unsupported) => {
+                    Err(format!("uses unsupported algorithm {}", rrsig.algorithm()))
+                }
+                Err(_) => Err("is invalid".into()),
+            };
+        }
+        result
+    }
+
+    /// Check that the NSEC chain covers all names in the zone in order
+    fn check_nsec_chain(&self, nodes: &[Node], problems: &mut Vec<String>) {
+        let nodes: Vec<_> = nodes
+            .iter()
+            .filter(|n| n.kind != NodeKind::Occluded)
+            .collect();
+
+        for (i, node) in nodes.iter().enumerate() {
+            let owner = node.owner.fmt_with_dot();
+            let Some([nsec]) = node.rrset(Rtype::NSEC) else {
+                problems.push(format!("{owner}: expected exactly one NSEC record"));
+                continue;
+            };
+            let ZoneRecordData::Nsec(nsec) = nsec.data() else {
+                continue;
+            };
+
+            let next = &nodes[(i + 1) % nodes.len()].owner;
+            if !nsec.next_name().name_eq(next) {
+                problems.push(format!(
+                    "{owner} NSEC: next name is {} instead of {}",
+                    nsec.next_name().fmt_with_dot(),
+                    next.fmt_with_dot()
+                ));
+            }
+
+            let types: BTreeSet<_> = nsec.types().iter().collect();
+            if types != node.rtypes().collect() {
+                problems.push(format!(
+                    "{owner} NSEC: type bitmap does not match the types at the name"
+                ));
+            }
+        }
+    }
+
+    /// Check that the NSEC3 chain covers all names in the zone in order
+    ///
+    /// The NSEC3 parameters are also checked against RFC 9276, which only
+    /// gives warnings.
+    fn check_nsec3_chain(
+        &self,
+        nodes: &[Node],
+        problems: &mut Vec<String>,
+        warnings: &mut Vec<Warning>,
+    ) {
+        // The NSEC3 records by the first label of their owner name
+        let mut nsec3s = BTreeMap::new();
+        let mut names = Vec::new();
+        for node in nodes {
+            match node.rrset(Rtype::NSEC3) {
+                Some([record]) => match record.data() {
+                    ZoneRecordData::Nsec3(nsec3) => {
+                        let label = node.owner.first().to_string().to_lowercase();
+                        nsec3s.insert(label, (node, nsec3));
+                    }
+                    _ => unreachable!(),
+                },
+                Some(_) => problems.push(format!(
+                    "{}: expected exactly one NSEC3 record",
+                    node.owner.fmt_with_dot()
+                )),
+                None if node.kind != NodeKind::Occluded => names.push(node),
+                None => {}
+            }
+        }
+
+        let apex_param = nodes
+            .iter()
+            .find(|n| n.kind == NodeKind::Apex)
+            .and_then(|n| n.rrset(Rtype::NSEC3PARAM))
+            .and_then(|rrset| match rrset[0].data() {
+                ZoneRecordData::Nsec3param(param) => Some(param),
+                _ => None,
+            });
+        let (algorithm, iterations, salt) = match apex_param {
+            Some(param) => (param.hash_algorithm(), param.iterations(), param.salt()),
+            None => {
+                problems.push(format!(
+                    "{}: no NSEC3PARAM record",
+                    self.apex.fmt_with_dot()
+                ));
+                let Some((_, nsec3)) = nsec3s.values().next() else {
+                    problems.push("zone has no usable NSEC3 records".into());
+                    return;
+                };
+                (nsec3.hash_algorithm(), nsec3.iterations(), nsec3.salt())
+            }
+        };
+        let digest = match Nsec3Digest::new(algorithm) {
+            Ok(digest) => digest,
+            Err(err) => {
+                problems.push(err.to_string());
+                return;
+            }
+        };
+        warnings.extend(audit_nsec3_params(iterations, salt));
+
+        let opt_out = nsec3s.values().any(|(_, nsec3)| nsec3.opt_out());
+
+        // With opt-out, insecure delegations and the empty non-terminals
+        // that only lead to them may be left out of the chain.
+        let mut expected = BTreeMap::new();
+        for node in &names {
+            let optional =
+                opt_out && node.kind == NodeKind::Delegation && !node.has_rtype(Rtype::DS);
+            let types: BTreeSet<_> = node.rtypes().collect();
+            expected.insert(node.owner.clone(), (types, optional));
+        }
+        let mut empty_non_terminals = BTreeMap::new();
+        for (name, (_, optional)) in &expected {
+            let mut parent = name.parent();
+            while let Some(name) = parent {
+                if !is_in_zone(&name, self.apex) || expected.contains_key(&name) {
+                    break;
+                }
+                parent = name.parent();
+                let entry = empty_non_terminals.entry(name).or_insert(true);
+                *entry &= *optional;
+            }
+        }
+        expected.extend(
+            empty_non_terminals
+                .into_iter()
+                .map(|(name, optional)| (name, (BTreeSet::new(), optional))),
+        );
+
+        let mut matched = BTreeSet::new();
+        for (name, (types, optional)) in &expected {
+            let hash = digest.hash(name, iterations, salt);
+            let label = base32::encode_string_hex(hash.as_slice()).to_lowercase();
+            let Some((node, nsec3)) = nsec3s.get(&label) else {
+                if !optional {
+                    problems.push(format!(
+                        "{}: no NSEC3 record for hash {label}",
+                        name.fmt_with_dot()
+                    ));
+                }
+                continue;
+            };
+            matched.insert(label);
+
+            let nsec3_types: BTreeSet<_> = nsec3.types().iter().collect();
+            if &nsec3_types != types {
+                problems.push(format!(
+                    "{} NSEC3: type bitmap does not match the types at {}",
+                    node.owner.fmt_with_dot(),
+                    name.fmt_with_dot()
+                ));
+            }
+        }
+
+        let labels: Vec<_> = nsec3s.keys().collect();
+        for (i, (label, (node, nsec3))) in nsec3s.iter().enumerate() {
+            let owner = node.owner.fmt_with_dot();
+            if !matched.contains(label) {
+                problems.push(format!(
+                    "{owner} NSEC3: does not match any name in the zone"
+                ));
+            }
+
+            if nsec3.hash_algorithm() != algorithm
+                || nsec3.iterations() != iterations
+                || nsec3.salt().as_slice() != salt.as_slice()
+            {
+                problems.push(format!(
+                    "{owner} NSEC3: parameters differ from the NSEC3PARAM record"
+                ));
+            }
+
+            let next = labels[(i + 1) % labels.len()];
+            let next_owner =
+                base32::encode_string_hex(nsec3.next_owner().as_slice()).to_lowercase();
+            if &next_owner != next {
+                problems.push(format!(
+                    "{owner} NSEC3: next hash is {next_owner} instead of {next}"
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use domain::base::Name;
+
+    use super::VerifyZone;
+    use crate::env::fake::FakeCmd;
+    use crate::keys::{KeyAlgorithm, KeyPair, KSK_FLAGS, ZSK_FLAGS};
+
+    const ZONE: &str = "\
+$ORIGIN example.test.
+$TTL 3600
+@       IN SOA ns1 hostmaster 2024010101 7200 3600 1209600 300
+@       IN NS  ns1
+ns1     IN A   192.0.2.1
+www     IN A   192.0.2.2
+*.wild  IN TXT \"wildcard\"
+sub     IN NS  ns.sub
+ns.sub  IN A   192.0.2.3
+a.b.c   IN A   192.0.2.4
+";
+
+    /// Sign the test zone with `dnst signzone` and the given options
+    fn sign(options: &[&str]) -> String {
+        let cmd = FakeCmd::new(["dnst", "signzone", "-f", "-"]).file("zone", ZONE);
+
+        let owner = Name::from_str("example.test").unwrap();
+        let mut keys = Vec::new();
+        for flags in [KSK_FLAGS, ZSK_FLAGS] {
+            let key = KeyPair::generate(owner.clone(), KeyAlgorithm::Ed25519, flags, 0).unwrap();
+            let base = key.base_name();
+            cmd.file(format!("{base}.key"), key.display_public().to_string());
+            cmd.file(format!("{base}.private"), key.display_private().to_string());
+            keys.push(base);
+        }
+
+        let res = cmd.args(options).args(["zone"]).args(&keys).run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        res.stdout
+    }
+
+    /// Apply `f` to the lines of a zone file
+    fn edit(zone: &str, f: impl FnOnce(&mut Vec<String>)) -> String {
+        let mut lines: Vec<_> = zone.lines().map(String::from).collect();
+        f(&mut lines);
+        lines.join("\n") + "\n"
+    }
+
+    #[test]
+    fn dnst_parse() {
+        let cmd = FakeCmd::new(["dnst", "verify-zone"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd.args(["zonefile"]).parse().is_ok());
+        assert!(cmd.args(["-e", "P1DT12H", "zonefile"]).parse().is_ok());
+        assert!(cmd.args(["-e", "1D", "zonefile"]).parse().is_err());
+        assert!(cmd.args(["-t", "-3600", "zonefile"]).parse().is_ok());
+        assert!(cmd.args(["-t", "20240101", "zonefile"]).parse().is_err());
+    }
+
+    #[test]
+    fn ldns_parse() {
+        let cmd = FakeCmd::new(["ldns-verify-zone"]);
+
+        assert!(cmd.parse().is_err());
+        assert!(cmd
+            .args([
+                "-e",
+                "P2W",
+                "-i",
+                "PT1H",
+                "-t",
+                "20240101120000",
+                "zonefile"
+            ])
+            .parse()
+            .is_ok());
+        assert!(cmd.args(["-x", "zonefile"]).parse().is_err());
+    }
+
+    #[test]
+    fn period() {
+        assert_eq!(VerifyZone::parse_period("3600"), Ok(3600));
+        assert_eq!(VerifyZone::parse_period("PT1H30M"), Ok(5400));
+        assert_eq!(VerifyZone::parse_period("P1DT1S"), Ok(86401));
+        assert_eq!(VerifyZone::parse_period("P1M"), Ok(31 * 86400));
+        assert!(VerifyZone::parse_period("P").is_err());
+        assert!(VerifyZone::parse_period("P1H").is_err());
+        assert!(VerifyZone::parse_period("PT5").is_err());
+    }
+
+    #[test]
+    fn verify_nsec() {
+        let signed = sign(&[]);
+
+        let cmd = FakeCmd::new(["dnst", "verify-zone", "zone.signed"]).file("zone.signed", &signed);
+        let res = cmd.run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, "Zone is verified and complete\n");
+
+        // Signatures are valid for four weeks.
+        let res = cmd.args(["-e", "P30D"]).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res
+            .stdout
+            .contains("www.example.test. A: signature with key tag "));
+        assert!(res
+            .stdout
+            .contains(" expires within the expiration window\n"));
+
+        let res = cmd.args(["-t", "+3000000"]).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stdout.contains(" has expired\n"));
+
+        let signed = edit(&signed, |lines| {
+            for line in lines.iter_mut() {
+                *line = line.replace("192.0.2.2", "192.0.2.22");
+            }
+            lines.retain(|l| {
+                !l.starts_with("ns1.example.test.\t300\tIN\tNSEC\t")
+                    && !l.starts_with("a.b.c.example.test.")
+            });
+        });
+        let res = cmd.file("zone.signed", signed).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res
+            .stderr
+            .contains("zone verification failed with 4 problem(s)"));
+        assert!(res
+            .stdout
+            .contains("www.example.test. A: signature with key tag "));
+        assert!(res.stdout.contains(" is invalid\n"));
+        assert!(res
+            .stdout
+            .contains("ns1.example.test.: expected exactly one NSEC record\n"));
+        // The signature of the removed NSEC record is left behind.
+        assert!(res
+            .stdout
+            .contains("ns1.example.test. NSEC: signature with key tag "));
+        assert!(res.stdout.contains(
+            "example.test. NSEC: next name is a.b.c.example.test. instead of ns1.example.test.\n"
+        ));
+    }
+
+    #[test]
+    fn orphan_signature() {
+        let signed = sign(&[]);
+
+        // Remove the A record of www.example.test., but not its signature.
+        let signed = edit(&signed, |lines| {
+            lines.retain(|l| !l.starts_with("www.example.test.\t3600\tIN\tA\t"));
+        });
+        let res = FakeCmd::new(["dnst", "verify-zone", "zone.signed"])
+            .file("zone.signed", &signed)
+            .run();
+        assert_eq!(res.exit_code, 1);
+        let orphan = res
+            .stdout
+            .lines()
+            .find(|l| l.ends_with(" for a type without records"));
+        assert!(
+            orphan.is_some_and(|l| l.starts_with("www.example.test. A: signature with key tag ")),
+            "{}",
+            res.stdout
+        );
+    }
+
+    #[test]
+    fn verify_nsec3() {
+        let signed = sign(&["-n", "-s", "cafe", "-p"]);
+
+        let cmd = FakeCmd::new(["ldns-verify-zone", "zone.signed"]).file("zone.signed", &signed);
+        let res = cmd.run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, "Zone is verified and complete\n");
+
+        // Remove the NSEC3 record of the apex.
+        let signed = edit(&signed, |lines| {
+            let apex = lines
+                .iter()
+                .position(|l| l.contains("\tNSEC3\t") && l.contains("NSEC3PARAM"))
+                .unwrap();
+            lines.remove(apex);
+        });
+        let res = cmd.file("zone.signed", signed).run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res
+            .stdout
+            .contains("example.test.: no NSEC3 record for hash "));
+        assert!(res.stdout.contains(" NSEC3: next hash is "));
+    }
+
+    #[test]
+    fn duplicate_nsec3() {
+        let signed = sign(&["-n", "-p"]);
+
+        // Only names with two NSEC3 records and no NSEC3PARAM record.
+        let signed = edit(&signed, |lines| {
+            lines.retain(|l| !l.contains("NSEC3PARAM"));
+            let nsec3s: Vec<_> = lines
+                .iter()
+                .filter(|l| l.contains("\tNSEC3\t"))
+                .cloned()
+                .collect();
+            for nsec3 in nsec3s {
+                lines.push(nsec3.replace("\tNSEC3\t1 1 0 - ", "\tNSEC3\t1 1 1 - "));
+            }
+        });
+        let res = FakeCmd::new(["dnst", "verify-zone", "zone.signed"])
+            .file("zone.signed", &signed)
+            .run();
+        assert_eq!(res.exit_code, 1);
+        assert!(res
+            .stdout
+            .contains("example.test.: no NSEC3PARAM record\nzone has no usable NSEC3 records\n"));
+        assert!(res.stdout.contains(": expected exactly one NSEC3 record\n"));
+    }
+
+    #[test]
+    fn lint_nsec3() {
+        let signed = sign(&["-n", "-s", "cafe", "-t", "10"]);
+
+        // The parameters are only warned about.
+        let res = FakeCmd::new(["dnst", "verify-zone", "zone.signed"])
+            .file("zone.signed", &signed)
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, "Zone is verified and complete\n");
+        assert!(res
+            .stderr
+            .contains("WARNING: using 10 NSEC3 iterations, but RFC 9276 recommends 0\n"));
+        assert!(res
+            .stderr
+            .contains("WARNING: using the NSEC3 salt cafe, but RFC 9276 recommends no salt\n"));
+
+        // ldns does not check the parameters.
+        let res = FakeCmd::new(["ldns-verify-zone", "zone.signed"])
+            .file("zone.signed", &signed)
+            .run();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stderr, "");
+    }
+}
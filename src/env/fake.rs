@@ -1,12 +1,34 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::ffi::OsString;
 use std::fmt;
+use std::future::{ready, Future, Ready};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::task::{Context, Poll};
 
-use crate::{error::Error, parse_args, run, Args};
+use bytes::Bytes;
+use domain::base::iana::Rtype;
+use domain::base::{Message, MessageBuilder, Name, ToName};
+use domain::net::client::dgram;
+use domain::net::client::protocol::{AsyncConnect, AsyncDgramRecv, AsyncDgramSend};
+use domain::net::client::request::{RequestMessage, SendRequest};
+use domain::resolv::stub::conf::ResolvConf;
+use domain::stelline::client::CurrStepValue;
+use domain::stelline::connection::Connection as StellineStream;
+use domain::stelline::dgram::{Dgram as StellineDgram, DgramConnection as StellineSocket};
+use domain::stelline::parse_stelline::{parse_file, Stelline};
+use tokio::io::ReadBuf;
+
+use crate::keys::{KeyAlgorithm, KeyPair};
+use crate::{error::Error, parse_args, run, run_ldns, Args};
 
-use super::Env;
 use super::Stream;
+use super::{DgramConnect, Env, StreamConnect, StubResolve};
 
 /// A command to run in a [`FakeEnv`]
 ///
@@ -16,8 +38,27 @@ use super::Stream;
 pub struct FakeCmd {
     /// The command to run, including `argv[0]`
     cmd: Vec<OsString>,
+
+    /// The server answering requests sent over the network
+    server: Option<FakeServer>,
+
+    /// The Stelline script answering requests sent over the network
+    stelline: Option<Stelline>,
+
+    /// The contents of stdin
+    stdin: Arc<[u8]>,
+
+    /// The file system, shared with all clones of this command
+    fs: FakeFs,
 }
 
+/// A function acting as the DNS server(s) on the fake network
+///
+/// It is called with the address a request was sent to and the request
+/// itself and returns the response, if any.
+pub type FakeServer =
+    Arc<dyn Fn(SocketAddr, &Message<[u8]>) -> Option<Message<Vec<u8>>> + Send + Sync>;
+
 /// The result of running a [`FakeCmd`]
 ///
 /// The fields are public to allow for easy assertions in tests.
@@ -37,11 +78,66 @@ pub struct FakeEnv {
 
     /// The mocked stderr
     pub stderr: FakeStream,
-    // pub stelline: Option<Stelline>,
-    // pub curr_step_value: Option<Arc<CurrStepValue>>,
+
+    /// The step of the Stelline script, which selects the ranges that apply
+    pub curr_step_value: Arc<CurrStepValue>,
 }
 
 impl Env for FakeEnv {
+    fn make_connection(&self, addr: SocketAddr) -> impl DgramConnect {
+        FakeConnect {
+            addr,
+            network: self.network(),
+        }
+    }
+
+    fn make_stream_connection(&self, _addr: SocketAddr) -> impl StreamConnect {
+        FakeStreamConnect {
+            network: self.network(),
+        }
+    }
+
+    fn make_stub_resolver(&self) -> impl StubResolve {
+        // Like the real resolver, fall back to 127.0.0.1 if there is no
+        // configuration.
+        let mut conf = ResolvConf::new();
+        if let Ok(contents) = self.cmd.fs.read(Path::new("/etc/resolv.conf")) {
+            conf.parse(&mut &contents[..])
+                .expect("fake /etc/resolv.conf is valid");
+        }
+        conf.finalize();
+
+        FakeResolver {
+            servers: conf.servers.iter().map(|s| s.addr).collect(),
+            network: self.network(),
+        }
+    }
+
+    fn lookup_host(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        // Only the fake /etc/hosts is used, there is no fake DNS lookup.
+        let hosts = self
+            .read_to_string(Path::new("/etc/hosts"))
+            .unwrap_or_default();
+        let addrs: Vec<_> = hosts
+            .lines()
+            .filter_map(|line| {
+                let line = line.split('#').next().unwrap_or_default();
+                let mut fields = line.split_whitespace();
+                let addr: IpAddr = fields.next()?.parse().ok()?;
+                fields
+                    .any(|name| name.eq_ignore_ascii_case(host))
+                    .then_some(SocketAddr::new(addr, port))
+            })
+            .collect();
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "failed to lookup address information",
+            ));
+        }
+        Ok(addrs)
+    }
+
     fn args_os(&self) -> impl Iterator<Item = OsString> {
         self.cmd.cmd.iter().map(Into::into)
     }
@@ -53,6 +149,56 @@ impl Env for FakeEnv {
     fn stderr(&self) -> Stream<impl fmt::Write> {
         Stream(self.stderr.clone())
     }
+
+    fn stdout_is_terminal(&self) -> bool {
+        false
+    }
+
+    fn stdin(&self) -> impl io::Read {
+        io::Cursor::new(self.cmd.stdin.clone())
+    }
+
+    fn open(&self, path: &Path) -> io::Result<impl io::Read> {
+        self.cmd.fs.read(path).map(io::Cursor::new)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.cmd.fs.read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.cmd.fs.write(path, contents, false)
+    }
+
+    fn create_new(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.cmd.fs.write(path, contents, true)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.cmd.fs.remove(path)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        self.cmd.fs.symlink(target, link)
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> io::Result<()> {
+        self.cmd.fs.hard_link(original, link)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.cmd.fs.exists(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.cmd.fs.read_dir(path)
+    }
+
+    fn current_exe(&self) -> io::Result<PathBuf> {
+        // The program name is the path of the executable.
+        Ok(self.cmd.cmd[0].clone().into())
+    }
 }
 
 impl FakeCmd {
@@ -62,6 +208,10 @@ impl FakeCmd {
     pub fn new<S: Into<OsString>>(cmd: impl IntoIterator<Item = S>) -> Self {
         Self {
             cmd: cmd.into_iter().map(Into::into).collect(),
+            server: None,
+            stelline: None,
+            stdin: Arc::new([]),
+            fs: FakeFs::default(),
         }
     }
 
@@ -79,25 +229,102 @@ impl FakeCmd {
         new
     }
 
-    /// Parse the arguments of this [`FakeCmd`] and return the result
-    pub fn parse(&self) -> Result<Args, Error> {
-        let env = FakeEnv {
+    /// Answer network requests of a clone of the [`FakeCmd`] with `server`
+    ///
+    /// Without a server, all network requests fail.
+    pub fn server(
+        &self,
+        server: impl Fn(SocketAddr, &Message<[u8]>) -> Option<Message<Vec<u8>>> + Send + Sync + 'static,
+    ) -> Self {
+        let mut new = self.clone();
+        new.server = Some(Arc::new(server));
+        new
+    }
+
+    /// Answer network requests of a clone of the [`FakeCmd`] with a Stelline script
+    ///
+    /// Only the ranges of the script are used: the requests are answered
+    /// with the last matching entry of the ranges that include step 0. A
+    /// server set with [`FakeCmd::server`] takes precedence over the script.
+    pub fn stelline(&self, name: &str, script: &str) -> Self {
+        let mut new = self.clone();
+        new.stelline = Some(parse_file(script.as_bytes(), name));
+        new
+    }
+
+    /// Give a clone of the [`FakeCmd`] `input` as stdin
+    pub fn stdin(&self, input: impl AsRef<[u8]>) -> Self {
+        let mut new = self.clone();
+        new.stdin = input.as_ref().into();
+        new
+    }
+
+    /// Write a file to the fake file system and return a clone of the [`FakeCmd`]
+    ///
+    /// The file system is shared between a [`FakeCmd`] and its clones, so
+    /// files written by one run are visible to later runs.
+    pub fn file(&self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Self {
+        self.fs
+            .write(path.as_ref(), contents.as_ref(), false)
+            .expect("fake file system is writable");
+        self.clone()
+    }
+
+    /// Read a file from the fake file system, if it exists
+    pub fn read_file(&self, path: impl AsRef<Path>) -> Option<String> {
+        let contents = self.fs.read(path.as_ref()).ok()?;
+        Some(String::from_utf8(contents).expect("file is valid UTF-8"))
+    }
+
+    /// Generate key pairs for `owner` and write them to the file system
+    ///
+    /// A key pair is generated for each of `flags`. Returns the base names
+    /// of the key files, as passed to signzone.
+    pub fn keys(&self, owner: &str, algorithm: KeyAlgorithm, flags: &[u16]) -> Vec<String> {
+        let owner = Name::from_str(owner).expect("valid owner name");
+        flags
+            .iter()
+            .map(|&flags| {
+                let key = KeyPair::generate(owner.clone(), algorithm, flags, 0)
+                    .expect("key generation succeeds");
+                let base = key.base_name();
+                self.file(format!("{base}.key"), key.display_public().to_string());
+                self.file(format!("{base}.private"), key.display_private().to_string());
+                base
+            })
+            .collect()
+    }
+
+    /// Make a [`FakeEnv`] for this [`FakeCmd`], sharing its file system
+    ///
+    /// This is for testing functions that take an [`Env`] directly.
+    pub fn env(&self) -> FakeEnv {
+        FakeEnv {
             cmd: self.clone(),
             stdout: Default::default(),
             stderr: Default::default(),
-        };
-        parse_args(env)
+            curr_step_value: Default::default(),
+        }
+    }
+
+    /// Parse the arguments of this [`FakeCmd`] and return the result
+    pub fn parse(&self) -> Result<Args, Error> {
+        parse_args(self.env())
     }
 
     /// Run the [`FakeCmd`] in a [`FakeEnv`], returning a [`FakeResult`]
     pub fn run(&self) -> FakeResult {
-        let env = FakeEnv {
-            cmd: self.clone(),
-            stdout: Default::default(),
-            stderr: Default::default(),
-        };
+        self.run_with(|env| run(env))
+    }
 
-        let exit_code = run(&env);
+    /// Run the [`FakeCmd`] as the `ldns` binary, see [`FakeCmd::run`]
+    pub fn run_ldns(&self) -> FakeResult {
+        self.run_with(|env| run_ldns(env))
+    }
+
+    fn run_with(&self, main: fn(&FakeEnv) -> u8) -> FakeResult {
+        let env = self.env();
+        let exit_code = main(&env);
 
         FakeResult {
             exit_code,
@@ -107,7 +334,33 @@ impl FakeCmd {
     }
 }
 
+/// Sign `zone` with `dnst signzone` and return the signed zone
+///
+/// The zone is signed with new keys for its apex `owner`, one for each of
+/// `flags`. The `options` are passed to signzone.
+pub fn signed_zone(
+    zone: &str,
+    owner: &str,
+    algorithm: KeyAlgorithm,
+    flags: &[u16],
+    options: &[&str],
+) -> String {
+    let cmd = FakeCmd::new(["dnst", "signzone", "-f", "-"]).file("zone", zone);
+    let keys = cmd.keys(owner, algorithm, flags);
+    let res = cmd.args(options).args(["zone"]).args(&keys).run();
+    assert_eq!(res.exit_code, 0, "{}", res.stderr);
+    res.stdout
+}
+
 impl FakeEnv {
+    fn network(&self) -> FakeNetwork {
+        FakeNetwork {
+            server: self.cmd.server.clone(),
+            stelline: self.cmd.stelline.clone(),
+            step_value: self.curr_step_value.clone(),
+        }
+    }
+
     pub fn get_stdout(&self) -> String {
         self.stdout.0.lock().unwrap().clone()
     }
@@ -133,3 +386,272 @@ impl fmt::Display for FakeStream {
         f.write_str(self.0.lock().unwrap().as_ref())
     }
 }
+
+/// An in-memory file system
+///
+/// Directories are not stored, but exist implicitly for every path with
+/// entries below it. Paths are used as given, except that `.` components
+/// are removed, so relative and absolute paths to the same file differ.
+#[derive(Clone, Default)]
+struct FakeFs(Arc<Mutex<BTreeMap<PathBuf, FakeEntry>>>);
+
+#[derive(Clone)]
+enum FakeEntry {
+    File(Vec<u8>),
+    Symlink(PathBuf),
+}
+
+impl FakeFs {
+    /// The maximum number of symbolic links followed when resolving a path
+    const MAX_SYMLINKS: usize = 8;
+
+    fn normalize(path: &Path) -> PathBuf {
+        path.components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .collect()
+    }
+
+    /// Resolve symbolic links in the last component of `path`
+    fn resolve(entries: &BTreeMap<PathBuf, FakeEntry>, path: &Path) -> io::Result<PathBuf> {
+        let mut path = Self::normalize(path);
+        for _ in 0..Self::MAX_SYMLINKS {
+            match entries.get(&path) {
+                Some(FakeEntry::Symlink(target)) => {
+                    let parent = path.parent().unwrap_or(Path::new(""));
+                    path = Self::normalize(&parent.join(target));
+                }
+                _ => return Ok(path),
+            }
+        }
+        Err(io::Error::other("too many levels of symbolic links"))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let entries = self.0.lock().unwrap();
+        let path = Self::resolve(&entries, path)?;
+        match entries.get(&path) {
+            Some(FakeEntry::File(contents)) => Ok(contents.clone()),
+            _ if Self::is_dir(&entries, &path) => Err(io::Error::other("is a directory")),
+            _ => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: &[u8], create_new: bool) -> io::Result<()> {
+        let mut entries = self.0.lock().unwrap();
+        if create_new && entries.contains_key(&Self::normalize(path)) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        let path = Self::resolve(&entries, path)?;
+        entries.insert(path, FakeEntry::File(contents.to_vec()));
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        let mut entries = self.0.lock().unwrap();
+        match entries.remove(&Self::normalize(path)) {
+            Some(_) => Ok(()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        let mut entries = self.0.lock().unwrap();
+        let link = Self::normalize(link);
+        if entries.contains_key(&link) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        entries.insert(link, FakeEntry::Symlink(target.to_path_buf()));
+        Ok(())
+    }
+
+    /// Create a hard link, which is a copy since files have no identity here
+    fn hard_link(&self, original: &Path, link: &Path) -> io::Result<()> {
+        let contents = self.read(original)?;
+        self.write(link, &contents, true)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let entries = self.0.lock().unwrap();
+        let path = Self::normalize(path);
+        entries.contains_key(&path) || Self::is_dir(&entries, &path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let entries = self.0.lock().unwrap();
+        let path = Self::resolve(&entries, path)?;
+        if !Self::is_dir(&entries, &path) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+
+        // Entries deeper down imply the directories above them.
+        let children: BTreeSet<_> = entries
+            .keys()
+            .filter_map(|p| p.strip_prefix(&path).ok()?.components().next())
+            .map(|c| path.join(c))
+            .collect();
+        Ok(children.into_iter().collect())
+    }
+
+    fn is_dir(entries: &BTreeMap<PathBuf, FakeEntry>, path: &Path) -> bool {
+        entries.keys().any(|p| p != path && p.starts_with(path))
+    }
+}
+
+/// The network of a [`FakeEnv`]
+///
+/// Requests are answered by the [`FakeServer`] or the Stelline script of the
+/// [`FakeCmd`]. Without either, all connections are refused.
+#[derive(Clone)]
+struct FakeNetwork {
+    server: Option<FakeServer>,
+    stelline: Option<Stelline>,
+    step_value: Arc<CurrStepValue>,
+}
+
+/// Sets up datagram connections to the [`FakeNetwork`]
+#[derive(Clone)]
+struct FakeConnect {
+    addr: SocketAddr,
+    network: FakeNetwork,
+}
+
+impl fmt::Debug for FakeConnect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FakeConnect")
+            .field("addr", &self.addr)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsyncConnect for FakeConnect {
+    type Connection = FakeSocket;
+    type Fut = Pin<Box<dyn Future<Output = io::Result<FakeSocket>> + Send + Sync>>;
+
+    fn connect(&self) -> Self::Fut {
+        let FakeNetwork {
+            server,
+            stelline,
+            step_value,
+        } = self.network.clone();
+
+        if let Some(server) = server {
+            return Box::pin(ready(Ok(FakeSocket::Server {
+                addr: self.addr,
+                server,
+                responses: Default::default(),
+            })));
+        }
+
+        let Some(stelline) = stelline else {
+            return Box::pin(ready(Err(io::ErrorKind::ConnectionRefused.into())));
+        };
+        let connect = StellineDgram::new(stelline, step_value);
+        Box::pin(async move { Ok(FakeSocket::Stelline(connect.connect().await?)) })
+    }
+}
+
+/// A datagram socket connected to the [`FakeNetwork`]
+///
+/// The [`FakeServer`] computes its responses as soon as a request is sent
+/// and they are queued until they are received. If the server does not
+/// respond, receiving never completes and the client will time out.
+enum FakeSocket {
+    Server {
+        addr: SocketAddr,
+        server: FakeServer,
+        responses: Mutex<VecDeque<Vec<u8>>>,
+    },
+    Stelline(StellineSocket),
+}
+
+impl AsyncDgramSend for FakeSocket {
+    fn poll_send(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let (addr, server, responses) = match self {
+            FakeSocket::Server {
+                addr,
+                server,
+                responses,
+            } => (addr, server, responses),
+            FakeSocket::Stelline(socket) => return socket.poll_send(cx, buf),
+        };
+
+        if let Ok(msg) = Message::from_slice(buf) {
+            if let Some(response) = server(*addr, msg) {
+                responses.lock().unwrap().push_back(response.into_octets());
+            }
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+}
+
+impl AsyncDgramRecv for FakeSocket {
+    fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let responses = match self {
+            FakeSocket::Server { responses, .. } => responses,
+            FakeSocket::Stelline(socket) => return socket.poll_recv(cx, buf),
+        };
+
+        match responses.lock().unwrap().pop_front() {
+            Some(response) => {
+                let len = response.len().min(buf.remaining());
+                buf.put_slice(&response[..len]);
+                Poll::Ready(Ok(()))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Sets up stream connections to the [`FakeNetwork`]
+///
+/// Only Stelline scripts can answer requests over streams.
+struct FakeStreamConnect {
+    network: FakeNetwork,
+}
+
+impl AsyncConnect for FakeStreamConnect {
+    type Connection = StellineStream;
+    type Fut = Ready<io::Result<StellineStream>>;
+
+    fn connect(&self) -> Self::Fut {
+        let Some(stelline) = self.network.stelline.clone() else {
+            return ready(Err(io::ErrorKind::ConnectionRefused.into()));
+        };
+        let step_value = self.network.step_value.clone();
+        ready(Ok(StellineStream::new(stelline, step_value)))
+    }
+}
+
+/// A stub resolver sending its queries over the [`FakeNetwork`]
+///
+/// The name servers are tried in order, like the real resolver does by
+/// default.
+struct FakeResolver {
+    servers: Vec<SocketAddr>,
+    network: FakeNetwork,
+}
+
+impl StubResolve for FakeResolver {
+    async fn query<N: ToName>(&self, qname: N, qtype: Rtype) -> io::Result<Message<Bytes>> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_rd(true);
+        let mut msg = msg.question();
+        msg.push((qname, qtype)).map_err(io::Error::other)?;
+        let msg = msg.into_message();
+
+        let mut last_error = io::Error::other("no name servers configured");
+        for &addr in &self.servers {
+            let connect = FakeConnect {
+                addr,
+                network: self.network.clone(),
+            };
+            let conn = dgram::Connection::new(connect);
+            let request = RequestMessage::new(msg.clone()).map_err(io::Error::other)?;
+            match conn.send_request(request).get_response().await {
+                Ok(response) => return Ok(response),
+                Err(e) => last_error = io::Error::other(e),
+            }
+        }
+        Err(last_error)
+    }
+}
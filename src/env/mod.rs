@@ -1,5 +1,16 @@
 use std::ffi::OsString;
 use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use domain::base::iana::Rtype;
+use domain::base::{Message, ToName};
+use domain::net::client::protocol::{AsyncConnect, AsyncDgramRecv, AsyncDgramSend};
+use domain::resolv::StubResolver;
+use tokio::io::{AsyncRead, AsyncWrite};
 
 mod real;
 
@@ -9,11 +20,24 @@ pub mod fake;
 pub use real::RealEnv;
 
 pub trait Env {
-    // /// Make a network connection
-    // fn make_connection(&self);
+    /// Make a network connection for exchanging datagrams with `addr`
+    ///
+    /// The result can be passed to [`domain::net::client::dgram::Connection`].
+    fn make_connection(&self, addr: SocketAddr) -> impl DgramConnect;
+
+    /// Make a network connection for exchanging a stream of messages with `addr`
+    ///
+    /// The result can be passed to
+    /// [`domain::net::client::multi_stream::Connection`].
+    fn make_stream_connection(&self, addr: SocketAddr) -> impl StreamConnect;
+
+    /// Make a stub resolver using the name servers from `/etc/resolv.conf`
+    fn make_stub_resolver(&self) -> impl StubResolve;
 
-    // /// Make a new [`StubResolver`]
-    // fn make_stub_resolver(&self);
+    /// Look up the socket addresses of a host name
+    ///
+    /// Equivalent to [`std::net::ToSocketAddrs`] on `(host, port)`
+    fn lookup_host(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>>;
 
     /// Get an iterator over the command line arguments passed to the program
     ///
@@ -30,8 +54,66 @@ pub trait Env {
     /// Equivalent to [`std::io::stderr`]
     fn stderr(&self) -> Stream<impl fmt::Write>;
 
-    // /// Get a reference to stdin
-    // fn stdin(&self) -> impl io::Read;
+    /// Whether stdout is a terminal
+    ///
+    /// Equivalent to [`std::io::IsTerminal::is_terminal`] on [`std::io::stdout`]
+    fn stdout_is_terminal(&self) -> bool;
+
+    /// Get a reference to stdin
+    ///
+    /// Equivalent to [`std::io::stdin`]
+    fn stdin(&self) -> impl io::Read;
+
+    /// Open a file for reading
+    ///
+    /// Equivalent to [`std::fs::File::open`]
+    fn open(&self, path: &Path) -> io::Result<impl io::Read>;
+
+    /// Read the entire contents of a file into a string
+    ///
+    /// Equivalent to [`std::fs::read_to_string`]
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Write a file, replacing its contents if it already exists
+    ///
+    /// Equivalent to [`std::fs::write`]
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    /// Write a new file, failing if it already exists
+    ///
+    /// Equivalent to opening the file with [`std::fs::OpenOptions::create_new`]
+    /// and writing `contents` to it.
+    fn create_new(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    /// Remove a file or symbolic link
+    ///
+    /// Equivalent to [`std::fs::remove_file`]
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Create a symbolic link at `link` pointing to `target`
+    ///
+    /// Equivalent to [`std::os::unix::fs::symlink`]
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()>;
+
+    /// Create a hard link at `link` to the file at `original`
+    ///
+    /// Equivalent to [`std::fs::hard_link`]
+    fn hard_link(&self, original: &Path, link: &Path) -> io::Result<()>;
+
+    /// Whether anything exists at `path`, including a dangling symbolic link
+    ///
+    /// Equivalent to `std::fs::symlink_metadata(path).is_ok()`
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Get the paths of the entries of a directory, sorted by name
+    ///
+    /// Equivalent to [`std::fs::read_dir`]
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Get the path of the running executable
+    ///
+    /// Equivalent to [`std::env::current_exe`]
+    fn current_exe(&self) -> io::Result<PathBuf>;
 }
 
 /// A type with an infallible `write_fmt` method for use with [`write!`] macros
@@ -53,14 +135,76 @@ impl<T: fmt::Write> Stream<T> {
     }
 }
 
+/// A type that can set up datagram connections, such as UDP sockets
+///
+/// This only exists to name the bounds that [`dgram::Connection`] and
+/// [`dgram_stream::Connection`] put on the connections they use.
+///
+/// [`dgram::Connection`]: domain::net::client::dgram::Connection
+/// [`dgram_stream::Connection`]: domain::net::client::dgram_stream::Connection
+pub trait DgramConnect:
+    AsyncConnect<Connection = Self::Socket> + Clone + fmt::Debug + Send + Sync + 'static
+{
+    type Socket: AsyncDgramRecv + AsyncDgramSend + Send + Sync + Unpin + 'static;
+}
+
+impl<T> DgramConnect for T
+where
+    T: AsyncConnect + Clone + fmt::Debug + Send + Sync + 'static,
+    T::Connection: AsyncDgramRecv + AsyncDgramSend + Send + Sync + Unpin + 'static,
+{
+    type Socket = T::Connection;
+}
+
+/// A type that can set up stream connections, such as TCP connections
+///
+/// Like [`DgramConnect`], this only names the bounds that the stream based
+/// connections of [`domain::net::client`] put on the connections they use.
+pub trait StreamConnect: AsyncConnect<Connection = Self::Stream> + Send + Sync + 'static {
+    type Stream: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static;
+}
+
+impl<T> StreamConnect for T
+where
+    T: AsyncConnect + Send + Sync + 'static,
+    T::Connection: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static,
+{
+    type Stream = T::Connection;
+}
+
+/// A resolver that sends recursive queries to the configured name servers
+pub trait StubResolve {
+    /// Query the name servers for the `qtype` records of `qname`
+    fn query<N: ToName>(
+        &self,
+        qname: N,
+        qtype: Rtype,
+    ) -> impl Future<Output = io::Result<Message<Bytes>>>;
+}
+
+impl StubResolve for StubResolver {
+    async fn query<N: ToName>(&self, qname: N, qtype: Rtype) -> io::Result<Message<Bytes>> {
+        let answer = StubResolver::query(self, (qname, qtype)).await?;
+        Ok(answer.into_message())
+    }
+}
+
 impl<E: Env> Env for &E {
-    // fn make_connection(&self) {
-    //     todo!()
-    // }
+    fn make_connection(&self, addr: SocketAddr) -> impl DgramConnect {
+        (**self).make_connection(addr)
+    }
 
-    // fn make_stub_resolver(&self) {
-    //     todo!()
-    // }
+    fn make_stream_connection(&self, addr: SocketAddr) -> impl StreamConnect {
+        (**self).make_stream_connection(addr)
+    }
+
+    fn make_stub_resolver(&self) -> impl StubResolve {
+        (**self).make_stub_resolver()
+    }
+
+    fn lookup_host(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        (**self).lookup_host(host, port)
+    }
 
     fn args_os(&self) -> impl Iterator<Item = OsString> {
         (**self).args_os()
@@ -73,4 +217,52 @@ impl<E: Env> Env for &E {
     fn stderr(&self) -> Stream<impl fmt::Write> {
         (**self).stderr()
     }
+
+    fn stdout_is_terminal(&self) -> bool {
+        (**self).stdout_is_terminal()
+    }
+
+    fn stdin(&self) -> impl io::Read {
+        (**self).stdin()
+    }
+
+    fn open(&self, path: &Path) -> io::Result<impl io::Read> {
+        (**self).open(path)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        (**self).read_to_string(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        (**self).write(path, contents)
+    }
+
+    fn create_new(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        (**self).create_new(path, contents)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        (**self).remove_file(path)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        (**self).symlink(target, link)
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> io::Result<()> {
+        (**self).hard_link(original, link)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        (**self).exists(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        (**self).read_dir(path)
+    }
+
+    fn current_exe(&self) -> io::Result<PathBuf> {
+        (**self).current_exe()
+    }
 }
@@ -1,14 +1,36 @@
 use std::ffi::OsString;
 use std::fmt;
-use std::io;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Write as _};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+
+use domain::net::client::protocol::{TcpConnect, UdpConnect};
+use domain::resolv::StubResolver;
 
-use super::Env;
 use super::Stream;
+use super::{DgramConnect, Env, StreamConnect, StubResolve};
 
 /// Use real I/O
 pub struct RealEnv;
 
 impl Env for RealEnv {
+    fn make_connection(&self, addr: SocketAddr) -> impl DgramConnect {
+        UdpConnect::new(addr)
+    }
+
+    fn make_stream_connection(&self, addr: SocketAddr) -> impl StreamConnect {
+        TcpConnect::new(addr)
+    }
+
+    fn make_stub_resolver(&self) -> impl StubResolve {
+        StubResolver::new()
+    }
+
+    fn lookup_host(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok((host, port).to_socket_addrs()?.collect())
+    }
+
     fn args_os(&self) -> impl Iterator<Item = OsString> {
         std::env::args_os()
     }
@@ -20,6 +42,68 @@ impl Env for RealEnv {
     fn stderr(&self) -> Stream<impl fmt::Write> {
         Stream(FmtWriter(io::stderr()))
     }
+
+    fn stdout_is_terminal(&self) -> bool {
+        io::stdout().is_terminal()
+    }
+
+    fn stdin(&self) -> impl io::Read {
+        io::stdin()
+    }
+
+    fn open(&self, path: &Path) -> io::Result<impl io::Read> {
+        File::open(path)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn create_new(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        File::options()
+            .write(true)
+            .create_new(true)
+            .open(path)?
+            .write_all(contents)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    #[cfg(unix)]
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    #[cfg(not(unix))]
+    fn symlink(&self, _target: &Path, _link: &Path) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> io::Result<()> {
+        fs::hard_link(original, link)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.symlink_metadata().is_ok()
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut paths = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.sort();
+        Ok(paths)
+    }
+
+    fn current_exe(&self) -> io::Result<PathBuf> {
+        std::env::current_exe()
+    }
 }
 
 struct FmtWriter<T: io::Write>(T);
@@ -1,3 +1,4 @@
+use crate::args::OutputFormat;
 use crate::env::Env;
 use std::fmt;
 use std::{error, io};
@@ -53,10 +54,31 @@ impl Error {
     }
 
     /// Pretty-print this error.
-    pub fn pretty_print(&self, env: impl Env) {
+    ///
+    /// In the JSON format, the error is printed as a JSON object with the
+    /// error message and its context instead.
+    pub fn pretty_print(&self, env: impl Env, format: OutputFormat) {
         use std::io::IsTerminal;
         let mut err = env.stderr();
 
+        if format == OutputFormat::Json {
+            let error = match &self.0.primary {
+                // The help and version are not errors and stay text.
+                PrimaryError::Clap(e) if !e.use_stderr() => {
+                    writeln!(err, "{}", e.render());
+                    return;
+                }
+                PrimaryError::Clap(e) => e.render().to_string(),
+                PrimaryError::Other(error) => error.to_string(),
+            };
+            let json = serde_json::json!({
+                "error": error.trim_end(),
+                "context": self.0.context,
+            });
+            writeln!(err, "{json}");
+            return;
+        }
+
         let error = match &self.0.primary {
             // Clap errors are already styled. We don't want our own pretty
             // styling around that and context does not make sense for command
@@ -158,6 +180,70 @@ impl fmt::Debug for Error {
 
 impl error::Error for Error {}
 
+//------------ Warning -------------------------------------------------------
+
+/// A program warning.
+///
+/// Unlike an [`Error`], a warning does not halt the program. It is printed
+/// and the program continues.
+pub struct Warning(Box<str>);
+
+impl Warning {
+    /// Construct a new warning from a string.
+    pub fn new(warning: &str) -> Self {
+        Self(warning.into())
+    }
+
+    /// Pretty-print this warning.
+    ///
+    /// In the JSON format, the warning is printed as a JSON object instead.
+    pub fn pretty_print(&self, env: impl Env, format: OutputFormat) {
+        use std::io::IsTerminal;
+        let mut err = env.stderr();
+
+        if format == OutputFormat::Json {
+            writeln!(err, "{}", serde_json::json!({ "warning": self.0 }));
+            return;
+        }
+
+        // NOTE: See Error::pretty_print.
+        let prog = std::env::args().next().unwrap();
+        let term = std::io::stderr().is_terminal();
+
+        let warning_marker = if term {
+            "\x1B[33mWARNING:\x1B[0m"
+        } else {
+            "WARNING:"
+        };
+
+        writeln!(err, "[{prog}] {warning_marker} {}", self.0);
+    }
+}
+
+impl From<&str> for Warning {
+    fn from(warning: &str) -> Self {
+        Self::new(warning)
+    }
+}
+
+impl From<String> for Warning {
+    fn from(warning: String) -> Self {
+        Self::new(&warning)
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Debug for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Warning").field(&self.0).finish()
+    }
+}
+
 //------------ Result --------------------------------------------------------
 
 /// A program result.
@@ -0,0 +1,200 @@
+//! Snapshot tests for the ldns compatibility modes
+//!
+//! Each directory in `tests/golden` is a case with the recorded behaviour of
+//! dnst in the mode of an ldns tool. The case is run in a [`FakeCmd`] both with the name of the
+//! tool as the program name, as when dnst is invoked through a link, and
+//! through the `ldns` binary. See `tests/golden/README.md` for the layout of
+//! a case.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::env::fake::{FakeCmd, FakeResult};
+
+/// The directory with the cases
+const CASES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+
+/// A recorded run of dnst as an ldns tool
+struct Case {
+    /// The name of the directory of the case
+    name: String,
+
+    /// The arguments, starting with the name of the tool
+    args: Vec<String>,
+
+    /// The input on stdin
+    stdin: Vec<u8>,
+
+    /// The files that exist before the run
+    files: Vec<(PathBuf, Vec<u8>)>,
+
+    /// The expected output on stdout
+    stdout: String,
+
+    /// The expected output on stderr, with the program name normalised
+    stderr: String,
+
+    /// The expected exit code
+    exit_code: u8,
+
+    /// The files that are expected to exist after the run
+    outputs: Vec<(PathBuf, String)>,
+}
+
+impl Case {
+    fn load(dir: &Path) -> Self {
+        let read = |name: &str| fs::read(dir.join(name)).ok();
+        let read_string = |name: &str| {
+            read(name).map(|contents| String::from_utf8(contents).expect("case files are UTF-8"))
+        };
+
+        let name = dir.file_name().unwrap().to_string_lossy().into_owned();
+        let args: Vec<_> = read_string("cmd")
+            .unwrap_or_else(|| panic!("case {name} has no cmd file"))
+            .lines()
+            .map(String::from)
+            .collect();
+        assert!(
+            args.first().is_some_and(|tool| tool.starts_with("ldns-")),
+            "case {name} does not run an ldns tool"
+        );
+
+        let exit_code = read_string("exit_code").map_or(0, |code| {
+            code.trim()
+                .parse()
+                .unwrap_or_else(|e| panic!("case {name} has an invalid exit code: {e}"))
+        });
+
+        let outputs = read_files(&dir.join("out"))
+            .into_iter()
+            .map(|(path, contents)| (path, String::from_utf8(contents).unwrap()))
+            .collect();
+
+        Self {
+            args,
+            stdin: read("stdin").unwrap_or_default(),
+            files: read_files(&dir.join("in")),
+            stdout: read_string("stdout").unwrap_or_default(),
+            stderr: read_string("stderr").unwrap_or_default(),
+            exit_code,
+            outputs,
+            name,
+        }
+    }
+
+    /// Set up a [`FakeCmd`] for the case with the given arguments
+    fn cmd(&self, args: &[String]) -> FakeCmd {
+        let mut cmd = FakeCmd::new(args).stdin(&self.stdin);
+        for (path, contents) in &self.files {
+            cmd = cmd.file(path, contents);
+        }
+        cmd
+    }
+
+    /// Compare the result of a run with the recording
+    ///
+    /// Returns a description of each difference.
+    fn check(&self, entry: &str, cmd: &FakeCmd, res: &FakeResult) -> Vec<String> {
+        let mut diffs = Vec::new();
+        let mut compare = |what: &str, expected: &str, actual: &str| {
+            if expected != actual {
+                diffs.push(format!(
+                    "case {} ({entry}): {what} differs\n\
+                     --- expected\n{expected}\n--- actual\n{actual}",
+                    self.name
+                ));
+            }
+        };
+
+        compare(
+            "exit code",
+            &self.exit_code.to_string(),
+            &res.exit_code.to_string(),
+        );
+        compare("stdout", &self.stdout, &res.stdout);
+        compare(
+            "stderr",
+            &self.stderr,
+            &normalise_program(&res.stderr, &self.args[0]),
+        );
+        for (path, expected) in &self.outputs {
+            let actual = cmd.read_file(path);
+            compare(
+                &format!("file {}", path.display()),
+                expected,
+                actual.as_deref().unwrap_or("<missing>"),
+            );
+        }
+        diffs
+    }
+}
+
+/// Replace the program name in error messages with the name of the tool
+///
+/// Errors start with the program name as invoked, e.g. `[ldns-key2ds]`, which
+/// differs between a link, the `ldns` binary and the test binary. The marker
+/// after it is styled when the tests run in a terminal.
+fn normalise_program(stderr: &str, tool: &str) -> String {
+    stderr
+        .replace("\x1B[31mERROR:\x1B[0m", "ERROR:")
+        .split_inclusive('\n')
+        .map(|line| match line.split_once("] ERROR: ") {
+            Some((prog, rest)) if prog.starts_with('[') => format!("[{tool}] ERROR: {rest}"),
+            _ => line.to_string(),
+        })
+        .collect()
+}
+
+/// Read the files below `dir`, with their paths relative to `dir`
+///
+/// Returns nothing if `dir` does not exist.
+fn read_files(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+    let mut files = Vec::new();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(sub) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(dir.join(&sub)) else {
+            continue;
+        };
+        for entry in entries {
+            let entry = entry.unwrap();
+            let path = sub.join(entry.file_name());
+            if entry.file_type().unwrap().is_dir() {
+                dirs.push(path);
+            } else {
+                files.push((path, fs::read(entry.path()).unwrap()));
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[test]
+fn golden() {
+    let mut dirs: Vec<_> = fs::read_dir(CASES)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    assert!(!dirs.is_empty(), "no cases in {CASES}");
+
+    let mut diffs = Vec::new();
+    for dir in dirs {
+        let case = Case::load(&dir);
+
+        // As if dnst was invoked through a link with the name of the tool.
+        let cmd = case.cmd(&case.args);
+        let res = cmd.run();
+        diffs.extend(case.check(&case.args[0], &cmd, &res));
+
+        // Through the ldns binary, with the tool as the first argument.
+        let mut args = vec!["ldns".to_string()];
+        args.extend(case.args.iter().cloned());
+        let cmd = case.cmd(&args);
+        let res = cmd.run_ldns();
+        diffs.extend(case.check("ldns", &cmd, &res));
+    }
+
+    assert!(diffs.is_empty(), "\n{}", diffs.join("\n\n"));
+}
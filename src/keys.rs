@@ -0,0 +1,717 @@
+//! DNSSEC key pairs and their on-disk representation.
+//!
+//! Keys are stored in the format used by BIND and ldns: the public key as a
+//! DNSKEY record in a `K<name>+<alg>+<tag>.key` file and the private key in
+//! a `K<name>+<alg>+<tag>.private` file.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use domain::base::iana::SecAlg;
+use domain::base::{Name, ToName};
+use domain::rdata::{Dnskey, ZoneRecordData};
+use domain::utils::base64;
+use ring::rand::SystemRandom;
+use ring::signature::{
+    EcdsaKeyPair, Ed25519KeyPair, KeyPair as _, RsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING,
+    ECDSA_P384_SHA384_FIXED_SIGNING, RSA_PKCS1_SHA256,
+};
+
+use crate::env::Env;
+use crate::error::{Context, Error};
+use crate::zone::read_zone_file;
+
+/// The DNSKEY flags of a zone signing key.
+pub const ZSK_FLAGS: u16 = 256;
+
+/// The DNSKEY flags of a key signing key, i.e., a zone key with the SEP bit.
+pub const KSK_FLAGS: u16 = 257;
+
+/// The TTL used for newly generated DNSKEY records.
+const DEFAULT_TTL: u32 = 3600;
+
+//------------ KeyAlgorithm --------------------------------------------------
+
+/// The signing algorithms that keys can be generated and used for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyAlgorithm {
+    RsaSha256,
+    EcdsaP256Sha256,
+    EcdsaP384Sha384,
+    Ed25519,
+}
+
+impl KeyAlgorithm {
+    /// All supported algorithms.
+    pub const ALL: [Self; 4] = [
+        Self::RsaSha256,
+        Self::EcdsaP256Sha256,
+        Self::EcdsaP384Sha384,
+        Self::Ed25519,
+    ];
+
+    /// Parse an algorithm from its number or mnemonic.
+    pub fn parse(arg: &str) -> Result<Self, &'static str> {
+        let alg = if let Ok(num) = arg.parse() {
+            SecAlg::from_int(num)
+        } else {
+            SecAlg::from_mnemonic(arg.to_uppercase().as_bytes())
+                .ok_or("unknown algorithm mnemonic")?
+        };
+        Self::from_sec_alg(alg).ok_or("unsupported algorithm")
+    }
+
+    pub fn from_sec_alg(alg: SecAlg) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.to_sec_alg() == alg)
+    }
+
+    pub fn to_sec_alg(self) -> SecAlg {
+        match self {
+            Self::RsaSha256 => SecAlg::RSASHA256,
+            Self::EcdsaP256Sha256 => SecAlg::ECDSAP256SHA256,
+            Self::EcdsaP384Sha384 => SecAlg::ECDSAP384SHA384,
+            Self::Ed25519 => SecAlg::ED25519,
+        }
+    }
+}
+
+impl fmt::Display for KeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_sec_alg().fmt(f)
+    }
+}
+
+//------------ SecretKey -----------------------------------------------------
+
+/// The private half of a key pair.
+///
+/// All numbers are stored as big-endian byte strings, which is also how
+/// they are encoded (in base64) in the private key file.
+#[derive(Clone)]
+pub enum SecretKey {
+    RsaSha256(RsaSecretKey),
+
+    /// The private scalar of a P-256 key.
+    EcdsaP256Sha256(Vec<u8>),
+
+    /// The private scalar of a P-384 key.
+    EcdsaP384Sha384(Vec<u8>),
+
+    /// The seed of an Ed25519 key.
+    Ed25519(Vec<u8>),
+}
+
+#[derive(Clone)]
+pub struct RsaSecretKey {
+    pub modulus: Vec<u8>,
+    pub public_exponent: Vec<u8>,
+    pub private_exponent: Vec<u8>,
+    pub prime1: Vec<u8>,
+    pub prime2: Vec<u8>,
+    pub exponent1: Vec<u8>,
+    pub exponent2: Vec<u8>,
+    pub coefficient: Vec<u8>,
+}
+
+impl SecretKey {
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        match self {
+            Self::RsaSha256(_) => KeyAlgorithm::RsaSha256,
+            Self::EcdsaP256Sha256(_) => KeyAlgorithm::EcdsaP256Sha256,
+            Self::EcdsaP384Sha384(_) => KeyAlgorithm::EcdsaP384Sha384,
+            Self::Ed25519(_) => KeyAlgorithm::Ed25519,
+        }
+    }
+}
+
+// Never print key material by accident.
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretKey({})", self.algorithm())
+    }
+}
+
+//------------ KeyPair -------------------------------------------------------
+
+/// A DNSSEC key pair for a zone.
+#[derive(Clone, Debug)]
+pub struct KeyPair {
+    owner: Name<Vec<u8>>,
+    dnskey: Dnskey<Vec<u8>>,
+    secret: SecretKey,
+}
+
+impl KeyPair {
+    /// Generate a new key pair.
+    ///
+    /// The `bits` are only used for RSA keys.
+    pub fn generate(
+        owner: Name<Vec<u8>>,
+        algorithm: KeyAlgorithm,
+        flags: u16,
+        bits: u32,
+    ) -> Result<Self, Error> {
+        let rng = SystemRandom::new();
+        let (secret, public_key) = match algorithm {
+            KeyAlgorithm::RsaSha256 => generate_rsa(bits)?,
+            KeyAlgorithm::EcdsaP256Sha256 => {
+                let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+                    .map_err(|_| "could not generate key")?;
+                let pair = EcdsaKeyPair::from_pkcs8(
+                    &ECDSA_P256_SHA256_FIXED_SIGNING,
+                    pkcs8.as_ref(),
+                    &rng,
+                )
+                .map_err(|_| "could not generate key")?;
+                let secret = der_find_after(pkcs8.as_ref(), &[0x02, 0x01, 0x01, 0x04, 0x20], 32)?;
+                (
+                    SecretKey::EcdsaP256Sha256(secret),
+                    // DNSKEY leaves out the uncompressed point marker.
+                    pair.public_key().as_ref()[1..].to_vec(),
+                )
+            }
+            KeyAlgorithm::EcdsaP384Sha384 => {
+                let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P384_SHA384_FIXED_SIGNING, &rng)
+                    .map_err(|_| "could not generate key")?;
+                let pair = EcdsaKeyPair::from_pkcs8(
+                    &ECDSA_P384_SHA384_FIXED_SIGNING,
+                    pkcs8.as_ref(),
+                    &rng,
+                )
+                .map_err(|_| "could not generate key")?;
+                let secret = der_find_after(pkcs8.as_ref(), &[0x02, 0x01, 0x01, 0x04, 0x30], 48)?;
+                (
+                    SecretKey::EcdsaP384Sha384(secret),
+                    pair.public_key().as_ref()[1..].to_vec(),
+                )
+            }
+            KeyAlgorithm::Ed25519 => {
+                let pkcs8 =
+                    Ed25519KeyPair::generate_pkcs8(&rng).map_err(|_| "could not generate key")?;
+                let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+                    .map_err(|_| "could not generate key")?;
+                let seed = der_find_after(pkcs8.as_ref(), &[0x04, 0x22, 0x04, 0x20], 32)?;
+                (
+                    SecretKey::Ed25519(seed),
+                    pair.public_key().as_ref().to_vec(),
+                )
+            }
+        };
+
+        let dnskey = Dnskey::new(flags, 3, algorithm.to_sec_alg(), public_key)
+            .map_err(|_| "public key too long")?;
+
+        Ok(Self {
+            owner,
+            dnskey,
+            secret,
+        })
+    }
+
+    /// Load a key pair from the `<base>.key` and `<base>.private` files.
+    ///
+    /// For convenience, the base name may also be given with either of the
+    /// two extensions.
+    pub fn load(env: &impl Env, base: &Path) -> Result<Self, Error> {
+        let base = strip_key_extension(base);
+        Self::load_inner(env, &base).with_context(|| format!("loading key {}", base.display()))
+    }
+
+    fn load_inner(env: &impl Env, base: &Path) -> Result<Self, Error> {
+        let public_file = with_suffix(base, ".key");
+        let (owner, dnskey) = read_zone_file(env, &public_file, None)?
+            .into_iter()
+            .find_map(|record| {
+                let owner = record.owner().to_vec();
+                match record.into_data() {
+                    ZoneRecordData::Dnskey(dnskey) => Some((owner, dnskey)),
+                    _ => None,
+                }
+            })
+            .ok_or_else(|| format!("no DNSKEY record found in {}", public_file.display()))?;
+
+        let dnskey = Dnskey::new(
+            dnskey.flags(),
+            dnskey.protocol(),
+            dnskey.algorithm(),
+            dnskey.public_key().to_vec(),
+        )
+        .map_err(|_| "public key too long")?;
+
+        let private_file = with_suffix(base, ".private");
+        let private = env
+            .read_to_string(&private_file)
+            .map_err(|e| format!("could not read {}: {e}", private_file.display()))?;
+        let secret = parse_private_key(&private)
+            .with_context(|| format!("parsing {}", private_file.display()))?;
+
+        if secret.algorithm().to_sec_alg() != dnskey.algorithm() {
+            return Err("the algorithms of the public and private key do not match".into());
+        }
+
+        let key = Self {
+            owner,
+            dnskey,
+            secret,
+        };
+
+        // Check that the halves fit together before we sign a whole zone.
+        key.signer()?;
+        Ok(key)
+    }
+
+    pub fn owner(&self) -> &Name<Vec<u8>> {
+        &self.owner
+    }
+
+    pub fn dnskey(&self) -> &Dnskey<Vec<u8>> {
+        &self.dnskey
+    }
+
+    pub fn secret(&self) -> &SecretKey {
+        &self.secret
+    }
+
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        self.secret.algorithm()
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.dnskey.key_tag()
+    }
+
+    /// The base name of the key files, `K<name>+<alg>+<tag>`.
+    pub fn base_name(&self) -> String {
+        format!(
+            "K{}+{:03}+{:05}",
+            self.owner.fmt_with_dot(),
+            self.dnskey.algorithm().to_int(),
+            self.key_tag()
+        )
+    }
+
+    /// The size of the key in bits, as reported by ldns.
+    pub fn bits(&self) -> usize {
+        match &self.secret {
+            SecretKey::RsaSha256(rsa) => {
+                let leading = rsa.modulus.iter().take_while(|b| **b == 0).count();
+                let modulus = &rsa.modulus[leading..];
+                modulus.len() * 8 - modulus.first().map_or(0, |b| b.leading_zeros() as usize)
+            }
+            SecretKey::EcdsaP256Sha256(_) => 256,
+            SecretKey::EcdsaP384Sha384(_) => 384,
+            SecretKey::Ed25519(_) => 256,
+        }
+    }
+
+    /// Create a [`Signer`] for this key pair.
+    pub fn signer(&self) -> Result<Signer, Error> {
+        let rng = SystemRandom::new();
+        let public_key = self.dnskey.public_key();
+        let mismatch = |_| Error::from("the private key does not match the public key");
+
+        let key =
+            match &self.secret {
+                SecretKey::RsaSha256(rsa) => {
+                    if rsa_public_key(&rsa.public_exponent, &rsa.modulus) != *public_key {
+                        return Err("the private key does not match the public key".into());
+                    }
+                    let components = ring::rsa::KeyPairComponents {
+                        public_key: ring::rsa::PublicKeyComponents {
+                            n: &rsa.modulus,
+                            e: &rsa.public_exponent,
+                        },
+                        d: &rsa.private_exponent,
+                        p: &rsa.prime1,
+                        q: &rsa.prime2,
+                        dP: &rsa.exponent1,
+                        dQ: &rsa.exponent2,
+                        qInv: &rsa.coefficient,
+                    };
+                    RingKey::Rsa(RsaKeyPair::from_components(&components).map_err(|e| {
+                        Error::from(format!("the RSA key was rejected: {e}").as_str())
+                    })?)
+                }
+                SecretKey::EcdsaP256Sha256(secret) | SecretKey::EcdsaP384Sha384(secret) => {
+                    let alg = if self.algorithm() == KeyAlgorithm::EcdsaP256Sha256 {
+                        &ECDSA_P256_SHA256_FIXED_SIGNING
+                    } else {
+                        &ECDSA_P384_SHA384_FIXED_SIGNING
+                    };
+                    let mut point = Vec::with_capacity(public_key.len() + 1);
+                    point.push(0x04);
+                    point.extend_from_slice(public_key);
+                    RingKey::Ecdsa(
+                        EcdsaKeyPair::from_private_key_and_public_key(alg, secret, &point, &rng)
+                            .map_err(mismatch)?,
+                    )
+                }
+                SecretKey::Ed25519(seed) => RingKey::Ed25519(
+                    Ed25519KeyPair::from_seed_and_public_key(seed, public_key).map_err(mismatch)?,
+                ),
+            };
+
+        Ok(Signer { key, rng })
+    }
+
+    /// The contents of the `.key` file.
+    pub fn display_public(&self) -> impl fmt::Display + '_ {
+        PublicKeyFile(self)
+    }
+
+    /// The contents of the `.private` file.
+    pub fn display_private(&self) -> impl fmt::Display + '_ {
+        PrivateKeyFile(self)
+    }
+}
+
+struct PublicKeyFile<'a>(&'a KeyPair);
+
+impl fmt::Display for PublicKeyFile<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = self.0;
+        let dnskey = &key.dnskey;
+        writeln!(
+            f,
+            "{}\t{}\tIN\tDNSKEY\t{} {} {} {} ;{{id = {} ({}), size = {}b}}",
+            key.owner.fmt_with_dot(),
+            DEFAULT_TTL,
+            dnskey.flags(),
+            dnskey.protocol(),
+            dnskey.algorithm().to_int(),
+            base64::encode_display(dnskey.public_key()),
+            key.key_tag(),
+            if dnskey.is_secure_entry_point() {
+                "ksk"
+            } else {
+                "zsk"
+            },
+            key.bits(),
+        )
+    }
+}
+
+struct PrivateKeyFile<'a>(&'a KeyPair);
+
+impl fmt::Display for PrivateKeyFile<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let alg = self.0.algorithm();
+        writeln!(f, "Private-key-format: v1.2")?;
+        writeln!(f, "Algorithm: {} ({alg})", alg.to_sec_alg().to_int())?;
+        match &self.0.secret {
+            SecretKey::RsaSha256(rsa) => {
+                let fields = [
+                    ("Modulus", &rsa.modulus),
+                    ("PublicExponent", &rsa.public_exponent),
+                    ("PrivateExponent", &rsa.private_exponent),
+                    ("Prime1", &rsa.prime1),
+                    ("Prime2", &rsa.prime2),
+                    ("Exponent1", &rsa.exponent1),
+                    ("Exponent2", &rsa.exponent2),
+                    ("Coefficient", &rsa.coefficient),
+                ];
+                for (name, value) in fields {
+                    writeln!(f, "{name}: {}", base64::encode_display(value))?;
+                }
+            }
+            SecretKey::EcdsaP256Sha256(key)
+            | SecretKey::EcdsaP384Sha384(key)
+            | SecretKey::Ed25519(key) => {
+                writeln!(f, "PrivateKey: {}", base64::encode_display(key))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+//------------ Signer --------------------------------------------------------
+
+/// A key pair ready to sign data.
+pub struct Signer {
+    key: RingKey,
+    rng: SystemRandom,
+}
+
+enum RingKey {
+    Rsa(RsaKeyPair),
+    Ecdsa(EcdsaKeyPair),
+    Ed25519(Ed25519KeyPair),
+}
+
+impl Signer {
+    /// Sign the given data, returning the signature as used in RRSIG records.
+    pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match &self.key {
+            RingKey::Rsa(key) => {
+                let mut sig = vec![0; key.public().modulus_len()];
+                key.sign(&RSA_PKCS1_SHA256, &self.rng, data, &mut sig)
+                    .map_err(|_| "could not create RSA signature")?;
+                Ok(sig)
+            }
+            RingKey::Ecdsa(key) => key
+                .sign(&self.rng, data)
+                .map(|sig| sig.as_ref().to_vec())
+                .map_err(|_| "could not create ECDSA signature".into()),
+            RingKey::Ed25519(key) => Ok(key.sign(data).as_ref().to_vec()),
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Parse the contents of a private key file in the BIND format.
+fn parse_private_key(text: &str) -> Result<SecretKey, Error> {
+    let mut fields = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(format!("invalid line: {line}").into());
+        };
+        fields.push((name.trim(), value.trim()));
+    }
+
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| *v)
+            .ok_or_else(|| Error::from(format!("missing field {name}")))
+    };
+    let decode = |name: &str| -> Result<Vec<u8>, Error> {
+        base64::decode(field(name)?).map_err(|e| format!("invalid field {name}: {e}").into())
+    };
+
+    let format = field("Private-key-format")?;
+    if !format.starts_with("v1.") {
+        return Err(format!("unsupported private key format {format}").into());
+    }
+
+    let algorithm = field("Algorithm")?;
+    let algorithm = algorithm
+        .split_whitespace()
+        .next()
+        .and_then(|num| num.parse().ok())
+        .map(SecAlg::from_int)
+        .and_then(KeyAlgorithm::from_sec_alg)
+        .ok_or_else(|| format!("unsupported algorithm {algorithm}"))?;
+
+    Ok(match algorithm {
+        KeyAlgorithm::RsaSha256 => SecretKey::RsaSha256(RsaSecretKey {
+            modulus: decode("Modulus")?,
+            public_exponent: decode("PublicExponent")?,
+            private_exponent: decode("PrivateExponent")?,
+            prime1: decode("Prime1")?,
+            prime2: decode("Prime2")?,
+            exponent1: decode("Exponent1")?,
+            exponent2: decode("Exponent2")?,
+            coefficient: decode("Coefficient")?,
+        }),
+        KeyAlgorithm::EcdsaP256Sha256 => SecretKey::EcdsaP256Sha256(decode("PrivateKey")?),
+        KeyAlgorithm::EcdsaP384Sha384 => SecretKey::EcdsaP384Sha384(decode("PrivateKey")?),
+        KeyAlgorithm::Ed25519 => SecretKey::Ed25519(decode("PrivateKey")?),
+    })
+}
+
+/// Strip a `.key` or `.private` extension from a key file name
+fn strip_key_extension(path: &Path) -> PathBuf {
+    let s = path.as_os_str().to_string_lossy();
+    for ext in [".key", ".private"] {
+        if let Some(base) = s.strip_suffix(ext) {
+            return PathBuf::from(base);
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Append a suffix to a path without treating it as an extension
+///
+/// Key base names contain dots, so [`Path::with_extension`] doesn't work.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut s = OsString::from(path.as_os_str());
+    s.push(suffix);
+    s.into()
+}
+
+fn generate_rsa(bits: u32) -> Result<(SecretKey, Vec<u8>), Error> {
+    use rsa::traits::{PrivateKeyParts, PublicKeyParts};
+
+    // ring can only sign with RSA keys in this range.
+    if !(2048..=4096).contains(&bits) {
+        return Err(format!("RSA key size must be between 2048 and 4096 bits, not {bits}").into());
+    }
+
+    let key = rsa::RsaPrivateKey::new(&mut rsa::rand_core::OsRng, bits as usize)
+        .map_err(|e| format!("could not generate key: {e}"))?;
+
+    let [p, q] = key.primes() else {
+        return Err("could not generate key: unexpected number of primes".into());
+    };
+    let (Some(dp), Some(dq), Some(qinv)) = (key.dp(), key.dq(), key.crt_coefficient()) else {
+        return Err("could not generate key: missing CRT values".into());
+    };
+
+    let secret = RsaSecretKey {
+        modulus: key.n().to_bytes_be(),
+        public_exponent: key.e().to_bytes_be(),
+        private_exponent: key.d().to_bytes_be(),
+        prime1: p.to_bytes_be(),
+        prime2: q.to_bytes_be(),
+        exponent1: dp.to_bytes_be(),
+        exponent2: dq.to_bytes_be(),
+        coefficient: qinv.to_bytes_be(),
+    };
+
+    let public_key = rsa_public_key(&secret.public_exponent, &secret.modulus);
+    Ok((SecretKey::RsaSha256(secret), public_key))
+}
+
+/// Encode an RSA public key for a DNSKEY record as described in RFC 3110.
+fn rsa_public_key(exponent: &[u8], modulus: &[u8]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(exponent.len() + modulus.len() + 3);
+    if let Ok(len) = u8::try_from(exponent.len()) {
+        res.push(len);
+    } else {
+        res.push(0);
+        res.extend_from_slice(&(exponent.len() as u16).to_be_bytes());
+    }
+    res.extend_from_slice(exponent);
+    res.extend_from_slice(modulus);
+    res
+}
+
+/// Extract the `len` bytes following `prefix` from a DER document
+///
+/// This is just enough DER parsing to get the private key out of the PKCS#8
+/// documents generated by ring, which have a fixed layout.
+fn der_find_after(der: &[u8], prefix: &[u8], len: usize) -> Result<Vec<u8>, Error> {
+    der.windows(prefix.len())
+        .position(|w| w == prefix)
+        .map(|pos| pos + prefix.len())
+        .and_then(|start| der.get(start..start + len))
+        .map(|key| key.to_vec())
+        .ok_or_else(|| "could not extract private key from generated key".into())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use std::str::FromStr;
+
+    use domain::base::Name;
+    use domain::validate::RrsigExt;
+
+    use super::{KeyAlgorithm, KeyPair, KSK_FLAGS, ZSK_FLAGS};
+    use crate::env::fake::FakeCmd;
+
+    #[test]
+    fn parse_algorithm() {
+        assert_eq!(KeyAlgorithm::parse("8"), Ok(KeyAlgorithm::RsaSha256));
+        assert_eq!(KeyAlgorithm::parse("ed25519"), Ok(KeyAlgorithm::Ed25519));
+        assert_eq!(
+            KeyAlgorithm::parse("ECDSAP384SHA384"),
+            Ok(KeyAlgorithm::EcdsaP384Sha384)
+        );
+        assert!(KeyAlgorithm::parse("5").is_err());
+        assert!(KeyAlgorithm::parse("foo").is_err());
+    }
+
+    #[test]
+    fn generate() {
+        let owner = Name::from_str("example.test").unwrap();
+        for (alg, key_len) in [
+            (KeyAlgorithm::EcdsaP256Sha256, 64),
+            (KeyAlgorithm::EcdsaP384Sha384, 96),
+            (KeyAlgorithm::Ed25519, 32),
+        ] {
+            let key = KeyPair::generate(owner.clone(), alg, ZSK_FLAGS, 0).unwrap();
+            assert_eq!(key.dnskey().public_key().len(), key_len);
+            assert!(key.base_name().starts_with("Kexample.test.+0"));
+
+            let private = key.display_private().to_string();
+            assert!(private.starts_with("Private-key-format: v1.2\nAlgorithm: "));
+            assert!(private.contains("\nPrivateKey: "));
+        }
+
+        let key = KeyPair::generate(owner, KeyAlgorithm::RsaSha256, KSK_FLAGS, 2048).unwrap();
+        assert_eq!(key.bits(), 2048);
+        assert!(key.dnskey().is_secure_entry_point());
+        assert!(key
+            .display_public()
+            .to_string()
+            .contains(" (ksk), size = 2048b}"));
+        assert!(key
+            .display_private()
+            .to_string()
+            .contains("\nCoefficient: "));
+    }
+
+    #[test]
+    fn save_load_sign() {
+        let cmd = FakeCmd::new(["dnst"]);
+        let owner = Name::from_str("example.test").unwrap();
+
+        for alg in KeyAlgorithm::ALL {
+            let key = KeyPair::generate(owner.clone(), alg, ZSK_FLAGS, 2048).unwrap();
+            let base = Path::new("keys").join(key.base_name());
+            cmd.file(
+                super::with_suffix(&base, ".key"),
+                key.display_public().to_string(),
+            );
+            cmd.file(
+                super::with_suffix(&base, ".private"),
+                key.display_private().to_string(),
+            );
+
+            let loaded = KeyPair::load(&cmd.env(), &super::with_suffix(&base, ".private")).unwrap();
+            assert_eq!(loaded.dnskey(), key.dnskey());
+            assert_eq!(loaded.owner(), key.owner());
+
+            // Sign something and check the signature with the validator
+            // from domain.
+            let rrsig = domain::rdata::Rrsig::new(
+                domain::base::iana::Rtype::A,
+                alg.to_sec_alg(),
+                2,
+                domain::base::Ttl::from_secs(3600),
+                0.into(),
+                0.into(),
+                key.key_tag(),
+                owner.clone(),
+                Vec::new(),
+            )
+            .unwrap();
+            let data = b"some data";
+            let sig = loaded.signer().unwrap().sign(data).unwrap();
+            let mut rrsig = rrsig;
+            rrsig.set_signature(sig);
+            assert!(rrsig.verify_signed_data(key.dnskey(), data).is_ok());
+            assert!(rrsig
+                .verify_signed_data(key.dnskey(), b"other data")
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn load_mismatch() {
+        let cmd = FakeCmd::new(["dnst"]);
+        let owner = Name::from_str("example.test").unwrap();
+        let key1 = KeyPair::generate(owner.clone(), KeyAlgorithm::Ed25519, KSK_FLAGS, 0).unwrap();
+        let key2 = KeyPair::generate(owner, KeyAlgorithm::Ed25519, KSK_FLAGS, 0).unwrap();
+
+        let base = Path::new("Kmismatch");
+        cmd.file(
+            super::with_suffix(base, ".key"),
+            key1.display_public().to_string(),
+        );
+        cmd.file(
+            super::with_suffix(base, ".private"),
+            key2.display_private().to_string(),
+        );
+
+        assert!(KeyPair::load(&cmd.env(), base).is_err());
+    }
+}
@@ -2,16 +2,24 @@ use std::ffi::OsString;
 use std::path::Path;
 
 use clap::Parser;
-use commands::{nsec3hash::Nsec3Hash, LdnsCommand};
+use commands::LdnsTool;
 use env::Env;
 use error::Error;
 
-pub use self::args::Args;
+pub use self::args::{Args, OutputFormat};
 
 pub mod args;
+pub mod chain;
 pub mod commands;
 pub mod env;
 pub mod error;
+pub mod keys;
+pub mod message;
+pub mod tsig;
+pub mod zone;
+
+#[cfg(test)]
+mod golden;
 
 pub fn try_ldns_compatibility<I: IntoIterator<Item = OsString>>(
     args: I,
@@ -25,12 +33,11 @@ pub fn try_ldns_compatibility<I: IntoIterator<Item = OsString>>(
         .to_str()
         .ok_or("Binary file name is not valid unicode")?;
 
-    let res = match binary_name {
-        "ldns-nsec3-hash" => Nsec3Hash::parse_ldns_args(args_iter),
-        _ => return Ok(None),
+    let Some(tool) = LdnsTool::find(binary_name) else {
+        return Ok(None);
     };
 
-    res.map(Some)
+    (tool.parse_args)(args_iter.collect()).map(Some)
 }
 
 fn parse_args(env: impl Env) -> Result<Args, Error> {
@@ -41,13 +48,127 @@ fn parse_args(env: impl Env) -> Result<Args, Error> {
     Ok(args)
 }
 
+/// The usage of the `ldns` binary
+const LDNS_USAGE: &str = "\
+usage: ldns <TOOL> [ARGS]...
+       ldns <TOOL> --help
+       ldns --list
+
+Runs the ldns tool TOOL, e.g. ldns-nsec3-hash, as if dnst was invoked
+under its name.\
+";
+
+/// Parse the arguments of the `ldns` binary
+///
+/// Returns `None` if the arguments asked for help or the list of tools,
+/// which has then been printed.
+fn parse_ldns_binary_args(env: impl Env) -> Result<Option<Args>, Error> {
+    let mut args = env.args_os().skip(1);
+    let Some(tool) = args.next() else {
+        return Err(format!("missing ldns tool\n\n{LDNS_USAGE}").into());
+    };
+    let tool = tool.to_str().ok_or("ldns tool name is not valid unicode")?;
+
+    match tool {
+        "-h" | "--help" => writeln!(env.stdout(), "{LDNS_USAGE}"),
+        "--list" => {
+            for tool in commands::LDNS_TOOLS {
+                writeln!(env.stdout(), "{}", tool.name);
+            }
+        }
+        _ => {
+            let tool = LdnsTool::find_or_err(tool)?;
+            let args: Vec<_> = args.collect();
+            if let [arg] = &args[..] {
+                if arg == "-h" || arg == "--help" {
+                    writeln!(env.stdout(), "{}", tool.help);
+                    return Ok(None);
+                }
+            }
+            return (tool.parse_args)(args).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// Run the `ldns` binary, which takes the name of the ldns tool to run as
+/// its first argument
+pub fn run_ldns(env: impl Env) -> u8 {
+    let res = parse_ldns_binary_args(&env).and_then(|args| match args {
+        Some(args) => args.execute(&env),
+        None => Ok(()),
+    });
+    match res {
+        Ok(()) => 0,
+        Err(err) => {
+            err.pretty_print(&env, OutputFormat::Text);
+            err.exit_code()
+        }
+    }
+}
+
 pub fn run(env: impl Env) -> u8 {
-    let res = parse_args(&env).and_then(|args| args.execute(&env));
+    // Errors in the arguments themselves are printed as text, because the
+    // format is not known yet.
+    let mut format = OutputFormat::Text;
+    let res = parse_args(&env).and_then(|args| {
+        format = args.format;
+        args.execute(&env)
+    });
     match res {
         Ok(()) => 0,
         Err(err) => {
-            err.pretty_print(&env);
+            err.pretty_print(&env, format);
             err.exit_code()
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::commands::LDNS_TOOLS;
+    use crate::env::fake::FakeCmd;
+
+    #[test]
+    fn ldns_tools() {
+        // The tools are sorted and each is found by its own name.
+        assert!(LDNS_TOOLS.windows(2).all(|w| w[0].name < w[1].name));
+        for tool in LDNS_TOOLS {
+            let res = FakeCmd::new([tool.name, "--bogus-option"]).run();
+            assert_eq!(res.exit_code, 1, "{}", tool.name);
+            assert!(res.stderr.contains(tool.help), "{}", tool.name);
+        }
+    }
+
+    #[test]
+    fn ldns_binary() {
+        let res = FakeCmd::new(["ldns", "--list"]).run_ldns();
+        assert_eq!(res.exit_code, 0);
+        assert!(res.stdout.starts_with("drill\nldns-compare-zones\n"));
+        assert_eq!(res.stdout.lines().count(), LDNS_TOOLS.len());
+
+        let res = FakeCmd::new(["ldns", "ldns-nsec3-hash", "--help"]).run_ldns();
+        assert_eq!(res.exit_code, 0);
+        assert!(res
+            .stdout
+            .starts_with("ldns-nsec3-hash [OPTIONS] <domain name>\n"));
+
+        let res = FakeCmd::new(["ldns", "ldns-nsec3-hash", "-t", "0", "example.test"]).run_ldns();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        assert_eq!(res.stdout, "jbas736chung3bb701jkjdhqkqlhvug7.\n");
+
+        let res = FakeCmd::new(["ldns", "ldns-bogus"]).run_ldns();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains(
+            "unknown ldns tool `ldns-bogus`, the supported tools are: drill, ldns-compare-zones, "
+        ));
+
+        let res = FakeCmd::new(["ldns"]).run_ldns();
+        assert_eq!(res.exit_code, 1);
+        assert!(res.stderr.contains("missing ldns tool"));
+
+        let res = FakeCmd::new(["ldns", "--help"]).run_ldns();
+        assert_eq!(res.exit_code, 0);
+        assert!(res.stdout.starts_with("usage: ldns <TOOL> [ARGS]...\n"));
+    }
+}
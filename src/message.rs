@@ -0,0 +1,166 @@
+//! Printing DNS messages received from servers.
+
+use bytes::Bytes;
+use domain::base::iana::Rtype;
+use domain::base::message::RecordSection;
+use domain::base::name::ParsedName;
+use domain::base::{Header, Message};
+use domain::rdata::AllRecordData;
+
+use crate::env::Stream;
+
+/// Print a DNS message in the format used by the ldns tools
+///
+/// Like ldns, the OPT record is not printed as part of the additional
+/// section. Use [`print_edns`] to print its contents.
+pub fn print_message(out: &mut Stream<impl std::fmt::Write>, msg: &Message<Bytes>) {
+    let header = msg.header();
+    let counts = msg.header_counts();
+
+    writeln!(
+        out,
+        ";; ->>HEADER<<- opcode: {}, rcode: {}, id: {}",
+        header.opcode(),
+        header.rcode(),
+        header.id()
+    );
+
+    let flags = header_flags(header);
+    writeln!(
+        out,
+        ";; flags: {} ; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {} ",
+        flags.join(" "),
+        counts.qdcount(),
+        counts.ancount(),
+        counts.nscount(),
+        counts.arcount()
+    );
+
+    writeln!(out, ";; QUESTION SECTION:");
+    for question in msg.question().flatten() {
+        writeln!(out, ";; {question}");
+    }
+
+    let Some(sections) = record_sections(msg) else {
+        return;
+    };
+
+    for (name, section) in sections {
+        writeln!(out, "\n;; {name} SECTION:");
+        let Some(section) = section else {
+            continue;
+        };
+        for record in section.limit_to::<AllRecordData<_, ParsedName<_>>>() {
+            let Ok(record) = record else {
+                continue;
+            };
+            if record.rtype() == Rtype::OPT {
+                continue;
+            }
+            writeln!(
+                out,
+                "{}.\t{}\t{}\t{}\t{}",
+                record.owner(),
+                record.ttl().as_secs(),
+                record.class(),
+                record.rtype(),
+                record.data()
+            );
+        }
+    }
+    writeln!(out);
+}
+
+/// Print the EDNS data of a DNS message, if it has an OPT record
+pub fn print_edns(out: &mut Stream<impl std::fmt::Write>, msg: &Message<Bytes>) {
+    let Some(opt) = msg.opt() else {
+        return;
+    };
+    let flags = if opt.dnssec_ok() { "do " } else { "" };
+    writeln!(
+        out,
+        ";; EDNS: version {}; flags: {flags}; udp: {}",
+        opt.version(),
+        opt.udp_payload_size()
+    );
+}
+
+/// A DNS message as a JSON object, with the same fields as [`print_message`]
+///
+/// The EDNS data, if any, is included as the `edns` field.
+pub fn message_json(msg: &Message<Bytes>) -> serde_json::Value {
+    let header = msg.header();
+    let mut json = serde_json::json!({
+        "id": header.id(),
+        "opcode": header.opcode().to_string(),
+        "rcode": header.rcode().to_string(),
+        "flags": header_flags(header),
+        "question": msg.question().flatten().map(|q| serde_json::json!({
+            "name": format!("{}.", q.qname()),
+            "class": q.qclass().to_string(),
+            "type": q.qtype().to_string(),
+        })).collect::<Vec<_>>(),
+    });
+
+    for (name, section) in record_sections(msg).into_iter().flatten() {
+        let records: Vec<_> = section
+            .into_iter()
+            .flat_map(|s| s.limit_to::<AllRecordData<_, ParsedName<_>>>())
+            .flatten()
+            .filter(|record| record.rtype() != Rtype::OPT)
+            .map(|record| {
+                serde_json::json!({
+                    "owner": format!("{}.", record.owner()),
+                    "ttl": record.ttl().as_secs(),
+                    "class": record.class().to_string(),
+                    "type": record.rtype().to_string(),
+                    "data": record.data().to_string(),
+                })
+            })
+            .collect();
+        json[name.to_lowercase()] = records.into();
+    }
+
+    if let Some(opt) = msg.opt() {
+        json["edns"] = serde_json::json!({
+            "version": opt.version(),
+            "dnssec_ok": opt.dnssec_ok(),
+            "udp": opt.udp_payload_size(),
+        });
+    }
+    json
+}
+
+/// The names of the flags that are set in a message header
+fn header_flags(header: Header) -> Vec<&'static str> {
+    [
+        ("qr", header.qr()),
+        ("aa", header.aa()),
+        ("tc", header.tc()),
+        ("rd", header.rd()),
+        ("ra", header.ra()),
+        ("ad", header.ad()),
+        ("cd", header.cd()),
+    ]
+    .into_iter()
+    .filter_map(|(name, set)| set.then_some(name))
+    .collect()
+}
+
+/// A record section with its name, if it could be parsed
+type NamedSection<'a> = (&'static str, Option<RecordSection<'a, Bytes>>);
+
+/// The record sections of a message, by name
+///
+/// Returns `None` if the question section cannot be parsed. A section is
+/// `None` if it or a section before it cannot be parsed.
+fn record_sections(msg: &Message<Bytes>) -> Option<[NamedSection<'_>; 3]> {
+    let answer = msg.answer().ok()?;
+    let authority = answer.next_section().ok().flatten();
+    let additional = authority.and_then(|s| s.next_section().ok().flatten());
+    Some([
+        ("ANSWER", Some(answer)),
+        ("AUTHORITY", authority),
+        ("ADDITIONAL", additional),
+    ])
+}
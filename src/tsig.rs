@@ -0,0 +1,75 @@
+//! TSIG keys given on the command line.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use domain::base::Name;
+use domain::tsig::{Algorithm, Key, KeyName};
+use domain::utils::base64;
+
+use crate::error::Error;
+
+/// A TSIG key in the `name:secret[:algorithm]` format used by the ldns tools
+///
+/// The secret is base64 encoded. Because `hmac-md5` is not supported, the
+/// algorithm defaults to `hmac-sha256`.
+#[derive(Clone, Debug)]
+pub struct TsigKey {
+    name: Name<Vec<u8>>,
+    secret: Vec<u8>,
+    algorithm: Algorithm,
+}
+
+impl TsigKey {
+    /// Parse a key given to an ldns tool
+    ///
+    /// The ldns tools default to `hmac-md5`, which is not supported. Rather
+    /// than silently using another algorithm, the algorithm must be given.
+    pub fn from_ldns_str(s: &str) -> Result<Self, Error> {
+        if s.splitn(3, ':').nth(2).is_none() {
+            return Err("the default algorithm hmac-md5 is not supported, \
+                        give another one as <name:secret:algorithm>, e.g. hmac-sha256"
+                .into());
+        }
+        Self::from_str(s)
+    }
+
+    /// Create the key for signing messages
+    pub fn to_key(&self) -> Result<Arc<Key>, Error> {
+        let name = KeyName::from_str(&self.name.to_string())
+            .map_err(|e| format!("invalid TSIG key name: {e}"))?;
+        let key = Key::new(self.algorithm, &self.secret, name, None, None)
+            .map_err(|e| format!("invalid TSIG key: {e}"))?;
+        Ok(Arc::new(key))
+    }
+}
+
+impl FromStr for TsigKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':');
+        let (Some(name), Some(secret)) = (parts.next(), parts.next()) else {
+            return Err("expected <name:secret[:algorithm]>".into());
+        };
+
+        let name = Name::from_str(name).map_err(|e| format!("invalid key name: {e}"))?;
+        let secret = base64::decode(secret).map_err(|e| format!("invalid secret: {e}"))?;
+        let algorithm = match parts.next() {
+            None => Algorithm::Sha256,
+            Some(alg) => {
+                // Algorithms are often given as a domain name, so we allow a
+                // trailing dot.
+                let alg = alg.trim_end_matches('.').to_lowercase();
+                Algorithm::from_str(&alg)
+                    .map_err(|_| format!("unsupported TSIG algorithm: {alg}"))?
+            }
+        };
+
+        Ok(Self {
+            name,
+            secret,
+            algorithm,
+        })
+    }
+}
@@ -0,0 +1,460 @@
+//! Reading and printing zone files.
+
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+use bytes::Bytes;
+use domain::base::cmp::CanonicalOrd;
+use domain::base::iana::Rtype;
+use domain::base::name::FlattenInto;
+use domain::base::{Name, Record, ToName};
+use domain::rdata::dnssec::Timestamp;
+use domain::rdata::ZoneRecordData;
+use domain::utils::{base32, base64};
+use domain::zonefile::inplace::{Entry, Zonefile};
+
+use crate::args::OutputFormat;
+use crate::env::Env;
+use crate::error::{Context, Error};
+
+/// A domain name as read from a zone file.
+pub type ZoneName = Name<Bytes>;
+
+/// A record as read from a zone file.
+pub type ZoneRecord = Record<ZoneName, ZoneRecordData<Bytes, ZoneName>>;
+
+/// The maximum depth of nested `$INCLUDE` directives
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Read all records from a zone file.
+///
+/// The `origin` is used for relative names until the zone file sets its own
+/// origin with `$ORIGIN`. Files included with `$INCLUDE` are read relative
+/// to the current directory, like BIND and ldns do.
+pub fn read_zone_file(
+    env: &impl Env,
+    path: &Path,
+    origin: Option<ZoneName>,
+) -> Result<Vec<ZoneRecord>, Error> {
+    let mut records = Vec::new();
+    read_zone_file_into(env, path, origin, 0, &mut records)?;
+    Ok(records)
+}
+
+/// Read all records from a zone file given as a reader.
+///
+/// The `name` is used in error messages, e.g., `-` for stdin.
+pub fn read_zone(
+    env: &impl Env,
+    reader: &mut impl Read,
+    name: &str,
+    origin: Option<ZoneName>,
+) -> Result<Vec<ZoneRecord>, Error> {
+    let mut records = Vec::new();
+    read_zone_into(env, reader, name, origin, 0, &mut records)
+        .with_context(|| format!("reading {name}"))?;
+    Ok(records)
+}
+
+fn read_zone_file_into(
+    env: &impl Env,
+    path: &Path,
+    origin: Option<ZoneName>,
+    depth: usize,
+    records: &mut Vec<ZoneRecord>,
+) -> Result<(), Error> {
+    let name = path.display().to_string();
+    env.open(path)
+        .map_err(|e| format!("could not open file: {e}").into())
+        .and_then(|mut file| read_zone_into(env, &mut file, &name, origin, depth, records))
+        .with_context(|| format!("reading {name}"))
+}
+
+fn read_zone_into(
+    env: &impl Env,
+    reader: &mut impl Read,
+    name: &str,
+    origin: Option<ZoneName>,
+    depth: usize,
+    records: &mut Vec<ZoneRecord>,
+) -> Result<(), Error> {
+    let mut data = Vec::new();
+    reader
+        .read_to_end(&mut data)
+        .map_err(|e| format!("could not read file: {e}"))?;
+
+    let mut zonefile = Zonefile::with_capacity(data.len());
+    if let Some(origin) = origin {
+        zonefile.set_origin(origin);
+    }
+
+    // The zone file is fed to the parser one entry at a time, so that errors
+    // can point at the line where the failing entry starts. The parser itself
+    // reports the position it reached, which is often the line after it.
+    for (line, entry) in split_entries(&data) {
+        zonefile.extend_from_slice(entry);
+        // The parser needs the last entry to end in a line feed.
+        if !entry.ends_with(b"\n") {
+            zonefile.extend_from_slice(b"\n");
+        }
+        while let Some(entry) = zonefile.next_entry().map_err(|e| {
+            // Strip the position of the parser from the message.
+            let e = e.to_string();
+            let msg = e.split_once(": ").map_or(e.as_str(), |(_, msg)| msg);
+            format!("parse error at {name}:{line}: {msg}")
+        })? {
+            match entry {
+                Entry::Record(record) => records.push(record.flatten_into()),
+                Entry::Include { path, origin } => {
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        return Err("too many nested $INCLUDE directives".into());
+                    }
+                    let origin = origin.or_else(|| zonefile.origin().ok());
+                    read_zone_file_into(env, Path::new(path.as_str()), origin, depth + 1, records)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Split zone file data into entries with the line numbers they start at
+///
+/// An entry ends at a line feed that is not in parentheses, quotes or
+/// escaped. Each entry includes its line feed.
+fn split_entries(data: &[u8]) -> Vec<(usize, &[u8])> {
+    let mut entries = Vec::new();
+    let mut start = 0;
+    let mut line = 1;
+    let mut start_line = 1;
+    let mut parens = 0usize;
+    let mut quoted = false;
+    let mut comment = false;
+    let mut escaped = false;
+
+    for (i, &ch) in data.iter().enumerate() {
+        if ch == b'\n' {
+            line += 1;
+            comment = false;
+        }
+        match ch {
+            _ if escaped => escaped = false,
+            _ if comment => {}
+            b'\\' => escaped = true,
+            b'"' => quoted = !quoted,
+            _ if quoted => {}
+            b';' => comment = true,
+            b'(' => parens += 1,
+            b')' => parens = parens.saturating_sub(1),
+            b'\n' if parens == 0 => {
+                entries.push((start_line, &data[start..=i]));
+                start = i + 1;
+                start_line = line;
+            }
+            _ => {}
+        }
+    }
+
+    if start < data.len() {
+        entries.push((start_line, &data[start..]));
+    }
+    entries
+}
+
+/// Parse a single record in zone file format
+///
+/// Relative names are relative to `origin`. If the TTL or class are not
+/// given, they default to 3600 and IN.
+pub fn parse_record(s: &str, origin: &ZoneName) -> Result<ZoneRecord, Error> {
+    let mut zonefile = Zonefile::new();
+    zonefile.extend_from_slice(s.as_bytes());
+    zonefile.extend_from_slice(b"\n");
+    zonefile.set_origin(origin.clone());
+
+    let entry = zonefile
+        .next_entry()
+        .map_err(|e| format!("invalid record '{s}': {e}"))?;
+    match entry {
+        Some(Entry::Record(record)) => Ok(record.flatten_into()),
+        _ => Err(format!("invalid record '{s}'").into()),
+    }
+}
+
+/// Parse a domain name that may be relative to `origin`
+pub fn parse_name(s: &str, origin: &ZoneName) -> Result<ZoneName, Error> {
+    let name = if s == "@" {
+        return Ok(origin.clone());
+    } else if s.ends_with('.') || origin.is_root() {
+        ZoneName::from_str(s)
+    } else {
+        ZoneName::from_str(&format!("{s}.{origin}"))
+    };
+    name.map_err(|e| format!("invalid domain name '{s}': {e}").into())
+}
+
+/// Display a record in the format used by the ldns tools
+///
+/// This is mostly the regular presentation format, separated by tabs, but
+/// DNSSEC related records use numbers for algorithms and dates for
+/// signature times.
+pub fn display_record(record: &ZoneRecord) -> impl fmt::Display + '_ {
+    DisplayRecord(record)
+}
+
+/// Display a record in the given output format
+///
+/// This is [`display_record`] for text and [`record_json`] for JSON.
+pub fn display_record_as(record: &ZoneRecord, format: OutputFormat) -> impl fmt::Display + '_ {
+    DisplayRecordAs(record, format)
+}
+
+struct DisplayRecordAs<'a>(&'a ZoneRecord, OutputFormat);
+
+impl fmt::Display for DisplayRecordAs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.1 {
+            OutputFormat::Text => DisplayRecord(self.0).fmt(f),
+            OutputFormat::Json => record_json(self.0).fmt(f),
+        }
+    }
+}
+
+/// A record as a JSON object, with the same fields as [`display_record`]
+pub fn record_json(record: &ZoneRecord) -> serde_json::Value {
+    serde_json::json!({
+        "owner": record.owner().fmt_with_dot().to_string(),
+        "ttl": record.ttl().as_secs(),
+        "class": record.class().to_string(),
+        "type": record.rtype().to_string(),
+        "data": DisplayData(record.data()).to_string(),
+    })
+}
+
+struct DisplayRecord<'a>(&'a ZoneRecord);
+
+impl fmt::Display for DisplayRecord<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let record = self.0;
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t",
+            record.owner().fmt_with_dot(),
+            record.ttl().as_secs(),
+            record.class(),
+            record.rtype()
+        )?;
+        DisplayData(record.data()).fmt(f)
+    }
+}
+
+/// Display record data in the format used by the ldns tools
+struct DisplayData<'a>(&'a ZoneRecordData<Bytes, ZoneName>);
+
+impl fmt::Display for DisplayData<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            ZoneRecordData::Dnskey(dnskey) => {
+                write!(
+                    f,
+                    "{} {} {} ",
+                    dnskey.flags(),
+                    dnskey.protocol(),
+                    dnskey.algorithm().to_int()
+                )?;
+                base64::display(dnskey.public_key(), f)
+            }
+            ZoneRecordData::Rrsig(rrsig) => {
+                write!(
+                    f,
+                    "{} {} {} {} {} {} {} {} ",
+                    rrsig.type_covered(),
+                    rrsig.algorithm().to_int(),
+                    rrsig.labels(),
+                    rrsig.original_ttl().as_secs(),
+                    DisplayTimestamp(rrsig.expiration()),
+                    DisplayTimestamp(rrsig.inception()),
+                    rrsig.key_tag(),
+                    rrsig.signer_name().fmt_with_dot(),
+                )?;
+                base64::display(rrsig.signature(), f)
+            }
+            ZoneRecordData::Ds(ds) => {
+                write!(
+                    f,
+                    "{} {} {} ",
+                    ds.key_tag(),
+                    ds.algorithm().to_int(),
+                    ds.digest_type().to_int()
+                )?;
+                ds.digest().iter().try_for_each(|b| write!(f, "{b:02x}"))
+            }
+            ZoneRecordData::Nsec3(nsec3) => {
+                write!(
+                    f,
+                    "{} {} {} {} ",
+                    nsec3.hash_algorithm().to_int(),
+                    nsec3.flags(),
+                    nsec3.iterations(),
+                    DisplaySalt(nsec3.salt().as_slice()),
+                )?;
+                let next = base32::encode_string_hex(nsec3.next_owner().as_slice());
+                write!(f, "{}", next.to_lowercase())?;
+                for rtype in nsec3.types().iter() {
+                    write!(f, " {rtype}")?;
+                }
+                Ok(())
+            }
+            ZoneRecordData::Nsec3param(param) => write!(
+                f,
+                "{} {} {} {}",
+                param.hash_algorithm().to_int(),
+                param.flags(),
+                param.iterations(),
+                DisplaySalt(param.salt().as_slice()),
+            ),
+            data => write!(f, "{data}"),
+        }
+    }
+}
+
+/// Display a signature time as `YYYYMMDDHHmmSS`
+struct DisplayTimestamp(Timestamp);
+
+impl fmt::Display for DisplayTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Ok(time) = time::OffsetDateTime::from_unix_timestamp(self.0.into_int().into()) else {
+            return write!(f, "{}", self.0);
+        };
+        write!(
+            f,
+            "{:04}{:02}{:02}{:02}{:02}{:02}",
+            time.year(),
+            u8::from(time.month()),
+            time.day(),
+            time.hour(),
+            time.minute(),
+            time.second()
+        )
+    }
+}
+
+/// Display an NSEC3 salt in lowercase hex or `-` if it is empty
+struct DisplaySalt<'a>(&'a [u8]);
+
+impl fmt::Display for DisplaySalt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("-");
+        }
+        self.0.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+/// Whether a record type is one that is added when signing a zone
+pub fn is_dnssec_type(rtype: Rtype) -> bool {
+    matches!(
+        rtype,
+        Rtype::RRSIG | Rtype::NSEC | Rtype::NSEC3 | Rtype::NSEC3PARAM
+    )
+}
+
+/// Whether `name` is at or below `apex`
+pub fn is_in_zone(name: &impl ToName, apex: &impl ToName) -> bool {
+    name.ends_with(apex)
+}
+
+/// Sort records into canonical order and remove duplicates.
+pub fn sort_records(records: &mut Vec<ZoneRecord>) {
+    records.sort_by(|a, b| a.canonical_cmp(b));
+    records.dedup_by(|a, b| a.canonical_cmp(b).is_eq());
+}
+
+//------------ Node ----------------------------------------------------------
+
+/// All records of a zone for a single owner name, grouped into RRsets.
+pub struct Node {
+    pub owner: ZoneName,
+    pub kind: NodeKind,
+
+    /// The RRsets at this name, ordered by record type
+    pub rrsets: Vec<Vec<ZoneRecord>>,
+}
+
+/// The role of a name within a zone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    /// The apex of the zone.
+    Apex,
+
+    /// A name with authoritative data.
+    Authoritative,
+
+    /// A delegation point, i.e., a name with NS records below the apex.
+    Delegation,
+
+    /// A name below a delegation point, e.g., for glue records.
+    Occluded,
+}
+
+impl Node {
+    pub fn rtypes(&self) -> impl Iterator<Item = Rtype> + '_ {
+        self.rrsets.iter().map(|rrset| rrset[0].rtype())
+    }
+
+    pub fn rrset(&self, rtype: Rtype) -> Option<&[ZoneRecord]> {
+        self.rrsets
+            .iter()
+            .find(|rrset| rrset[0].rtype() == rtype)
+            .map(Vec::as_slice)
+    }
+
+    pub fn has_rtype(&self, rtype: Rtype) -> bool {
+        self.rrset(rtype).is_some()
+    }
+}
+
+/// Group records into nodes.
+///
+/// The records must be sorted in canonical order, as done by
+/// [`sort_records`], and all be in the zone below `apex`.
+pub fn group_nodes(records: Vec<ZoneRecord>, apex: &ZoneName) -> Vec<Node> {
+    let mut nodes: Vec<Node> = Vec::new();
+
+    for record in records {
+        match nodes.last_mut() {
+            Some(node) if node.owner.name_eq(record.owner()) => match node.rrsets.last_mut() {
+                Some(rrset) if rrset[0].rtype() == record.rtype() => rrset.push(record),
+                _ => node.rrsets.push(vec![record]),
+            },
+            _ => nodes.push(Node {
+                owner: record.owner().clone(),
+                kind: NodeKind::Authoritative,
+                rrsets: vec![vec![record]],
+            }),
+        }
+    }
+
+    // In canonical order, all names below a delegation directly follow it.
+    let mut cut: Option<ZoneName> = None;
+    for node in &mut nodes {
+        if let Some(c) = &cut {
+            if node.owner.ends_with(c) {
+                node.kind = NodeKind::Occluded;
+                continue;
+            }
+            cut = None;
+        }
+
+        if node.owner.name_eq(apex) {
+            node.kind = NodeKind::Apex;
+        } else if node.has_rtype(Rtype::NS) {
+            node.kind = NodeKind::Delegation;
+            cut = Some(node.owner.clone());
+        }
+    }
+
+    nodes
+}